@@ -8,6 +8,7 @@ use bevy::{
 };
 use bevy_prototype_lyon::prelude::ShapePlugin;
 use systems::*;
+pub use utils::build_arrow;
 
 pub struct ArrowPlugin;
 
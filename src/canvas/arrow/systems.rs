@@ -5,7 +5,7 @@ use super::events::{CreateArrow, RedrawArrow};
 use super::utils::{build_arrow, create_arrow};
 use crate::themes::Theme;
 use crate::ui_plugin::ui_helpers::VeloNode;
-use crate::ui_plugin::{NodeInteraction, UiState};
+use crate::ui_plugin::{CanvasEdit, NodeInteraction, UiState};
 use bevy_prototype_lyon::prelude::Path;
 
 pub fn create_arrow_start(
@@ -14,6 +14,7 @@ pub fn create_arrow_start(
     mut state: ResMut<UiState>,
     mut create_arrow: EventWriter<CreateArrow>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     let mut primary_window = windows.single_mut();
     for event in node_interaction_events.iter() {
@@ -35,6 +36,9 @@ pub fn create_arrow_start(
                                 end: *arrow_connect,
                                 arrow_type: state.arrow_type,
                             });
+                            canvas_edits.send(CanvasEdit {
+                                ids: vec![start_arrow.id, arrow_connect.id],
+                            });
                         }
                         None => {
                             state.arrow_to_draw_start = Some(*arrow_connect);
@@ -67,33 +71,37 @@ pub fn create_arrow_end(
             if *arrow_connect == event.end {
                 end = Some(global_transform.affine().translation.truncate());
             }
-            if let (Some(start), Some(end)) = (start, end) {
-                let mut max_z = 0.1;
-                for (transform, velo_node) in velo_nodes.iter() {
-                    if velo_node.id == event.start.id {
-                        max_z = f32::max(max_z, transform.translation.z);
-                    }
-                    if velo_node.id == event.end.id {
-                        max_z = f32::max(max_z, transform.translation.z);
-                    }
-                }
-
-                create_arrow(
-                    &mut commands,
-                    &theme,
-                    start,
-                    end,
-                    max_z,
-                    ArrowMeta {
-                        visible: event.visible,
-                        start: event.start,
-                        end: event.end,
-                        arrow_type: event.arrow_type,
-                    },
-                );
-                break;
+        }
+        let (Some(start), Some(end)) = (start, end) else {
+            warn!(
+                "Arrow from {:?} to {:?} is not drawn, a node it connects is missing",
+                event.start.id, event.end.id
+            );
+            continue;
+        };
+        let mut max_z = 0.1;
+        for (transform, velo_node) in velo_nodes.iter() {
+            if velo_node.id == event.start.id {
+                max_z = f32::max(max_z, transform.translation.z);
+            }
+            if velo_node.id == event.end.id {
+                max_z = f32::max(max_z, transform.translation.z);
             }
         }
+
+        create_arrow(
+            &mut commands,
+            &theme,
+            start,
+            end,
+            max_z,
+            ArrowMeta {
+                visible: event.visible,
+                start: event.start,
+                end: event.end,
+                arrow_type: event.arrow_type,
+            },
+        );
     }
 }
 pub fn redraw_arrows(
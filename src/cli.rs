@@ -0,0 +1,394 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bevy_pkv::PkvStore;
+use tantivy::Index;
+
+use crate::components::{Doc, Tab};
+use crate::document::{
+    check_doc, fuzzy_search, is_bundle, load_doc_index, load_stored_doc, merge_docs, read_doc_file,
+    repair_doc, TabSnapshot, DOC_SCHEMA_VERSION,
+};
+use crate::export::{write_doc, RasterFonts};
+use crate::import::diagram_to_snapshot;
+use crate::themes::{get_theme_by_name, Theme};
+use crate::utils::{data_dir, ReflectableUuid};
+use crate::{APP_NAME, ORG_NAME};
+
+pub const USAGE: &str = "\
+Usage: velo-cli <command> [options]
+
+Commands:
+  convert <input> <output>    Convert a document, bundle or diagram. The output
+                              format follows the extension: .json, .velo, .svg,
+                              .png, .md, .mmd, .dot, .canvas or .opml
+  export <input> <output>     Like convert, for the tab picked with --tab
+  validate <file>...          Check that files load as velo documents without
+                              broken references
+  merge <mine> <theirs> <output>
+                              Merge two copies of the same document. Nodes
+                              changed differently in both keep the side of
+                              <mine> and are listed as conflicts
+  list                        List the documents velo has stored
+  search <query>              Fuzzy search the text of stored documents
+
+Options:
+  --tab <name or number>      Tab to export, the active tab by default
+  --theme <light or dark>     Colors of rendered exports, light by default
+  --data-dir <dir>            Where velo keeps its documents and search index
+  --repair                    Let validate fix the problems it finds and write
+                              the files back
+";
+
+#[derive(Default)]
+struct Options {
+    arguments: Vec<String>,
+    tab: Option<String>,
+    theme: Option<String>,
+    data_dir: Option<PathBuf>,
+    repair: bool,
+}
+
+/// Run the command line tool with the arguments after the program name.
+/// Errors come back as the message to show, results are written to `out`.
+pub fn run(args: &[String], out: &mut impl Write) -> Result<(), String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Err(USAGE.to_string());
+    };
+    let options = parse_options(rest)?;
+    let theme = get_theme_by_name(options.theme.as_deref().unwrap_or("light"));
+    match (command.as_str(), options.arguments.as_slice()) {
+        ("convert", [input, output]) => convert(input, output, None, &theme),
+        ("export", [input, output]) => convert(input, output, options.tab.as_deref(), &theme),
+        ("validate", files) if !files.is_empty() => validate(files, options.repair, &theme, out),
+        ("merge", [local, remote, output]) => merge(local, remote, output, &theme, out),
+        ("list", []) => list(&open_store(&options), out),
+        ("search", [query]) => search(query, &options, out),
+        ("help" | "--help" | "-h", _) => write(out, USAGE),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--tab" => options.tab = Some(value()?),
+            "--theme" => options.theme = Some(value()?),
+            "--data-dir" => options.data_dir = Some(PathBuf::from(value()?)),
+            "--repair" => options.repair = true,
+            option if option.starts_with("--") => {
+                return Err(format!("unknown option {}\n\n{}", option, USAGE))
+            }
+            _ => options.arguments.push(arg.clone()),
+        }
+    }
+    Ok(options)
+}
+
+/// Read a velo document, `.velo` bundle or any diagram format velo imports.
+/// Diagrams become a document with a single tab named after the file.
+pub fn read_doc(path: &Path, theme: &Theme) -> Result<Doc, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let diagram = std::str::from_utf8(&data)
+        .ok()
+        .filter(|_| !is_bundle(&data))
+        .and_then(|text| diagram_to_snapshot(text, theme));
+    match diagram {
+        Some(Ok(snapshot)) => {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Untitled".to_string());
+            Ok(doc_with_snapshot(name, snapshot))
+        }
+        Some(Err(e)) => Err(format!("{}: {}", path.display(), e)),
+        None => read_doc_file(&data).map_err(|e| format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn doc_with_snapshot(name: String, snapshot: TabSnapshot) -> Doc {
+    let z_index = snapshot.nodes.iter().map(|node| node.z).fold(1., f32::max);
+    Doc {
+        id: ReflectableUuid::generate(),
+        name,
+        tabs: vec![Tab {
+            id: ReflectableUuid::generate(),
+            name: "Tab 1".to_string(),
+            checkpoints: VecDeque::from([snapshot]),
+            is_active: true,
+            z_index,
+            versions: vec![],
+        }],
+        tags: vec![],
+        version: DOC_SCHEMA_VERSION,
+        images: Default::default(),
+    }
+}
+
+fn convert(input: &str, output: &str, tab: Option<&str>, theme: &Theme) -> Result<(), String> {
+    let mut doc = read_doc(Path::new(input), theme)?;
+    if let Some(tab) = tab {
+        let index = find_tab(&doc, tab).ok_or_else(|| format!("{} has no tab {}", input, tab))?;
+        for (i, tab) in doc.tabs.iter_mut().enumerate() {
+            tab.is_active = i == index;
+        }
+    }
+    write_doc(Path::new(output), &doc, theme, &mut RasterFonts::default())
+        .map_err(|e| format!("{}: {}", output, e))
+}
+
+/// Tab with the given name, or at the given position counting from 1.
+fn find_tab(doc: &Doc, tab: &str) -> Option<usize> {
+    doc.tabs.iter().position(|t| t.name == tab).or_else(|| {
+        let number = tab.parse::<usize>().ok()?;
+        (1..=doc.tabs.len()).contains(&number).then(|| number - 1)
+    })
+}
+
+fn validate(
+    files: &[String],
+    repair: bool,
+    theme: &Theme,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let mut invalid = 0;
+    for file in files {
+        let mut doc = match read_doc(Path::new(file), theme) {
+            Ok(doc) => doc,
+            Err(e) => {
+                invalid += 1;
+                write(out, &format!("{}\n", e))?;
+                continue;
+            }
+        };
+        let issues = match repair {
+            true => repair_doc(&mut doc),
+            false => check_doc(&doc),
+        };
+        let snapshots = doc.tabs.iter().filter_map(|tab| tab.checkpoints.back());
+        let (nodes, arrows) = snapshots.fold((0, 0), |(nodes, arrows), snapshot| {
+            (nodes + snapshot.nodes.len(), arrows + snapshot.arrows.len())
+        });
+        let status = match (issues.len(), repair) {
+            (0, _) => "ok".to_string(),
+            (count, true) => format!("repaired {} problems", count),
+            (count, false) => format!("{} problems", count),
+        };
+        write(
+            out,
+            &format!(
+                "{}: {}, {} tabs, {} nodes, {} arrows, {} images\n",
+                file,
+                status,
+                doc.tabs.len(),
+                nodes,
+                arrows,
+                doc.images.len()
+            ),
+        )?;
+        for issue in issues.iter() {
+            write(out, &format!("  {}\n", issue))?;
+        }
+        if issues.is_empty() {
+            continue;
+        }
+        if repair {
+            write_doc(Path::new(file), &doc, theme, &mut RasterFonts::default())
+                .map_err(|e| format!("{}: {}", file, e))?;
+        } else {
+            invalid += 1;
+        }
+    }
+    match invalid {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} files are invalid", invalid, files.len())),
+    }
+}
+
+fn merge(
+    local: &str,
+    remote: &str,
+    output: &str,
+    theme: &Theme,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let local_doc = read_doc(Path::new(local), theme)?;
+    let remote_doc = read_doc(Path::new(remote), theme)?;
+    if local_doc.id != remote_doc.id {
+        return Err(format!("{} and {} are different documents", local, remote));
+    }
+    let (merged, conflicts) = merge_docs(&local_doc, &remote_doc);
+    for conflict in conflicts.iter() {
+        write(out, &format!("conflict: {}\n", conflict))?;
+    }
+    write_doc(
+        Path::new(output),
+        &merged,
+        theme,
+        &mut RasterFonts::default(),
+    )
+    .map_err(|e| format!("{}: {}", output, e))
+}
+
+/// The store of the app. It can't be opened while velo is running.
+fn open_store(options: &Options) -> PkvStore {
+    match &options.data_dir {
+        Some(dir) => PkvStore::new_in_dir(dir),
+        None => PkvStore::new(ORG_NAME, APP_NAME),
+    }
+}
+
+fn list(pkv: &PkvStore, out: &mut impl Write) -> Result<(), String> {
+    let index = load_doc_index(pkv);
+    let mut docs: Vec<_> = index.names.iter().collect();
+    docs.sort_by(|a, b| a.1.cmp(b.1).then(a.0 .0.cmp(&b.0 .0)));
+    for (id, name) in docs {
+        let tags = index.tags.get(id).cloned().unwrap_or_default();
+        let tags = tags
+            .iter()
+            .map(|tag| format!(" #{}", tag))
+            .collect::<String>();
+        write(out, &format!("{}  {}{}\n", id.0, name, tags))?;
+    }
+    Ok(())
+}
+
+fn search(query: &str, options: &Options, out: &mut impl Write) -> Result<(), String> {
+    let dir = options.data_dir.clone().unwrap_or_else(data_dir);
+    let index = Index::open_in_dir(&dir)
+        .map_err(|e| format!("no search index in {}: {}", dir.display(), e))?;
+    let locations = fuzzy_search(&index, query).map_err(|e| e.to_string())?;
+    let pkv = open_store(options);
+    for location in locations {
+        let doc = load_stored_doc(&pkv, ReflectableUuid(location.doc_id)).and_then(Result::ok);
+        let tab = doc.as_ref().and_then(|doc| {
+            doc.tabs
+                .iter()
+                .find(|tab| tab.id == ReflectableUuid(location.tab_id))
+        });
+        let text = tab
+            .and_then(|tab| tab.checkpoints.back())
+            .and_then(|snapshot| {
+                snapshot
+                    .nodes
+                    .iter()
+                    .find(|node| node.id == location.node_id)
+            })
+            .map(|node| node.text.text.lines().next().unwrap_or_default().trim());
+        write(
+            out,
+            &format!(
+                "{} / {}: {}  ({})\n",
+                doc.as_ref().map_or("?", |doc| doc.name.as_str()),
+                tab.map_or("?", |tab| tab.name.as_str()),
+                text.unwrap_or_default(),
+                location.node_id
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+fn write(out: &mut impl Write, text: &str) -> Result<(), String> {
+    out.write_all(text.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::store_doc;
+
+    fn run_to_string(args: &[&str]) -> Result<String, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut out = vec![];
+        run(&args, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        std::fs::write(
+            path("doc.json"),
+            include_str!("document/fixtures/doc_v0.json"),
+        )
+        .unwrap();
+
+        run_to_string(&["convert", &path("doc.json"), &path("doc.velo")]).unwrap();
+        run_to_string(&["convert", &path("doc.velo"), &path("doc.mmd")]).unwrap();
+        let mermaid = std::fs::read_to_string(path("doc.mmd")).unwrap();
+        assert!(mermaid.starts_with("flowchart TD"));
+        run_to_string(&["export", &path("doc.mmd"), &path("tab.svg"), "--tab", "1"]).unwrap();
+        assert!(
+            run_to_string(&["export", &path("doc.json"), &path("x.svg"), "--tab", "9"]).is_err()
+        );
+
+        std::fs::write(path("broken.json"), "{\"tabs\": 1}").unwrap();
+        let report = run_to_string(&["validate", &path("doc.velo"), &path("broken.json")]);
+        assert!(report.is_err());
+        let report = run_to_string(&["validate", &path("doc.velo")]).unwrap();
+        assert!(report.ends_with("ok, 2 tabs, 2 nodes, 1 arrows, 1 images\n"));
+
+        let mut dangling =
+            read_doc(Path::new(&path("doc.json")), &get_theme_by_name("light")).unwrap();
+        let snapshot = dangling.tabs[0].checkpoints.back_mut().unwrap();
+        snapshot
+            .nodes
+            .retain(|node| node.id != snapshot.arrows[0].start.id.0);
+        std::fs::write(
+            path("dangling.json"),
+            serde_json::to_string(&dangling).unwrap(),
+        )
+        .unwrap();
+        assert!(run_to_string(&["validate", &path("dangling.json")]).is_err());
+        let report = run_to_string(&["validate", &path("dangling.json"), "--repair"]).unwrap();
+        assert!(report.contains("repaired 1 problems, 2 tabs, 1 nodes, 0 arrows"));
+        assert!(report.contains("has a missing end"));
+        run_to_string(&["validate", &path("dangling.json")]).unwrap();
+
+        let store = dir.path().join("store");
+        let mut doc = read_doc(Path::new(&path("doc.json")), &get_theme_by_name("light")).unwrap();
+        doc.tags = vec!["plans".to_string()];
+        store_doc(&mut PkvStore::new_in_dir(&store), &doc).unwrap();
+        let list = run_to_string(&["list", "--data-dir", &store.to_string_lossy()]).unwrap();
+        assert_eq!(list, format!("{}  {} #plans\n", doc.id.0, doc.name));
+
+        let mut theirs = doc.clone();
+        let mut snapshot = theirs.tabs[0].checkpoints.back().unwrap().clone();
+        snapshot.nodes[0].text.text = "theirs".to_string();
+        theirs.tabs[0].checkpoints.push_back(snapshot);
+        std::fs::write(path("theirs.json"), serde_json::to_string(&theirs).unwrap()).unwrap();
+        std::fs::write(path("mine.json"), serde_json::to_string(&doc).unwrap()).unwrap();
+        let report = run_to_string(&[
+            "merge",
+            &path("mine.json"),
+            &path("theirs.json"),
+            &path("merged.json"),
+        ])
+        .unwrap();
+        assert_eq!(report, "");
+        let merged =
+            read_doc(Path::new(&path("merged.json")), &get_theme_by_name("light")).unwrap();
+        let snapshot = merged.tabs[0].checkpoints.back().unwrap();
+        assert!(snapshot.nodes.iter().any(|node| node.text.text == "theirs"));
+        assert!(run_to_string(&[
+            "merge",
+            &path("mine.json"),
+            &path("doc.mmd"),
+            &path("x.json")
+        ])
+        .is_err());
+
+        assert!(run_to_string(&["convert", &path("doc.json")]).is_err());
+        assert!(run_to_string(&["list", "--colour", "red"]).is_err());
+    }
+}
@@ -1,7 +1,8 @@
+use crate::document::{deserialize_checkpoints, TabSnapshot, TabVersion};
 use crate::utils::ReflectableUuid;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 #[derive(Component)]
 pub struct MainCamera;
 
@@ -13,8 +14,12 @@ pub struct Tab {
     pub is_active: bool,
     pub id: ReflectableUuid,
     pub name: String,
-    pub checkpoints: VecDeque<String>,
+    #[serde(deserialize_with = "deserialize_checkpoints")]
+    pub checkpoints: VecDeque<TabSnapshot>,
     pub z_index: f32,
+    /// Named versions, oldest first.
+    #[serde(default)]
+    pub versions: Vec<TabVersion>,
 }
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
@@ -23,4 +28,9 @@ pub struct Doc {
     pub id: ReflectableUuid,
     pub name: String,
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub version: u32,
+    /// Base64 encoded PNG images keyed by content hash, shared by all checkpoints.
+    #[serde(default)]
+    pub images: HashMap<String, String>,
 }
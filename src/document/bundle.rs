@@ -0,0 +1,138 @@
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::components::Doc;
+
+use super::{
+    doc_from_json, doc_to_canonical_json, read_zip, DocError, ZipWriter, DOC_SCHEMA_VERSION,
+};
+
+/// Layout version of `.velo` bundles, independent of the document schema inside them.
+pub const BUNDLE_VERSION: u32 = 1;
+pub const BUNDLE_MANIFEST: &str = "manifest.json";
+const BUNDLE_DOC: &str = "doc.json";
+const BUNDLE_IMAGES: &str = "images/";
+
+/// Table of contents of a bundle, always its first entry.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BundleManifest {
+    pub bundle_version: u32,
+    /// Schema version of the document, see `DOC_SCHEMA_VERSION`.
+    pub schema_version: u32,
+    pub doc: String,
+    pub images: Vec<String>,
+    /// Further files that travel with the document. Readers skip the ones they don't know.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+}
+
+/// True for data that looks like a `.velo` bundle rather than JSON.
+pub fn is_bundle(data: &[u8]) -> bool {
+    super::is_zip(data)
+}
+
+/// Pack a document into a `.velo` bundle: a zip archive with a manifest, the document
+/// JSON and every image of the image store as a PNG file named after its hash.
+pub fn doc_to_bundle(doc: &Doc) -> Result<Vec<u8>, DocError> {
+    let mut hashes: Vec<&String> = doc.images.keys().collect();
+    hashes.sort();
+    let manifest = BundleManifest {
+        bundle_version: BUNDLE_VERSION,
+        schema_version: DOC_SCHEMA_VERSION,
+        doc: BUNDLE_DOC.to_string(),
+        images: hashes
+            .iter()
+            .map(|hash| format!("{}{}.png", BUNDLE_IMAGES, hash))
+            .collect(),
+        attachments: vec![],
+    };
+    // images live next to the document instead of inside it
+    let mut stripped = doc.clone();
+    stripped.images.clear();
+
+    let mut zip = ZipWriter::default();
+    zip.add(BUNDLE_MANIFEST, &serde_json::to_vec_pretty(&manifest)?)
+        .map_err(DocError::Malformed)?;
+    zip.add(BUNDLE_DOC, doc_to_canonical_json(&stripped)?.as_bytes())
+        .map_err(DocError::Malformed)?;
+    for (hash, path) in hashes.iter().zip(manifest.images.iter()) {
+        let png = general_purpose::STANDARD
+            .decode(&doc.images[*hash])
+            .map_err(|e| DocError::Malformed(format!("image {}: {}", hash, e)))?;
+        zip.add(path, &png).map_err(DocError::Malformed)?;
+    }
+    zip.finish().map_err(DocError::Malformed)
+}
+
+/// Unpack a `.velo` bundle, upgrading the document inside to the current schema.
+pub fn doc_from_bundle(data: &[u8]) -> Result<Doc, DocError> {
+    let files = read_zip(data).map_err(DocError::Malformed)?;
+    let file = |name: &str| {
+        files
+            .iter()
+            .find(|(path, _)| path == name)
+            .map(|(_, contents)| contents)
+            .ok_or_else(|| DocError::Malformed(format!("bundle has no {}", name)))
+    };
+    let manifest: BundleManifest = serde_json::from_slice(file(BUNDLE_MANIFEST)?)?;
+    if manifest.bundle_version > BUNDLE_VERSION {
+        return Err(DocError::Malformed(format!(
+            "bundle version {} is newer than supported version {}",
+            manifest.bundle_version, BUNDLE_VERSION
+        )));
+    }
+    let json = String::from_utf8(file(&manifest.doc)?.clone())
+        .map_err(|e| DocError::Malformed(format!("{}: {}", manifest.doc, e)))?;
+    let mut doc = doc_from_json(&json)?;
+    for path in manifest.images.iter() {
+        let hash = path
+            .strip_prefix(BUNDLE_IMAGES)
+            .and_then(|name| name.strip_suffix(".png"))
+            .ok_or_else(|| DocError::Malformed(format!("unexpected image path {}", path)))?;
+        doc.images.insert(
+            hash.to_string(),
+            general_purpose::STANDARD.encode(file(path)?),
+        );
+    }
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{image_hash, read_zip};
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let doc = doc_from_json(include_str!("fixtures/doc_v0.json")).unwrap();
+        let bundle = doc_to_bundle(&doc).unwrap();
+        assert!(is_bundle(&bundle));
+        assert!(!is_bundle(serde_json::to_string(&doc).unwrap().as_bytes()));
+        // the same document always packs to the same bytes
+        assert_eq!(doc_to_bundle(&doc).unwrap(), bundle);
+
+        let files = read_zip(&bundle).unwrap();
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        let hash = doc.images.keys().next().unwrap();
+        let image_path = format!("images/{}.png", hash);
+        assert_eq!(names, [BUNDLE_MANIFEST, "doc.json", image_path.as_str()]);
+        // images are real files, not base64 inside the document
+        assert_eq!(image_hash(&files[2].1), *hash);
+        assert!(!String::from_utf8_lossy(&files[1].1).contains(&doc.images[hash]));
+        let manifest: BundleManifest = serde_json::from_slice(&files[0].1).unwrap();
+        assert_eq!(manifest.schema_version, DOC_SCHEMA_VERSION);
+        assert_eq!(manifest.images, [image_path]);
+
+        let unpacked = doc_from_bundle(&bundle).unwrap();
+        assert_eq!(
+            serde_json::to_value(&unpacked).unwrap(),
+            serde_json::to_value(&doc).unwrap()
+        );
+
+        let mut corrupted = bundle.clone();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0xff;
+        assert!(doc_from_bundle(&corrupted).is_err());
+        assert!(doc_from_bundle(b"{}").is_err());
+    }
+}
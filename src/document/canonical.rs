@@ -0,0 +1,119 @@
+use serde_json::{Number, Value};
+
+use crate::components::Doc;
+
+use super::{doc_from_json, DocError, TabSnapshot, DOC_SCHEMA_VERSION};
+
+/// Decimals kept for coordinates, sizes and z values. Nodes are never placed with
+/// sub-pixel precision, and z steps are at least a hundredth apart.
+const DECIMALS: i32 = 3;
+
+/// Serialize a document the same way every time it has the same content, for keeping
+/// documents under version control. Nodes, arrows and drawings are sorted by id, keys of
+/// objects and maps are sorted, and numbers are rounded, so that `0.1` doesn't turn into
+/// `0.10000000149011612` and floating point noise doesn't show up as a change.
+pub fn doc_to_canonical_json(doc: &Doc) -> Result<String, DocError> {
+    let mut doc = doc.clone();
+    doc.version = DOC_SCHEMA_VERSION;
+    for tab in doc.tabs.iter_mut() {
+        let versions = tab.versions.iter_mut().map(|version| &mut version.snapshot);
+        for snapshot in tab.checkpoints.iter_mut().chain(versions) {
+            snapshot.normalize();
+        }
+    }
+    // objects of `serde_json::Value` keep their keys sorted
+    let mut value = serde_json::to_value(&doc)?;
+    round_numbers(&mut value);
+    let mut json = serde_json::to_string_pretty(&value)?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// The document as it reads back from its canonical JSON, with numbers rounded the same way.
+pub fn round_doc(doc: &Doc) -> Result<Doc, DocError> {
+    doc_from_json(&doc_to_canonical_json(doc)?)
+}
+
+/// The snapshot with numbers rounded the same way as in canonical JSON.
+pub fn round_snapshot(snapshot: &TabSnapshot) -> TabSnapshot {
+    let Ok(mut value) = serde_json::to_value(snapshot) else {
+        return snapshot.clone();
+    };
+    round_numbers(&mut value);
+    serde_json::from_value(value).unwrap_or_else(|_| snapshot.clone())
+}
+
+fn round_numbers(value: &mut Value) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let scale = 10f64.powi(DECIMALS);
+            let rounded = (number.as_f64().unwrap_or_default() * scale).round() / scale;
+            // whole numbers are written without a fraction, and there is no negative zero
+            *number = match rounded {
+                _ if rounded == 0. => Number::from(0),
+                _ if rounded.fract() == 0. && rounded.abs() < i64::MAX as f64 => {
+                    Number::from(rounded as i64)
+                }
+                _ => Number::from_f64(rounded).unwrap_or_else(|| number.clone()),
+            };
+        }
+        Value::Array(values) => values.iter_mut().for_each(round_numbers),
+        Value::Object(map) => map.values_mut().for_each(round_numbers),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::TabVersion;
+    use crate::utils::ReflectableUuid;
+
+    #[test]
+    fn test_canonical_json() {
+        let mut doc = doc_from_json(include_str!("fixtures/doc_v1.json")).unwrap();
+        let snapshot = doc.tabs[0].checkpoints.back().unwrap().clone();
+        doc.tabs[0].versions.push(TabVersion {
+            id: ReflectableUuid::generate(),
+            name: "draft".to_string(),
+            created: 0,
+            snapshot,
+        });
+        let json = doc_to_canonical_json(&doc).unwrap();
+
+        let mut shuffled = doc.clone();
+        for tab in shuffled.tabs.iter_mut() {
+            let versions = tab.versions.iter_mut().map(|version| &mut version.snapshot);
+            for snapshot in tab.checkpoints.iter_mut().chain(versions) {
+                snapshot.nodes.reverse();
+                snapshot.arrows.reverse();
+                snapshot.drawings.reverse();
+            }
+        }
+        // map order is random per process, rebuilding the maps changes it
+        shuffled.images = shuffled.images.into_iter().collect();
+        assert_eq!(doc_to_canonical_json(&shuffled).unwrap(), json);
+
+        // checkpoints are nested objects rather than escaped strings
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert!(value["tabs"][0]["checkpoints"][0].is_object());
+
+        let mut moved = doc_from_json(&json).unwrap();
+        let node = &mut moved.tabs[0].checkpoints.back_mut().unwrap().nodes[0];
+        node.x += 0.1;
+        node.y += 1e-6;
+        let moved_json = doc_to_canonical_json(&moved).unwrap();
+        let changed: Vec<_> = json
+            .lines()
+            .zip(moved_json.lines())
+            .filter(|(a, b)| a != b)
+            .collect();
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].1.trim().starts_with("\"x\": "));
+        assert!(changed[0].1.ends_with(".1,") || changed[0].1.ends_with(".9,"));
+        assert_eq!(
+            doc_to_canonical_json(&doc_from_json(&moved_json).unwrap()).unwrap(),
+            moved_json
+        );
+    }
+}
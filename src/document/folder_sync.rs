@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use super::{doc_from_bundle, doc_from_json, is_bundle, DocError};
+use crate::components::Doc;
+use crate::utils::ReflectableUuid;
+
+/// A change to a synced folder found by `FolderSync::scan`.
+#[derive(Debug)]
+pub enum FolderChange {
+    /// A document file was added or its content changed.
+    Changed { path: PathBuf, doc: Doc },
+    /// A document file was deleted or renamed.
+    Removed {
+        path: PathBuf,
+        doc_id: Option<ReflectableUuid>,
+    },
+    /// A file that doesn't read as a document, possibly half written.
+    Invalid { path: PathBuf, error: String },
+}
+
+#[derive(Debug, Clone)]
+struct SyncedFile {
+    doc_id: Option<ReflectableUuid>,
+    hash: String,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Documents kept as `.json` or `.velo` files in a folder, for instance a git repository.
+/// The folder is polled: files are only read again when their size or modification time
+/// changed, and only reported when their content did.
+#[derive(Debug)]
+pub struct FolderSync {
+    pub dir: PathBuf,
+    files: HashMap<PathBuf, SyncedFile>,
+}
+
+impl FolderSync {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Changes since the last scan, in file name order. The first scan reports every file.
+    pub fn scan(&mut self) -> io::Result<Vec<FolderChange>> {
+        let paths = folder_doc_files(&self.dir)?;
+        let mut changes = vec![];
+        for path in paths.iter() {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                continue;
+            };
+            let modified = metadata.modified().ok();
+            if let Some(file) = self.files.get(path) {
+                if file.modified == modified && file.len == metadata.len() {
+                    continue;
+                }
+            }
+            let Ok(data) = std::fs::read(path) else {
+                continue;
+            };
+            let hash = content_hash(&data);
+            let known = self.files.get(path).map(|file| file.hash == hash);
+            let result = read_doc_file(&data);
+            let doc_id = result.as_ref().ok().map(|doc| doc.id);
+            self.files.insert(
+                path.clone(),
+                SyncedFile {
+                    doc_id,
+                    hash,
+                    modified,
+                    len: data.len() as u64,
+                },
+            );
+            if known == Some(true) {
+                continue;
+            }
+            changes.push(match result {
+                Ok(doc) => FolderChange::Changed {
+                    path: path.clone(),
+                    doc,
+                },
+                Err(e) => FolderChange::Invalid {
+                    path: path.clone(),
+                    error: e.to_string(),
+                },
+            });
+        }
+        let removed: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter(|path| !paths.contains(path))
+            .cloned()
+            .collect();
+        for path in removed {
+            let file = self.files.remove(&path).unwrap();
+            changes.push(FolderChange::Removed {
+                path,
+                doc_id: file.doc_id,
+            });
+        }
+        Ok(changes)
+    }
+
+    /// Remember a file velo just wrote, so the next scan doesn't report it back.
+    pub fn mark_written(&mut self, path: &Path, doc_id: ReflectableUuid) {
+        let (Ok(metadata), Ok(data)) = (std::fs::metadata(path), std::fs::read(path)) else {
+            return;
+        };
+        self.files.insert(
+            path.to_path_buf(),
+            SyncedFile {
+                doc_id: Some(doc_id),
+                hash: content_hash(&data),
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            },
+        );
+    }
+
+    /// Report the file again on the next scan, even if it doesn't change until then.
+    pub fn forget(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    /// File the document is synced to.
+    pub fn path_of(&self, doc_id: ReflectableUuid) -> Option<&PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.doc_id == Some(doc_id))
+            .map(|(path, _)| path)
+            .min()
+    }
+}
+
+/// `.json` and `.velo` files directly inside `dir`, sorted by name.
+pub fn folder_doc_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|extension| extension.to_str()),
+                Some("json" | "velo")
+            )
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parse a document file, either a JSON document or a `.velo` bundle.
+pub fn read_doc_file(data: &[u8]) -> Result<Doc, DocError> {
+    if is_bundle(data) {
+        return doc_from_bundle(data);
+    }
+    let json = std::str::from_utf8(data)
+        .map_err(|_| DocError::Malformed("not a text file".to_string()))?;
+    doc_from_json(json)
+}
+
+fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let doc_json = include_str!("fixtures/doc_v0.json");
+        let doc_path = dir.path().join("plans.json");
+        std::fs::write(&doc_path, doc_json).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not synced").unwrap();
+        let mut sync = FolderSync::new(dir.path().to_path_buf());
+
+        let changes = sync.scan().unwrap();
+        assert_eq!(changes.len(), 1);
+        let FolderChange::Changed { doc, .. } = &changes[0] else {
+            panic!("expected a changed document, got {:?}", changes[0]);
+        };
+        let doc_id = doc.id;
+        assert_eq!(sync.path_of(doc_id), Some(&doc_path));
+        assert!(sync.scan().unwrap().is_empty());
+
+        // a write by velo itself isn't reported
+        let mut doc = doc.clone();
+        doc.name = "renamed".to_string();
+        std::fs::write(&doc_path, serde_json::to_string(&doc).unwrap()).unwrap();
+        sync.mark_written(&doc_path, doc_id);
+        assert!(sync.scan().unwrap().is_empty());
+
+        // same content written again by another tool isn't either
+        std::fs::write(&doc_path, serde_json::to_string(&doc).unwrap()).unwrap();
+        assert!(sync.scan().unwrap().is_empty());
+
+        std::fs::write(&doc_path, "{\"tabs\": ").unwrap();
+        let changes = sync.scan().unwrap();
+        assert!(matches!(changes[0], FolderChange::Invalid { .. }));
+        std::fs::write(&doc_path, doc_json).unwrap();
+        let changes = sync.scan().unwrap();
+        assert!(matches!(&changes[0], FolderChange::Changed { doc, .. } if doc.id == doc_id));
+
+        sync.forget(&doc_path);
+        assert_eq!(sync.scan().unwrap().len(), 1);
+
+        std::fs::remove_file(&doc_path).unwrap();
+        let changes = sync.scan().unwrap();
+        assert!(matches!(
+            changes[0],
+            FolderChange::Removed { doc_id: Some(id), .. } if id == doc_id
+        ));
+        assert_eq!(sync.path_of(doc_id), None);
+    }
+}
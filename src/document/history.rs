@@ -0,0 +1,333 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use uuid::Uuid;
+
+use super::TabSnapshot;
+use crate::canvas::arrow::components::{ArrowMeta, ArrowType};
+use crate::{DrawingJsonNode, JsonNode};
+
+/// One entity of a tab before and after an edit. `None` before means the edit created it,
+/// `None` after means the edit deleted it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CanvasChange {
+    Node {
+        before: Option<JsonNode<String>>,
+        after: Option<JsonNode<String>>,
+    },
+    Arrow {
+        before: Option<ArrowMeta>,
+        after: Option<ArrowMeta>,
+    },
+    Drawing {
+        before: Option<DrawingJsonNode<String>>,
+        after: Option<DrawingJsonNode<String>>,
+    },
+}
+
+impl CanvasChange {
+    fn describe(&self) -> &'static str {
+        match self {
+            CanvasChange::Node {
+                before: None,
+                after: Some(_),
+            } => "create node",
+            CanvasChange::Node {
+                before: Some(_),
+                after: None,
+            } => "delete node",
+            CanvasChange::Node {
+                before: Some(before),
+                after: Some(after),
+            } if before.bg_color != after.bg_color => "recolor",
+            CanvasChange::Node {
+                before: Some(before),
+                after: Some(after),
+            } if (before.width, before.height) != (after.width, after.height) => "resize",
+            CanvasChange::Node { .. } => "move",
+            CanvasChange::Arrow { before: None, .. } => "create arrow",
+            CanvasChange::Arrow { .. } => "delete arrow",
+            CanvasChange::Drawing { .. } => "edit drawing",
+        }
+    }
+
+    fn inverse(&self) -> Self {
+        match self.clone() {
+            CanvasChange::Node { before, after } => CanvasChange::Node {
+                before: after,
+                after: before,
+            },
+            CanvasChange::Arrow { before, after } => CanvasChange::Arrow {
+                before: after,
+                after: before,
+            },
+            CanvasChange::Drawing { before, after } => CanvasChange::Drawing {
+                before: after,
+                after: before,
+            },
+        }
+    }
+}
+
+/// Everything one edit of the canvas changed, such as a node deleted together with its arrows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanvasCommand {
+    pub changes: Vec<CanvasChange>,
+}
+
+impl CanvasCommand {
+    /// The command that takes the canvas back to where this one started.
+    pub fn inverse(&self) -> Self {
+        Self {
+            changes: self
+                .changes
+                .iter()
+                .rev()
+                .map(CanvasChange::inverse)
+                .collect(),
+        }
+    }
+
+    /// Short name of the edit, such as "move" or "delete node". Arrows that were deleted
+    /// or restored together with their node don't count.
+    pub fn describe(&self) -> &'static str {
+        let is_node = |change: &&CanvasChange| matches!(change, CanvasChange::Node { .. });
+        let mut changes: Vec<&CanvasChange> = self.changes.iter().filter(is_node).collect();
+        if changes.is_empty() {
+            changes = self.changes.iter().collect();
+        }
+        let names: Vec<&'static str> = changes.into_iter().map(CanvasChange::describe).collect();
+        match names.first() {
+            Some(first) if names.iter().all(|name| name == first) => first,
+            _ => "edit",
+        }
+    }
+}
+
+/// Edits of a tab that can be undone and redone, oldest first.
+#[derive(Clone, Debug, Default)]
+pub struct CommandHistory {
+    undo: VecDeque<CanvasCommand>,
+    redo: Vec<CanvasCommand>,
+    /// Most edits kept for undo, unlimited when `None`.
+    limit: Option<usize>,
+}
+
+impl CommandHistory {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// Add an edit made on the canvas. Edits that were undone can't be redone after it.
+    pub fn record(&mut self, command: CanvasCommand) {
+        self.redo.clear();
+        self.undo.push_back(command);
+        if let Some(limit) = self.limit {
+            while self.undo.len() > limit {
+                self.undo.pop_front();
+            }
+        }
+    }
+
+    /// The command to apply to the canvas to undo the last edit.
+    pub fn undo(&mut self) -> Option<CanvasCommand> {
+        let command = self.undo.pop_back()?;
+        let inverse = command.inverse();
+        self.redo.push(command);
+        Some(inverse)
+    }
+
+    /// The command to apply to the canvas to redo the last undone edit.
+    pub fn redo(&mut self) -> Option<CanvasCommand> {
+        let command = self.redo.pop()?;
+        self.undo.push_back(command.clone());
+        Some(command)
+    }
+
+    /// Every edit that can still be undone or redone.
+    pub fn commands(&self) -> impl Iterator<Item = &CanvasCommand> {
+        self.undo.iter().chain(self.redo.iter())
+    }
+}
+
+/// The edit that turns the canvas of `before` into `after`, limited to the nodes and drawings
+/// in `ids` and the arrows of those nodes. Only what undo covers is compared: text, tags and
+/// visibility of nodes have their own history, and the sides arrows attach to follow from
+/// where their nodes are.
+pub fn diff_canvas(
+    before: &TabSnapshot,
+    after: &TabSnapshot,
+    ids: &HashSet<Uuid>,
+) -> Option<CanvasCommand> {
+    let mut changes = vec![];
+    let nodes = |snapshot: &TabSnapshot| -> HashMap<Uuid, JsonNode<String>> {
+        snapshot
+            .nodes
+            .iter()
+            .filter(|node| ids.contains(&node.id))
+            .map(|node| (node.id, node.clone()))
+            .collect()
+    };
+    let (mut before_nodes, mut after_nodes) = (nodes(before), nodes(after));
+    for node in before.nodes.iter() {
+        let Some(node) = before_nodes.remove(&node.id) else {
+            continue;
+        };
+        match after_nodes.remove(&node.id) {
+            Some(other) if same_node(&node, &other) => {}
+            other => changes.push(CanvasChange::Node {
+                before: Some(node),
+                after: other,
+            }),
+        }
+    }
+    for node in after.nodes.iter() {
+        if let Some(node) = after_nodes.remove(&node.id) {
+            changes.push(CanvasChange::Node {
+                before: None,
+                after: Some(node),
+            });
+        }
+    }
+
+    let touched =
+        |arrow: &&ArrowMeta| ids.contains(&arrow.start.id.0) || ids.contains(&arrow.end.id.0);
+    let count = |arrows: &[ArrowMeta]| {
+        let mut counts: HashMap<_, usize> = HashMap::new();
+        for arrow in arrows.iter().filter(touched) {
+            *counts.entry(arrow_key(arrow)).or_default() += 1;
+        }
+        counts
+    };
+    let (mut before_arrows, mut after_arrows) = (count(&before.arrows), count(&after.arrows));
+    for arrow in before.arrows.iter().filter(touched) {
+        match after_arrows.get_mut(&arrow_key(arrow)) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => changes.push(CanvasChange::Arrow {
+                before: Some(*arrow),
+                after: None,
+            }),
+        }
+    }
+    for arrow in after.arrows.iter().filter(touched) {
+        match before_arrows.get_mut(&arrow_key(arrow)) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => changes.push(CanvasChange::Arrow {
+                before: None,
+                after: Some(*arrow),
+            }),
+        }
+    }
+
+    let drawings = |snapshot: &TabSnapshot| -> HashMap<Uuid, DrawingJsonNode<String>> {
+        snapshot
+            .drawings
+            .iter()
+            .filter(|drawing| ids.contains(&drawing.id.0))
+            .map(|drawing| (drawing.id.0, drawing.clone()))
+            .collect()
+    };
+    let (mut before_drawings, mut after_drawings) = (drawings(before), drawings(after));
+    for drawing in before.drawings.iter() {
+        let Some(drawing) = before_drawings.remove(&drawing.id.0) else {
+            continue;
+        };
+        match after_drawings.remove(&drawing.id.0) {
+            Some(other) if other == drawing => {}
+            other => changes.push(CanvasChange::Drawing {
+                before: Some(drawing),
+                after: other,
+            }),
+        }
+    }
+    for drawing in after.drawings.iter() {
+        if let Some(drawing) = after_drawings.remove(&drawing.id.0) {
+            changes.push(CanvasChange::Drawing {
+                before: None,
+                after: Some(drawing),
+            });
+        }
+    }
+    (!changes.is_empty()).then_some(CanvasCommand { changes })
+}
+
+fn same_node(a: &JsonNode<String>, b: &JsonNode<String>) -> bool {
+    (a.x, a.y, a.z, a.width, a.height) == (b.x, b.y, b.z, b.width, b.height)
+        && a.bg_color == b.bg_color
+        && a.node_type == b.node_type
+}
+
+fn arrow_key(arrow: &ArrowMeta) -> (Uuid, Uuid, ArrowType) {
+    (arrow.start.id.0, arrow.end.id.0, arrow.arrow_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+
+    #[test]
+    fn test_command_history() {
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let canvas = doc.tabs[0].checkpoints.back().unwrap().clone();
+        let all = |snapshot: &TabSnapshot| -> HashSet<Uuid> {
+            let nodes = snapshot.nodes.iter().map(|node| node.id);
+            nodes
+                .chain(snapshot.drawings.iter().map(|drawing| drawing.id.0))
+                .collect()
+        };
+        assert_eq!(diff_canvas(&canvas, &canvas, &all(&canvas)), None);
+
+        let mut moved = canvas.clone();
+        moved.nodes[0].x += 10.;
+        // editing text isn't a canvas edit
+        moved.nodes[1].text.text = "edited".to_string();
+        let command = diff_canvas(&canvas, &moved, &all(&canvas)).unwrap();
+        assert_eq!(command.changes.len(), 1);
+        assert_eq!(command.describe(), "move");
+        // only the edited nodes are compared
+        let only_second = HashSet::from([canvas.nodes[1].id]);
+        assert_eq!(diff_canvas(&canvas, &moved, &only_second), None);
+
+        let mut deleted = moved.clone();
+        let id = deleted.nodes.remove(0).id;
+        deleted
+            .arrows
+            .retain(|arrow| arrow.start.id.0 != id && arrow.end.id.0 != id);
+        let delete = diff_canvas(&moved, &deleted, &HashSet::from([id])).unwrap();
+        assert_eq!(delete.describe(), "delete node");
+        assert_eq!(
+            delete.changes.len(),
+            1 + moved.arrows.len() - deleted.arrows.len()
+        );
+        assert_eq!(delete.inverse().describe(), "create node");
+        assert_eq!(delete.inverse().inverse(), delete);
+
+        let mut recolored = deleted.clone();
+        recolored.nodes[0].bg_color = "color_change_1".to_string();
+        recolored.nodes[0].width += 20.;
+        assert_eq!(
+            diff_canvas(&deleted, &recolored, &all(&deleted))
+                .unwrap()
+                .describe(),
+            "recolor"
+        );
+
+        let mut history = CommandHistory::new(Some(2));
+        history.record(command);
+        history.record(delete.clone());
+        history.record(diff_canvas(&deleted, &recolored, &all(&deleted)).unwrap());
+        assert_eq!(history.undo().unwrap().describe(), "recolor");
+        assert_eq!(history.undo(), Some(delete.inverse()));
+        // the move fell off the end of the history
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(delete.clone()));
+        history.record(diff_canvas(&canvas, &moved, &all(&canvas)).unwrap());
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo().unwrap().describe(), "move");
+        assert_eq!(history.undo(), Some(delete.inverse()));
+        assert_eq!(history.commands().count(), 2);
+    }
+}
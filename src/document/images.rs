@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use base64::{engine::general_purpose, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::components::Doc;
+
+/// Content hash under which an encoded PNG is kept in `Doc.images`.
+pub fn image_hash(png: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(png))
+}
+
+/// Add an encoded PNG to the document image store and return its hash.
+/// Images already in the store are not encoded again.
+pub fn store_image(doc: &mut Doc, png: &[u8]) -> String {
+    let hash = image_hash(png);
+    doc.images
+        .entry(hash.clone())
+        .or_insert_with(|| general_purpose::STANDARD.encode(png));
+    hash
+}
+
+/// Drop images that no checkpoint or version of any tab refers to anymore.
+/// Deleted documents take their image store with them.
+pub fn gc_images(doc: &mut Doc) {
+    let referenced: HashSet<&String> = doc
+        .tabs
+        .iter()
+        .flat_map(|tab| {
+            let versions = tab.versions.iter().map(|version| &version.snapshot);
+            tab.checkpoints.iter().chain(versions)
+        })
+        .flat_map(|snapshot| snapshot.images.values())
+        .collect();
+    doc.images.retain(|hash, _| referenced.contains(hash));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+
+    #[test]
+    fn test_gc_images() {
+        let mut doc = doc_from_json(include_str!("fixtures/doc_v0.json")).unwrap();
+        assert_eq!(doc.images.len(), 1);
+        let orphan = store_image(&mut doc, b"not referenced");
+        assert_eq!(store_image(&mut doc, b"not referenced"), orphan);
+        assert_eq!(doc.images.len(), 2);
+        gc_images(&mut doc);
+        assert_eq!(doc.images.len(), 1);
+        assert!(!doc.images.contains_key(&orphan));
+
+        // the image is only referenced from the last checkpoint of the first tab
+        let snapshot = doc.tabs[0].checkpoints.pop_back().unwrap();
+        doc.tabs[0].versions.push(crate::document::TabVersion {
+            id: crate::utils::ReflectableUuid::generate(),
+            name: "before review".to_string(),
+            created: 0,
+            snapshot,
+        });
+        gc_images(&mut doc);
+        assert_eq!(doc.images.len(), 1);
+        doc.tabs[0].versions.clear();
+        gc_images(&mut doc);
+        assert!(doc.images.is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::components::Doc;
+use crate::export::ThemeColors;
+use crate::themes::velo_light;
+use crate::utils::ReflectableUuid;
+
+use super::TabSnapshot;
+
+/// Colors given to nodes and drawings whose color isn't a theme color.
+const DEFAULT_NODE_COLOR: &str = "node_bg";
+const DEFAULT_DRAWING_COLOR: &str = "drawing_pencil_btn";
+
+/// Something in a document that velo can't load as is, and how it gets fixed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// An arrow starts or ends at a node that isn't in the tab. The arrow is removed.
+    DanglingArrow { start: Uuid, end: Uuid },
+    /// Two nodes or drawings of a tab, or two tabs, share an id. Later ones get a new id.
+    DuplicateId(Uuid),
+    /// A node shows an image that isn't in the image store. The node loses its image.
+    MissingImage { node: String, hash: String },
+    /// A node or drawing color that isn't a theme color. It gets the default color.
+    UnknownColor { id: Uuid, color: String },
+    /// A drawing without points. It is removed.
+    EmptyDrawing(Uuid),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// Name of the tab the problem is in.
+    pub tab: String,
+    /// Position of the affected checkpoint in `Tab.checkpoints`.
+    pub checkpoint: usize,
+    /// Name of the affected version, the checkpoint position is unused when set.
+    pub version: Option<String>,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DanglingArrow { start, end } => {
+                write!(f, "arrow from {} to {} has a missing end", start, end)
+            }
+            Problem::DuplicateId(id) => write!(f, "id {} is used more than once", id),
+            Problem::MissingImage { node, hash } => {
+                write!(f, "image {} of node {} is missing", hash, node)
+            }
+            Problem::UnknownColor { id, color } => write!(f, "{} has unknown color {}", id, color),
+            Problem::EmptyDrawing(id) => write!(f, "drawing {} has no points", id),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(
+                f,
+                "{} (version \"{}\"): {}",
+                self.tab, version, self.problem
+            ),
+            None => write!(
+                f,
+                "{} (checkpoint {}): {}",
+                self.tab,
+                self.checkpoint + 1,
+                self.problem
+            ),
+        }
+    }
+}
+
+/// Problems that would make the document fail to load or lose content on the canvas.
+pub fn check_doc(doc: &Doc) -> Vec<Issue> {
+    repair_doc(&mut doc.clone())
+}
+
+/// Fix every problem `check_doc` finds and return what was fixed.
+pub fn repair_doc(doc: &mut Doc) -> Vec<Issue> {
+    // every theme has the same color names, so any theme tells which names exist
+    let colors = ThemeColors::new(&velo_light());
+    let mut issues = vec![];
+    let mut tab_ids = HashSet::new();
+    for tab in doc.tabs.iter_mut() {
+        if !tab_ids.insert(tab.id) {
+            issues.push(Issue {
+                tab: tab.name.clone(),
+                checkpoint: 0,
+                version: None,
+                problem: Problem::DuplicateId(tab.id.0),
+            });
+            tab.id = ReflectableUuid::generate();
+        }
+        for (checkpoint, snapshot) in tab.checkpoints.iter_mut().enumerate() {
+            for problem in repair_snapshot(snapshot, doc.images.keys().collect(), &colors) {
+                issues.push(Issue {
+                    tab: tab.name.clone(),
+                    checkpoint,
+                    version: None,
+                    problem,
+                });
+            }
+        }
+        for version in tab.versions.iter_mut() {
+            for problem in
+                repair_snapshot(&mut version.snapshot, doc.images.keys().collect(), &colors)
+            {
+                issues.push(Issue {
+                    tab: tab.name.clone(),
+                    checkpoint: 0,
+                    version: Some(version.name.clone()),
+                    problem,
+                });
+            }
+        }
+    }
+    issues
+}
+
+fn repair_snapshot(
+    snapshot: &mut TabSnapshot,
+    images: HashSet<&String>,
+    colors: &ThemeColors,
+) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut ids = HashSet::new();
+    for node in snapshot.nodes.iter_mut() {
+        if !ids.insert(node.id) {
+            problems.push(Problem::DuplicateId(node.id));
+            node.id = Uuid::new_v4();
+        }
+        if colors.get(&node.bg_color).is_none() {
+            problems.push(Problem::UnknownColor {
+                id: node.id,
+                color: node.bg_color.clone(),
+            });
+            node.bg_color = DEFAULT_NODE_COLOR.to_string();
+        }
+    }
+    let node_ids = ids.clone();
+    snapshot.arrows.retain(|arrow| {
+        let (start, end) = (arrow.start.id.0, arrow.end.id.0);
+        let is_connected = node_ids.contains(&start) && node_ids.contains(&end);
+        if !is_connected {
+            problems.push(Problem::DanglingArrow { start, end });
+        }
+        is_connected
+    });
+    snapshot.drawings.retain(|drawing| {
+        if drawing.points.is_empty() {
+            problems.push(Problem::EmptyDrawing(drawing.id.0));
+        }
+        !drawing.points.is_empty()
+    });
+    for drawing in snapshot.drawings.iter_mut() {
+        if !ids.insert(drawing.id.0) {
+            problems.push(Problem::DuplicateId(drawing.id.0));
+            drawing.id = ReflectableUuid::generate();
+        }
+        if colors.get(&drawing.drawing_color).is_none() {
+            problems.push(Problem::UnknownColor {
+                id: drawing.id.0,
+                color: drawing.drawing_color.clone(),
+            });
+            drawing.drawing_color = DEFAULT_DRAWING_COLOR.to_string();
+        }
+    }
+    let mut missing: Vec<(String, String)> = snapshot
+        .images
+        .iter()
+        .filter(|(_, hash)| !images.contains(hash))
+        .map(|(node, hash)| (node.clone(), hash.clone()))
+        .collect();
+    missing.sort();
+    for (node, hash) in missing {
+        snapshot.images.remove(&node);
+        problems.push(Problem::MissingImage { node, hash });
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{doc_from_json, TabVersion};
+
+    #[test]
+    fn test_repair_doc() {
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        assert_eq!(check_doc(&doc), vec![]);
+
+        let mut broken = doc.clone();
+        let tab_id = broken.tabs[0].id;
+        broken.tabs[1].id = tab_id;
+        let snapshot = broken.tabs[0].checkpoints.back_mut().unwrap();
+        let removed = snapshot.nodes.remove(0);
+        let duplicate = snapshot.nodes[0].clone();
+        snapshot.nodes.push(duplicate.clone());
+        snapshot.nodes[1].bg_color = "arrow_connector_size".to_string();
+        snapshot.drawings[0].points.clear();
+        snapshot
+            .images
+            .insert(duplicate.id.to_string(), "gone".to_string());
+        let dangling = snapshot
+            .arrows
+            .iter()
+            .filter(|arrow| arrow.start.id.0 == removed.id || arrow.end.id.0 == removed.id)
+            .count();
+        assert!(dangling > 0);
+
+        let issues = check_doc(&broken);
+        let count = |matches: fn(&Problem) -> bool| {
+            issues
+                .iter()
+                .filter(|issue| matches(&issue.problem))
+                .count()
+        };
+        assert_eq!(
+            count(|p| matches!(p, Problem::DanglingArrow { .. })),
+            dangling
+        );
+        assert_eq!(count(|p| matches!(p, Problem::DuplicateId(_))), 2);
+        assert_eq!(count(|p| matches!(p, Problem::UnknownColor { .. })), 1);
+        assert_eq!(count(|p| matches!(p, Problem::EmptyDrawing(_))), 1);
+        assert_eq!(count(|p| matches!(p, Problem::MissingImage { .. })), 1);
+        assert_eq!(issues.len(), dangling + 5);
+
+        let mut repaired = broken.clone();
+        assert_eq!(repair_doc(&mut repaired), issues);
+        assert_eq!(check_doc(&repaired), vec![]);
+        let snapshot = repaired.tabs[0].checkpoints.back().unwrap();
+        assert_ne!(repaired.tabs[0].id, repaired.tabs[1].id);
+        assert_eq!(snapshot.nodes[1].bg_color, "node_bg");
+        assert_ne!(snapshot.nodes.last().unwrap().id, duplicate.id);
+        assert!(snapshot.drawings.iter().all(|d| !d.points.is_empty()));
+    }
+
+    #[test]
+    fn test_repair_versions() {
+        let mut doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let mut snapshot = doc.tabs[0].checkpoints.back().unwrap().clone();
+        snapshot.nodes.remove(0);
+        snapshot.drawings[0].points.clear();
+        doc.tabs[0].versions.push(TabVersion {
+            id: ReflectableUuid::generate(),
+            name: "draft".to_string(),
+            created: 0,
+            snapshot,
+        });
+
+        let issues = check_doc(&doc);
+        assert!(!issues.is_empty());
+        assert!(issues
+            .iter()
+            .all(|issue| issue.version.as_deref() == Some("draft")));
+        assert!(issues
+            .iter()
+            .any(|issue| matches!(issue.problem, Problem::DanglingArrow { .. })));
+        assert!(issues[0].to_string().contains("(version \"draft\")"));
+
+        assert_eq!(repair_doc(&mut doc), issues);
+        assert_eq!(check_doc(&doc), vec![]);
+        let version = &doc.tabs[0].versions[0].snapshot;
+        assert!(version.drawings.iter().all(|d| !d.points.is_empty()));
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::TabSnapshot;
+use crate::components::{Doc, Tab};
+use crate::utils::ReflectableUuid;
+
+pub const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// State of a tab's canvas that hasn't reached the store yet.
+/// The journal is a JSON line per entry; the last entry of a tab wins on replay.
+/// Images are only written with the first entry that references them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JournalEntry {
+    pub doc_id: ReflectableUuid,
+    pub tab_id: ReflectableUuid,
+    pub snapshot: TabSnapshot,
+    /// Encoded images referenced by `snapshot` that aren't in the document image store
+    /// or an earlier entry yet.
+    #[serde(default)]
+    pub images: HashMap<String, String>,
+}
+
+/// Append an entry and flush it to disk, so it survives a crash right after the edit.
+pub fn append_journal(path: &Path, entry: &JournalEntry) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    file.sync_data()
+}
+
+/// Read every complete entry. A line cut short by a crash is skipped.
+pub fn read_journal(path: &Path) -> io::Result<Vec<JournalEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("Skipping unreadable journal entry: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Rewrite the journal with only the last entry of each tab, and the images those entries
+/// reference. The new journal replaces the old one in a single rename.
+pub fn compact_journal(path: &Path) -> io::Result<Vec<JournalEntry>> {
+    let entries = read_journal(path)?;
+    let mut images: HashMap<String, String> = HashMap::new();
+    let mut latest: Vec<JournalEntry> = vec![];
+    for entry in entries {
+        images.extend(entry.images.clone());
+        latest.retain(|other| other.doc_id != entry.doc_id || other.tab_id != entry.tab_id);
+        latest.push(entry);
+    }
+    let tmp_path = path.with_extension("jsonl.tmp");
+    let mut file = File::create(&tmp_path)?;
+    for entry in latest.iter_mut() {
+        entry.images = entry
+            .snapshot
+            .images
+            .values()
+            .filter_map(|hash| Some((hash.clone(), images.remove(hash)?)))
+            .collect();
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+    }
+    file.sync_data()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(latest)
+}
+
+pub fn clear_journal(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Push the last journaled state of each tab of `doc` as a new checkpoint.
+/// Tabs that were never stored are recreated. Returns whether the document changed.
+pub fn replay_journal(doc: &mut Doc, entries: &[JournalEntry]) -> bool {
+    let mut latest: Vec<&JournalEntry> = vec![];
+    let mut images: HashMap<&String, &String> = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.doc_id == doc.id) {
+        images.extend(entry.images.iter());
+        latest.retain(|other| other.tab_id != entry.tab_id);
+        latest.push(entry);
+    }
+    let mut changed = false;
+    for entry in latest {
+        for hash in entry.snapshot.images.values() {
+            if let Some(image) = images.get(hash) {
+                doc.images
+                    .entry(hash.clone())
+                    .or_insert_with(|| (*image).clone());
+            }
+        }
+        let tab = match doc.tabs.iter().position(|tab| tab.id == entry.tab_id) {
+            Some(index) => &mut doc.tabs[index],
+            None => {
+                doc.tabs.push(Tab {
+                    id: entry.tab_id,
+                    name: "Recovered".to_string(),
+                    checkpoints: VecDeque::new(),
+                    is_active: false,
+                    z_index: 1.,
+                    versions: vec![],
+                });
+                doc.tabs.last_mut().unwrap()
+            }
+        };
+        let unchanged = tab
+            .checkpoints
+            .back()
+            .is_some_and(|last| last.same_canvas(&entry.snapshot));
+        if !unchanged {
+            tab.checkpoints.push_back(entry.snapshot.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_journal_replay() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE);
+        let mut doc = doc_from_json(include_str!("fixtures/doc_v0.json")).unwrap();
+        let tab_id = doc.tabs[0].id;
+        let checkpoints = doc.tabs[0].checkpoints.len();
+
+        let mut snapshot = doc.tabs[0].checkpoints.back().unwrap().clone();
+        snapshot.nodes[0].text.text = "first edit".to_string();
+        snapshot
+            .images
+            .insert("node".to_string(), "hash".to_string());
+        let first = JournalEntry {
+            doc_id: doc.id,
+            tab_id,
+            snapshot: snapshot.clone(),
+            images: HashMap::from([("hash".to_string(), "data".to_string())]),
+        };
+        // the image was written with the first entry
+        snapshot.nodes[0].text.text = "second edit".to_string();
+        let second = JournalEntry {
+            snapshot,
+            images: HashMap::new(),
+            ..first.clone()
+        };
+        append_journal(&path, &first).unwrap();
+        append_journal(&path, &second).unwrap();
+        // a crash in the middle of a write leaves a partial line behind
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"doc_id\":").unwrap();
+
+        let entries = read_journal(&path).unwrap();
+        assert_eq!(entries, vec![first.clone(), second.clone()]);
+        assert!(replay_journal(&mut doc, &entries));
+        assert_eq!(doc.tabs[0].checkpoints.len(), checkpoints + 1);
+        let replayed = doc.tabs[0].checkpoints.back().unwrap();
+        assert_eq!(replayed.nodes[0].text.text, "second edit");
+        assert_eq!(doc.images["hash"], "data");
+        // replaying twice doesn't add another checkpoint
+        assert!(!replay_journal(&mut doc, &entries));
+
+        // compacting keeps the last entry and the images it needs
+        let compacted = compact_journal(&path).unwrap();
+        assert_eq!(
+            compacted,
+            vec![JournalEntry {
+                images: first.images.clone(),
+                ..second
+            }]
+        );
+        assert_eq!(read_journal(&path).unwrap(), compacted);
+        append_journal(&path, &first).unwrap();
+        assert_eq!(read_journal(&path).unwrap().len(), 2);
+
+        clear_journal(&path).unwrap();
+        assert!(read_journal(&path).unwrap().is_empty());
+        clear_journal(&path).unwrap();
+    }
+}
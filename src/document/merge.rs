@@ -0,0 +1,551 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use uuid::Uuid;
+
+use super::{normalize_tags, TabSnapshot};
+use crate::canvas::arrow::components::{ArrowMeta, ArrowType};
+use crate::components::{Doc, Tab};
+use crate::utils::ReflectableUuid;
+use crate::{JsonNode, MAX_CHECKPOINTS};
+
+/// One of the two copies of a document being merged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// What both copies of a document changed differently on the same node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictKind {
+    Text,
+    /// Position or size.
+    Position,
+    /// Color or shape.
+    Style,
+    Tags,
+    /// One copy deleted the node, the other edited it.
+    Deleted,
+}
+
+/// A node both copies changed in different ways. The merge keeps the local side of it
+/// until the conflict is resolved with `resolve_conflict`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub tab_id: ReflectableUuid,
+    pub tab_name: String,
+    pub node_id: Uuid,
+    pub kind: ConflictKind,
+    /// The node in the local copy, `None` if deleted there.
+    pub local: Option<JsonNode<String>>,
+    /// The node in the remote copy, `None` if deleted there.
+    pub remote: Option<JsonNode<String>>,
+    /// Image hash of the remote node, to restore it with its image.
+    pub remote_image: Option<String>,
+    /// Arrows of the node on the side that kept it, to restore it with its arrows.
+    pub arrows: Vec<ArrowMeta>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node = self.local.as_ref().or(self.remote.as_ref());
+        let text = node
+            .and_then(|node| node.text.text.lines().find(|line| !line.trim().is_empty()))
+            .unwrap_or("")
+            .trim();
+        let text: String = text.chars().take(30).collect();
+        let what = match self.kind {
+            ConflictKind::Text => "text changed on both sides",
+            ConflictKind::Position => "moved or resized on both sides",
+            ConflictKind::Style => "restyled on both sides",
+            ConflictKind::Tags => "tagged differently on both sides",
+            ConflictKind::Deleted => "deleted on one side, edited on the other",
+        };
+        write!(f, "{}: \"{}\" {}", self.tab_name, text, what)
+    }
+}
+
+/// Combine two copies of the same document, matching nodes, arrows and drawings by id
+/// against the canvas both copies last had in common. Changes made on one side only are
+/// taken as they are; conflicting changes to a node keep the local side and are returned.
+///
+/// Tabs found in both copies get the merged canvas as a new checkpoint, tabs found in one
+/// copy only are kept. The name of the local copy wins.
+pub fn merge_docs(local: &Doc, remote: &Doc) -> (Doc, Vec<Conflict>) {
+    let mut merged = local.clone();
+    let mut conflicts = vec![];
+    for remote_tab in remote.tabs.iter() {
+        let Some(tab) = merged.tabs.iter_mut().find(|tab| tab.id == remote_tab.id) else {
+            merged.tabs.push(Tab {
+                is_active: false,
+                ..remote_tab.clone()
+            });
+            continue;
+        };
+        let base = common_ancestor(tab, remote_tab);
+        let local_snapshot = tab.checkpoints.back().cloned().unwrap_or_default();
+        let remote_snapshot = remote_tab.checkpoints.back().cloned().unwrap_or_default();
+        let (snapshot, tab_conflicts) = merge_snapshots(&base, &local_snapshot, &remote_snapshot);
+        conflicts.extend(tab_conflicts.into_iter().map(|conflict| Conflict {
+            tab_id: tab.id,
+            tab_name: tab.name.clone(),
+            ..conflict
+        }));
+        if !snapshot.same_canvas(&local_snapshot) || snapshot.images != local_snapshot.images {
+            if (tab.checkpoints.len() as i32) > MAX_CHECKPOINTS {
+                tab.checkpoints.pop_front();
+            }
+            tab.checkpoints.push_back(snapshot);
+        }
+        for version in remote_tab.versions.iter() {
+            if !tab.versions.iter().any(|other| other.id == version.id) {
+                tab.versions.push(version.clone());
+            }
+        }
+        tab.versions.sort_by_key(|version| version.created);
+    }
+    merged.tags = normalize_tags(local.tags.iter().chain(remote.tags.iter()).cloned());
+    for (hash, data) in remote.images.iter() {
+        merged
+            .images
+            .entry(hash.clone())
+            .or_insert_with(|| data.clone());
+    }
+    (merged, conflicts)
+}
+
+/// The newest canvas both copies of a tab went through: a checkpoint or version they
+/// share. Tabs that have nothing in common merge against an empty canvas.
+pub fn common_ancestor(local: &Tab, remote: &Tab) -> TabSnapshot {
+    let remote_states: Vec<&TabSnapshot> = remote
+        .checkpoints
+        .iter()
+        .chain(remote.versions.iter().map(|version| &version.snapshot))
+        .collect();
+    let mut versions: Vec<_> = local.versions.iter().collect();
+    versions.sort_by_key(|version| Reverse(version.created));
+    let local_states = local
+        .checkpoints
+        .iter()
+        .rev()
+        .chain(versions.into_iter().map(|version| &version.snapshot));
+    for state in local_states {
+        if remote_states.iter().any(|other| other.same_canvas(state)) {
+            return state.clone();
+        }
+    }
+    TabSnapshot::default()
+}
+
+/// Three-way merge of two canvases that both started from `base`.
+/// The returned conflicts don't name their tab yet.
+pub fn merge_snapshots(
+    base: &TabSnapshot,
+    local: &TabSnapshot,
+    remote: &TabSnapshot,
+) -> (TabSnapshot, Vec<Conflict>) {
+    let mut merged = TabSnapshot::default();
+    let mut conflicts = vec![];
+    let conflict =
+        |node_id, kind, mine: Option<&JsonNode<String>>, theirs: Option<&JsonNode<String>>| {
+            let kept = if mine.is_some() { local } else { remote };
+            Conflict {
+                tab_id: ReflectableUuid::default(),
+                tab_name: String::new(),
+                node_id,
+                kind,
+                local: mine.cloned(),
+                remote: theirs.cloned(),
+                remote_image: theirs.and_then(|node| image(&remote.images, node)),
+                arrows: match kind {
+                    ConflictKind::Deleted => node_arrows(&kept.arrows, node_id),
+                    _ => vec![],
+                },
+            }
+        };
+    for node in local.nodes.iter() {
+        let base_node = base.nodes.iter().find(|other| other.id == node.id);
+        let remote_node = remote.nodes.iter().find(|other| other.id == node.id);
+        match (base_node, remote_node) {
+            (_, Some(remote_node)) => {
+                let (merged_node, kinds) = merge_node(base_node, node, remote_node);
+                let hash = merge_field(
+                    base_node.map(|node| image(&base.images, node)).as_ref(),
+                    &image(&local.images, node),
+                    &image(&remote.images, node),
+                )
+                .unwrap_or_else(|| image(&local.images, node));
+                if let Some(hash) = hash {
+                    merged.images.insert(node.id.to_string(), hash);
+                }
+                for kind in kinds {
+                    conflicts.push(conflict(node.id, kind, Some(node), Some(remote_node)));
+                }
+                merged.nodes.push(merged_node);
+            }
+            // deleted on the remote side
+            (Some(base_node), None) => {
+                if !same_node(base, base_node, local, node) {
+                    let deleted = conflict(node.id, ConflictKind::Deleted, Some(node), None);
+                    // the node stays until the conflict is resolved, and so do its arrows
+                    merged.arrows.extend(deleted.arrows.iter().copied());
+                    conflicts.push(deleted);
+                    keep_node(&mut merged, local, node);
+                }
+            }
+            // added on the local side
+            (None, None) => keep_node(&mut merged, local, node),
+        }
+    }
+    for node in remote.nodes.iter() {
+        if local.nodes.iter().any(|other| other.id == node.id) {
+            continue;
+        }
+        match base.nodes.iter().find(|other| other.id == node.id) {
+            // deleted on the local side, which stays until the conflict is resolved
+            Some(base_node) => {
+                if !same_node(base, base_node, remote, node) {
+                    conflicts.push(conflict(node.id, ConflictKind::Deleted, None, Some(node)));
+                }
+            }
+            None => keep_node(&mut merged, remote, node),
+        }
+    }
+
+    for arrow in local.arrows.iter() {
+        let base_arrow = find_arrow(&base.arrows, arrow);
+        match find_arrow(&remote.arrows, arrow) {
+            _ if find_arrow(&merged.arrows, arrow).is_some() => {}
+            Some(remote_arrow) => merged
+                .arrows
+                .push(merge_field(base_arrow, arrow, remote_arrow).unwrap_or(*arrow)),
+            None if base_arrow.is_none() => merged.arrows.push(*arrow),
+            None => {}
+        }
+    }
+    for arrow in remote.arrows.iter() {
+        if find_arrow(&local.arrows, arrow).is_none() && find_arrow(&base.arrows, arrow).is_none() {
+            merged.arrows.push(*arrow);
+        }
+    }
+    merged.arrows.retain(|arrow| {
+        let exists = |id: Uuid| merged.nodes.iter().any(|node| node.id == id);
+        exists(arrow.start.id.0) && exists(arrow.end.id.0)
+    });
+
+    for drawing in local.drawings.iter() {
+        let base_drawing = base.drawings.iter().find(|other| other.id == drawing.id);
+        match remote.drawings.iter().find(|other| other.id == drawing.id) {
+            Some(remote_drawing) => merged.drawings.push(
+                merge_field(base_drawing, drawing, remote_drawing)
+                    .unwrap_or_else(|| drawing.clone()),
+            ),
+            // an edit wins over a deletion on the other side
+            None if base_drawing != Some(drawing) => merged.drawings.push(drawing.clone()),
+            None => {}
+        }
+    }
+    for drawing in remote.drawings.iter() {
+        if local.drawings.iter().any(|other| other.id == drawing.id) {
+            continue;
+        }
+        if base.drawings.iter().find(|other| other.id == drawing.id) != Some(drawing) {
+            merged.drawings.push(drawing.clone());
+        }
+    }
+    merged.normalize();
+    (merged, conflicts)
+}
+
+/// Apply one side of a conflict to the last checkpoint of its tab.
+pub fn resolve_conflict(doc: &mut Doc, conflict: &Conflict, side: Side) {
+    let Some(tab) = doc.tabs.iter_mut().find(|tab| tab.id == conflict.tab_id) else {
+        return;
+    };
+    let Some(snapshot) = tab.checkpoints.back_mut() else {
+        return;
+    };
+    let chosen = match side {
+        Side::Local => conflict.local.as_ref(),
+        Side::Remote => conflict.remote.as_ref(),
+    };
+    let index = snapshot
+        .nodes
+        .iter()
+        .position(|node| node.id == conflict.node_id);
+    match (chosen, index) {
+        (None, Some(index)) => {
+            snapshot.nodes.remove(index);
+            snapshot.images.remove(&conflict.node_id.to_string());
+            snapshot.arrows.retain(|arrow| {
+                arrow.start.id.0 != conflict.node_id && arrow.end.id.0 != conflict.node_id
+            });
+        }
+        (Some(node), None) => {
+            snapshot.nodes.push(node.clone());
+            if let Some(hash) = &conflict.remote_image {
+                snapshot.images.insert(node.id.to_string(), hash.clone());
+            }
+            for arrow in conflict.arrows.iter() {
+                let exists = |id: Uuid| snapshot.nodes.iter().any(|node| node.id == id);
+                if exists(arrow.start.id.0)
+                    && exists(arrow.end.id.0)
+                    && find_arrow(&snapshot.arrows, arrow).is_none()
+                {
+                    snapshot.arrows.push(*arrow);
+                }
+            }
+            snapshot.normalize();
+        }
+        (Some(node), Some(index)) => {
+            let merged = &mut snapshot.nodes[index];
+            match conflict.kind {
+                ConflictKind::Text => merged.text = node.text.clone(),
+                ConflictKind::Position => {
+                    merged.x = node.x;
+                    merged.y = node.y;
+                    merged.width = node.width;
+                    merged.height = node.height;
+                }
+                ConflictKind::Style => {
+                    merged.bg_color = node.bg_color.clone();
+                    merged.node_type = node.node_type.clone();
+                }
+                ConflictKind::Tags => merged.tags = node.tags.clone(),
+                ConflictKind::Deleted => *merged = node.clone(),
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+/// Merge a node present on both sides, property group by property group.
+/// Text, position, style and tags conflict; visibility and z order keep the local side.
+fn merge_node(
+    base: Option<&JsonNode<String>>,
+    local: &JsonNode<String>,
+    remote: &JsonNode<String>,
+) -> (JsonNode<String>, Vec<ConflictKind>) {
+    let mut merged = local.clone();
+    let mut conflicts = vec![];
+    match merge_field(base.map(|node| &node.text), &local.text, &remote.text) {
+        Some(text) => merged.text = text,
+        None => conflicts.push(ConflictKind::Text),
+    }
+    let position = |node: &JsonNode<String>| (node.x, node.y, node.width, node.height);
+    match merge_field(
+        base.map(position).as_ref(),
+        &position(local),
+        &position(remote),
+    ) {
+        Some((x, y, width, height)) => {
+            merged.x = x;
+            merged.y = y;
+            merged.width = width;
+            merged.height = height;
+        }
+        None => conflicts.push(ConflictKind::Position),
+    }
+    let style = |node: &JsonNode<String>| (node.bg_color.clone(), node.node_type.clone());
+    match merge_field(base.map(style).as_ref(), &style(local), &style(remote)) {
+        Some((bg_color, node_type)) => {
+            merged.bg_color = bg_color;
+            merged.node_type = node_type;
+        }
+        None => conflicts.push(ConflictKind::Style),
+    }
+    if let Some(visible) = merge_field(
+        base.map(|node| &node.visible),
+        &local.visible,
+        &remote.visible,
+    ) {
+        merged.visible = visible;
+    }
+    if let Some(z) = merge_field(base.map(|node| &node.z), &local.z, &remote.z) {
+        merged.z = z;
+    }
+    match merge_field(base.map(|node| &node.tags), &local.tags, &remote.tags) {
+        Some(tags) => merged.tags = tags,
+        None => conflicts.push(ConflictKind::Tags),
+    }
+    (merged, conflicts)
+}
+
+/// Three-way merge of a single value: a side that left it as it was takes the value of
+/// the other side. `None` if both sides changed it differently.
+fn merge_field<T: PartialEq + Clone>(base: Option<&T>, local: &T, remote: &T) -> Option<T> {
+    if local == remote || base == Some(remote) {
+        Some(local.clone())
+    } else if base == Some(local) {
+        Some(remote.clone())
+    } else {
+        None
+    }
+}
+
+fn keep_node(merged: &mut TabSnapshot, from: &TabSnapshot, node: &JsonNode<String>) {
+    if let Some(hash) = image(&from.images, node) {
+        merged.images.insert(node.id.to_string(), hash);
+    }
+    merged.nodes.push(node.clone());
+}
+
+fn same_node(
+    a: &TabSnapshot,
+    a_node: &JsonNode<String>,
+    b: &TabSnapshot,
+    b_node: &JsonNode<String>,
+) -> bool {
+    a_node == b_node && image(&a.images, a_node) == image(&b.images, b_node)
+}
+
+fn image(images: &HashMap<String, String>, node: &JsonNode<String>) -> Option<String> {
+    images.get(&node.id.to_string()).cloned()
+}
+
+fn node_arrows(arrows: &[ArrowMeta], node_id: Uuid) -> Vec<ArrowMeta> {
+    arrows
+        .iter()
+        .filter(|arrow| arrow.start.id.0 == node_id || arrow.end.id.0 == node_id)
+        .copied()
+        .collect()
+}
+
+fn find_arrow<'a>(arrows: &'a [ArrowMeta], arrow: &ArrowMeta) -> Option<&'a ArrowMeta> {
+    arrows
+        .iter()
+        .find(|other| arrow_key(other) == arrow_key(arrow))
+}
+
+fn arrow_key(arrow: &ArrowMeta) -> (Uuid, Uuid, ArrowType) {
+    (arrow.start.id.0, arrow.end.id.0, arrow.arrow_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+
+    #[test]
+    fn test_merge_docs() {
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let base = doc.tabs[0].checkpoints.back().unwrap().clone();
+        let id = |index: usize| base.nodes[index].id;
+        let (mut local, mut remote) = (doc.clone(), doc.clone());
+        let edit = |doc: &mut Doc, change: &dyn Fn(&mut TabSnapshot)| {
+            let mut snapshot = doc.tabs[0].checkpoints.back().unwrap().clone();
+            change(&mut snapshot);
+            doc.tabs[0].checkpoints.push_back(snapshot);
+        };
+        edit(&mut local, &|snapshot| {
+            snapshot.nodes[0].text.text = "mine".to_string();
+            snapshot.nodes[1].x += 10.;
+        });
+        edit(&mut remote, &|snapshot| {
+            snapshot.nodes[0].text.text = "theirs".to_string();
+            snapshot.nodes[1].bg_color = "#000000".to_string();
+            snapshot.nodes[3].tags.push("remote".to_string());
+            let removed = snapshot.nodes.remove(2);
+            snapshot
+                .arrows
+                .retain(|arrow| arrow.start.id.0 != removed.id && arrow.end.id.0 != removed.id);
+            let mut added = snapshot.nodes[0].clone();
+            added.id = Uuid::new_v4();
+            snapshot.nodes.push(added);
+        });
+        remote.tags.push("shared".to_string());
+
+        let (mut merged, conflicts) = merge_docs(&local, &remote);
+        assert!(merged.tags.contains(&"shared".to_string()));
+        let tab = &merged.tabs[0];
+        assert_eq!(tab.checkpoints.len(), local.tabs[0].checkpoints.len() + 1);
+        let snapshot = tab.checkpoints.back().unwrap();
+        let node = |id: Uuid| snapshot.nodes.iter().find(|node| node.id == id);
+        assert_eq!(snapshot.nodes.len(), base.nodes.len());
+        assert!(node(id(2)).is_none());
+        assert_eq!(node(id(0)).unwrap().text.text, "mine");
+        assert_eq!(node(id(1)).unwrap().x, base.nodes[1].x + 10.);
+        assert_eq!(node(id(1)).unwrap().bg_color, "#000000");
+        assert_eq!(
+            node(id(3)).unwrap().tags.len(),
+            base.nodes[3].tags.len() + 1
+        );
+        assert!(snapshot
+            .arrows
+            .iter()
+            .all(|arrow| arrow.start.id.0 != id(2) && arrow.end.id.0 != id(2)));
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].node_id, id(0));
+        assert_eq!(conflicts[0].kind, ConflictKind::Text);
+        resolve_conflict(&mut merged, &conflicts[0], Side::Remote);
+        let snapshot = merged.tabs[0].checkpoints.back().unwrap();
+        let node = snapshot.nodes.iter().find(|node| node.id == id(0)).unwrap();
+        assert_eq!(node.text.text, "theirs");
+
+        // merging a copy that has nothing new changes nothing
+        let (unchanged, conflicts) = merge_docs(&local, &doc);
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            serde_json::to_value(&unchanged).unwrap(),
+            serde_json::to_value(&local).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let base = doc.tabs[0].checkpoints.back().unwrap().clone();
+        let arrows = node_arrows(&base.arrows, base.nodes[2].id);
+        assert!(!arrows.is_empty());
+        let (mut local, mut remote) = (doc.clone(), doc.clone());
+        let edit = |doc: &mut Doc, change: &dyn Fn(&mut TabSnapshot)| {
+            let mut snapshot = doc.tabs[0].checkpoints.back().unwrap().clone();
+            change(&mut snapshot);
+            doc.tabs[0].checkpoints.push_back(snapshot);
+        };
+        edit(&mut local, &|snapshot| {
+            snapshot.nodes[0].tags = vec!["mine".to_string()];
+            let removed = snapshot.nodes.remove(2);
+            snapshot
+                .arrows
+                .retain(|arrow| arrow.start.id.0 != removed.id && arrow.end.id.0 != removed.id);
+        });
+        edit(&mut remote, &|snapshot| {
+            snapshot.nodes[0].tags = vec!["theirs".to_string()];
+            snapshot.nodes[2].text.text = "edited".to_string();
+        });
+
+        let (mut merged, conflicts) = merge_docs(&local, &remote);
+        assert_eq!(conflicts.len(), 2);
+        let tags = conflicts
+            .iter()
+            .find(|conflict| conflict.kind == ConflictKind::Tags)
+            .unwrap();
+        let deleted = conflicts
+            .iter()
+            .find(|conflict| conflict.kind == ConflictKind::Deleted)
+            .unwrap();
+        assert_eq!(deleted.arrows, arrows);
+
+        resolve_conflict(&mut merged, tags, Side::Remote);
+        resolve_conflict(&mut merged, deleted, Side::Remote);
+        let snapshot = merged.tabs[0].checkpoints.back().unwrap();
+        let node = |id: Uuid| snapshot.nodes.iter().find(|node| node.id == id).unwrap();
+        assert_eq!(node(base.nodes[0].id).tags, vec!["theirs".to_string()]);
+        assert_eq!(node(base.nodes[2].id).text.text, "edited");
+        for arrow in arrows.iter() {
+            assert!(find_arrow(&snapshot.arrows, arrow).is_some());
+        }
+
+        // deleted on the remote side, the local node keeps its arrows
+        let (merged, conflicts) = merge_docs(&remote, &local);
+        assert_eq!(conflicts.len(), 2);
+        let snapshot = merged.tabs[0].checkpoints.back().unwrap();
+        for arrow in arrows.iter() {
+            assert!(find_arrow(&snapshot.arrows, arrow).is_some());
+        }
+    }
+}
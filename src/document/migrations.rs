@@ -0,0 +1,325 @@
+use base64::{engine::general_purpose, Engine};
+use serde_json::{Map, Value};
+
+use crate::components::Doc;
+
+use super::{image_hash, normalize_tags};
+
+/// Schema version stamped into every persisted `Doc`.
+/// Bump it together with a new step in `MIGRATIONS` whenever `Doc`, `Tab`,
+/// `JsonNode` or `ArrowMeta` change shape.
+pub const DOC_SCHEMA_VERSION: u32 = 5;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), DocError>;
+
+/// `MIGRATIONS[n]` upgrades a document from version `n` to version `n + 1`.
+const MIGRATIONS: [Migration; DOC_SCHEMA_VERSION as usize] =
+    [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+#[derive(Debug)]
+pub enum DocError {
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    Malformed(String),
+}
+
+impl std::fmt::Display for DocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocError::Json(e) => write!(f, "invalid document: {}", e),
+            DocError::UnsupportedVersion(version) => write!(
+                f,
+                "document schema version {} is newer than supported version {}",
+                version, DOC_SCHEMA_VERSION
+            ),
+            DocError::Malformed(reason) => write!(f, "malformed document: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DocError {}
+
+impl From<serde_json::Error> for DocError {
+    fn from(e: serde_json::Error) -> Self {
+        DocError::Json(e)
+    }
+}
+
+/// Parse a document from JSON (exported file, URL, gist), upgrading it to the current schema.
+pub fn doc_from_json(json: &str) -> Result<Doc, DocError> {
+    doc_from_value(serde_json::from_str(json)?)
+}
+
+pub fn doc_from_value(value: Value) -> Result<Doc, DocError> {
+    Ok(serde_json::from_value(migrate(value)?)?)
+}
+
+/// Run every migration step between the document's version and `DOC_SCHEMA_VERSION`.
+/// Documents without a `version` field predate versioning and are treated as version 0.
+pub fn migrate(mut value: Value) -> Result<Value, DocError> {
+    let doc = value
+        .as_object_mut()
+        .ok_or_else(|| DocError::Malformed("document is not an object".to_string()))?;
+    let version = match doc.get("version") {
+        Some(version) => {
+            let version = version
+                .as_u64()
+                .ok_or_else(|| DocError::Malformed("version is not a number".to_string()))?;
+            u32::try_from(version)
+                .map_err(|_| DocError::Malformed(format!("version {} is out of range", version)))?
+        }
+        None => 0,
+    };
+    if version > DOC_SCHEMA_VERSION {
+        return Err(DocError::UnsupportedVersion(version));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(doc)?;
+        doc.insert("version".to_string(), Value::from(from as u32 + 1));
+    }
+    Ok(value)
+}
+
+fn object_mut<'a>(
+    value: &'a mut Value,
+    what: &str,
+) -> Result<&'a mut Map<String, Value>, DocError> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| DocError::Malformed(format!("{} is not an object", what)))
+}
+
+fn array_mut<'a>(value: &'a mut Value, what: &str) -> Result<&'a mut Vec<Value>, DocError> {
+    value
+        .as_array_mut()
+        .ok_or_else(|| DocError::Malformed(format!("{} is not an array", what)))
+}
+
+/// Unversioned documents: fills in fields that were added before versioning existed
+/// (`Doc.tags`, `Tab.z_index`, node and arrow `visible`, node `z`, checkpoint `drawings`).
+fn v0_to_v1(doc: &mut Map<String, Value>) -> Result<(), DocError> {
+    doc.entry("tags").or_insert_with(|| Value::Array(vec![]));
+    let tabs = doc
+        .get_mut("tabs")
+        .ok_or_else(|| DocError::Malformed("missing tabs".to_string()))?;
+    for tab in array_mut(tabs, "tabs")? {
+        let tab = object_mut(tab, "tab")?;
+        tab.entry("z_index").or_insert_with(|| Value::from(1.));
+        let Some(checkpoints) = tab.get_mut("checkpoints") else {
+            tab.insert("checkpoints".to_string(), Value::Array(vec![]));
+            continue;
+        };
+        for checkpoint in array_mut(checkpoints, "checkpoints")? {
+            // documents read from storage already hold typed snapshots
+            *checkpoint = match checkpoint.take() {
+                Value::String(json) => Value::String(
+                    upgrade_legacy_checkpoint(serde_json::from_str(&json)?)?.to_string(),
+                ),
+                snapshot => upgrade_legacy_checkpoint(snapshot)?,
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Checkpoints used to be JSON encoded strings; they are now stored as `TabSnapshot` objects.
+fn v1_to_v2(doc: &mut Map<String, Value>) -> Result<(), DocError> {
+    let tabs = doc
+        .get_mut("tabs")
+        .ok_or_else(|| DocError::Malformed("missing tabs".to_string()))?;
+    for tab in array_mut(tabs, "tabs")? {
+        let tab = object_mut(tab, "tab")?;
+        let Some(checkpoints) = tab.get_mut("checkpoints") else {
+            continue;
+        };
+        for checkpoint in array_mut(checkpoints, "checkpoints")? {
+            if let Some(json) = checkpoint.as_str() {
+                *checkpoint = upgrade_legacy_checkpoint(serde_json::from_str(json)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checkpoints used to embed base64 images keyed by node id; images now live once
+/// in `Doc.images` under their content hash and checkpoints only keep the hash.
+fn v2_to_v3(doc: &mut Map<String, Value>) -> Result<(), DocError> {
+    let mut images = Map::new();
+    let tabs = doc
+        .get_mut("tabs")
+        .ok_or_else(|| DocError::Malformed("missing tabs".to_string()))?;
+    for tab in array_mut(tabs, "tabs")? {
+        let tab = object_mut(tab, "tab")?;
+        let Some(checkpoints) = tab.get_mut("checkpoints") else {
+            continue;
+        };
+        for checkpoint in array_mut(checkpoints, "checkpoints")? {
+            let Some(node_images) = object_mut(checkpoint, "checkpoint")?.get_mut("images") else {
+                continue;
+            };
+            for image in object_mut(node_images, "images")?.values_mut() {
+                let base64 = image
+                    .as_str()
+                    .ok_or_else(|| DocError::Malformed("image is not a string".to_string()))?;
+                let png = general_purpose::STANDARD
+                    .decode(base64)
+                    .map_err(|e| DocError::Malformed(format!("invalid image: {}", e)))?;
+                let hash = image_hash(&png);
+                images.insert(hash.clone(), image.take());
+                *image = Value::String(hash);
+            }
+        }
+    }
+    doc.insert("images".to_string(), Value::Object(images));
+    Ok(())
+}
+
+/// Nodes gained tags, and document tags that piled up on every save are deduplicated.
+fn v3_to_v4(doc: &mut Map<String, Value>) -> Result<(), DocError> {
+    if let Some(tags) = doc.get_mut("tags") {
+        let tags: Vec<String> = serde_json::from_value(tags.take())?;
+        doc.insert("tags".to_string(), Value::from(normalize_tags(tags)));
+    }
+    let tabs = doc
+        .get_mut("tabs")
+        .ok_or_else(|| DocError::Malformed("missing tabs".to_string()))?;
+    for tab in array_mut(tabs, "tabs")? {
+        let tab = object_mut(tab, "tab")?;
+        let Some(checkpoints) = tab.get_mut("checkpoints") else {
+            continue;
+        };
+        for checkpoint in array_mut(checkpoints, "checkpoints")? {
+            let Some(nodes) = object_mut(checkpoint, "checkpoint")?.get_mut("nodes") else {
+                continue;
+            };
+            for node in array_mut(nodes, "nodes")? {
+                object_mut(node, "node")?
+                    .entry("tags")
+                    .or_insert_with(|| Value::Array(vec![]));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Tabs gained named versions.
+fn v4_to_v5(doc: &mut Map<String, Value>) -> Result<(), DocError> {
+    let tabs = doc
+        .get_mut("tabs")
+        .ok_or_else(|| DocError::Malformed("missing tabs".to_string()))?;
+    for tab in array_mut(tabs, "tabs")? {
+        object_mut(tab, "tab")?
+            .entry("versions")
+            .or_insert_with(|| Value::Array(vec![]));
+    }
+    Ok(())
+}
+
+/// Fills in checkpoint fields that unversioned documents may lack.
+pub fn upgrade_legacy_checkpoint(mut snapshot: Value) -> Result<Value, DocError> {
+    let map = object_mut(&mut snapshot, "checkpoint")?;
+    map.entry("images")
+        .or_insert_with(|| Value::Object(Map::new()));
+    map.entry("drawings")
+        .or_insert_with(|| Value::Array(vec![]));
+    let nodes = map.entry("nodes").or_insert(Value::Array(vec![]));
+    for node in array_mut(nodes, "nodes")? {
+        let node = object_mut(node, "node")?;
+        node.entry("visible").or_insert(Value::Bool(true));
+        node.entry("z").or_insert_with(|| Value::from(1.));
+    }
+    let arrows = map.entry("arrows").or_insert(Value::Array(vec![]));
+    for arrow in array_mut(arrows, "arrows")? {
+        object_mut(arrow, "arrow")?
+            .entry("visible")
+            .or_insert(Value::Bool(true));
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_legacy_doc() {
+        let doc = doc_from_json(include_str!("fixtures/doc_v0_legacy.json")).unwrap();
+        assert_eq!(doc.version, DOC_SCHEMA_VERSION);
+        assert_eq!(doc.name, "Legacy board");
+        assert!(doc.tags.is_empty());
+        assert_eq!(doc.tabs[0].z_index, 1.);
+        let snapshot = &doc.tabs[0].checkpoints[0];
+        assert_eq!(snapshot.nodes.len(), 2);
+        assert!(snapshot.nodes[0].visible);
+        assert_eq!(snapshot.nodes[0].z, 1.);
+        assert!(snapshot.arrows[0].visible);
+        assert!(snapshot.drawings.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_unversioned_doc() {
+        let doc = doc_from_json(include_str!("fixtures/doc_v0.json")).unwrap();
+        assert_eq!(doc.version, DOC_SCHEMA_VERSION);
+        assert_eq!(doc.tags, vec!["planning".to_string()]);
+        assert_eq!(doc.tabs.len(), 2);
+        assert_eq!(doc.tabs[0].checkpoints.len(), 2);
+        let snapshot = &doc.tabs[0].checkpoints[1];
+        assert!(!snapshot.nodes[1].visible);
+        assert_eq!(snapshot.drawings[0].points.len(), 3);
+        assert_eq!(snapshot.images.len(), 1);
+        // images are moved out of the checkpoints into the document store
+        let hash = snapshot.images.values().next().unwrap();
+        assert_eq!(doc.images.len(), 1);
+        let png = general_purpose::STANDARD.decode(&doc.images[hash]).unwrap();
+        assert_eq!(&image_hash(&png), hash);
+    }
+
+    #[test]
+    fn test_migrate_string_checkpoints() {
+        let doc = doc_from_json(include_str!("fixtures/doc_v1.json")).unwrap();
+        assert_eq!(doc.version, DOC_SCHEMA_VERSION);
+        assert_eq!(doc.name, "Board v1");
+        let snapshot = &doc.tabs[0].checkpoints[1];
+        assert_eq!(snapshot.nodes[0].text.text, "# Idea\nfirst");
+        assert_eq!(snapshot.arrows.len(), 1);
+        // typed snapshots survive a round trip without going through the migrations
+        let json = serde_json::to_string(&doc).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert!(value["tabs"][0]["checkpoints"][1].is_object());
+        let reloaded = doc_from_json(&json).unwrap();
+        assert_eq!(reloaded.tabs[0].checkpoints, doc.tabs[0].checkpoints);
+    }
+
+    #[test]
+    fn test_migrate_tags() {
+        let mut value: Value = serde_json::from_str(include_str!("fixtures/doc_v1.json")).unwrap();
+        value["tags"] = serde_json::json!(["idea", "todo", "idea", "#Todo"]);
+        let doc = doc_from_value(value).unwrap();
+        assert_eq!(doc.tags, vec!["idea".to_string(), "todo".to_string()]);
+        assert!(doc.tabs[0].checkpoints[1].nodes[0].tags.is_empty());
+    }
+
+    #[test]
+    fn test_reject_newer_version() {
+        let mut value: Value = serde_json::from_str(include_str!("fixtures/doc_v0.json")).unwrap();
+        value["version"] = Value::from(DOC_SCHEMA_VERSION + 1);
+        assert!(matches!(
+            doc_from_value(value),
+            Err(DocError::UnsupportedVersion(_))
+        ));
+
+        // doesn't wrap around to a supported version
+        let mut value: Value = serde_json::from_str(include_str!("fixtures/doc_v0.json")).unwrap();
+        value["version"] = Value::from(u64::from(u32::MAX) + 1);
+        assert!(matches!(doc_from_value(value), Err(DocError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_reject_malformed_doc() {
+        assert!(matches!(
+            doc_from_json(r#"{"id": "1f0e9d8c-7b6a-4f5e-8d4c-3b2a1f0e9d88", "name": "x"}"#),
+            Err(DocError::Malformed(_))
+        ));
+        assert!(matches!(doc_from_json("not json"), Err(DocError::Json(_))));
+    }
+}
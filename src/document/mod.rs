@@ -0,0 +1,35 @@
+pub mod bundle;
+pub mod canonical;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod folder_sync;
+pub mod history;
+pub mod images;
+pub mod integrity;
+pub mod journal;
+pub mod merge;
+pub mod migrations;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod search_index;
+pub mod snapshot;
+pub mod store;
+pub mod tags;
+pub mod versions;
+pub mod zip;
+
+pub use bundle::*;
+pub use canonical::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use folder_sync::*;
+pub use history::*;
+pub use images::*;
+pub use integrity::*;
+pub use journal::*;
+pub use merge::*;
+pub use migrations::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use search_index::*;
+pub use snapshot::*;
+pub use store::*;
+pub use tags::*;
+pub use versions::*;
+pub use zip::*;
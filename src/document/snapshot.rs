@@ -0,0 +1,201 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::{DrawingJsonNode, JsonNode};
+
+use super::{round_snapshot, upgrade_legacy_checkpoint, DocError};
+
+/// Everything needed to rebuild a tab on the canvas.
+/// Stored in `Tab.checkpoints`; `save_tab` produces it and `load_tab` consumes it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TabSnapshot {
+    pub nodes: Vec<JsonNode<String>>,
+    pub arrows: Vec<ArrowMeta>,
+    pub drawings: Vec<DrawingJsonNode<String>>,
+    /// Node background images: node id to image hash in `Doc.images`.
+    pub images: HashMap<String, String>,
+}
+
+impl TabSnapshot {
+    /// Parse a checkpoint written as a JSON string by versions before typed snapshots.
+    pub fn from_legacy_json(json: &str) -> Result<Self, DocError> {
+        let value = upgrade_legacy_checkpoint(serde_json::from_str(json)?)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Sort nodes, arrows and drawings, so snapshots of the same canvas compare equal
+    /// regardless of entity iteration order.
+    pub fn normalize(&mut self) {
+        self.nodes.sort_by_key(|node| node.id);
+        self.arrows.sort_by_key(|arrow| {
+            (
+                arrow.start.id.0,
+                arrow.start.pos as u8,
+                arrow.end.id.0,
+                arrow.end.pos as u8,
+                arrow.arrow_type as u8,
+                arrow.visible,
+            )
+        });
+        self.drawings.sort_by_key(|drawing| drawing.id.0);
+    }
+
+    /// Whether both snapshots show the same canvas.
+    /// Images are compared by which nodes have one, not by content, and numbers at the
+    /// precision documents are written with, so a canvas matches the file it was saved to.
+    pub fn same_canvas(&self, other: &TabSnapshot) -> bool {
+        let (mut this, mut other) = (round_snapshot(self), round_snapshot(other));
+        this.normalize();
+        other.normalize();
+        this.nodes == other.nodes
+            && this.arrows == other.arrows
+            && this.drawings == other.drawings
+            && this.images.len() == other.images.len()
+            && this.images.keys().all(|id| other.images.contains_key(id))
+    }
+}
+
+/// Accepts both typed snapshots and legacy JSON string checkpoints,
+/// so documents kept in storage keep loading after the format change.
+pub fn deserialize_checkpoints<'de, D>(deserializer: D) -> Result<VecDeque<TabSnapshot>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct CheckpointsVisitor;
+
+    impl<'de> Visitor<'de> for CheckpointsVisitor {
+        type Value = VecDeque<TabSnapshot>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a list of tab snapshots")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut checkpoints = VecDeque::new();
+            while let Some(CheckpointRepr(snapshot)) = seq.next_element()? {
+                checkpoints.push_back(snapshot);
+            }
+            Ok(checkpoints)
+        }
+    }
+
+    deserializer.deserialize_seq(CheckpointsVisitor)
+}
+
+struct CheckpointRepr(TabSnapshot);
+
+impl<'de> Deserialize<'de> for CheckpointRepr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CheckpointVisitor;
+
+        impl<'de> Visitor<'de> for CheckpointVisitor {
+            type Value = CheckpointRepr;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a tab snapshot or a JSON encoded checkpoint")
+            }
+
+            fn visit_str<E>(self, json: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                TabSnapshot::from_legacy_json(json)
+                    .map(CheckpointRepr)
+                    .map_err(E::custom)
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                TabSnapshot::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(CheckpointRepr)
+            }
+        }
+
+        deserializer.deserialize_any(CheckpointVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_pkv::PkvStore;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::components::Tab;
+    use crate::utils::ReflectableUuid;
+
+    /// `Tab` as it was persisted before typed snapshots.
+    #[derive(Serialize)]
+    struct LegacyTab {
+        is_active: bool,
+        id: ReflectableUuid,
+        name: String,
+        checkpoints: VecDeque<String>,
+        z_index: f32,
+    }
+
+    #[test]
+    fn test_load_legacy_checkpoints_from_store() {
+        let mut pkv = PkvStore::new("test", "snapshot");
+        pkv.clear().unwrap();
+        let legacy = LegacyTab {
+            is_active: true,
+            id: ReflectableUuid::generate(),
+            name: "Tab 1".to_string(),
+            checkpoints: VecDeque::from(vec![
+                r#"{"images": {}, "nodes": [], "arrows": []}"#.to_string()
+            ]),
+            z_index: 1.,
+        };
+        pkv.set("tab", &legacy).unwrap();
+
+        let mut tab: Tab = pkv.get("tab").unwrap();
+        assert_eq!(
+            tab.checkpoints,
+            VecDeque::from(vec![TabSnapshot::default()])
+        );
+
+        tab.checkpoints.push_back(TabSnapshot {
+            images: HashMap::from([("id".to_string(), "data".to_string())]),
+            ..Default::default()
+        });
+        pkv.set("tab", &tab).unwrap();
+        let stored: Tab = pkv.get("tab").unwrap();
+        assert_eq!(stored.checkpoints, tab.checkpoints);
+    }
+
+    #[test]
+    fn test_same_canvas() {
+        let doc = crate::document::doc_from_json(include_str!("fixtures/doc_v0.json")).unwrap();
+        let snapshot = doc.tabs[0].checkpoints.back().unwrap().clone();
+        assert!(snapshot.nodes.len() > 1);
+        // node order and image hashes don't matter, only what is on the canvas
+        let mut reordered = snapshot.clone();
+        reordered.nodes.reverse();
+        for hash in reordered.images.values_mut() {
+            hash.clear();
+        }
+        assert!(snapshot.same_canvas(&reordered));
+        // noise below the precision documents are written with doesn't count
+        reordered.nodes[0].x += 0.0001;
+        assert!(snapshot.same_canvas(&reordered));
+        reordered.nodes[0].x += 1.;
+        assert!(!snapshot.same_canvas(&reordered));
+        reordered.nodes[0].x -= 1.;
+        reordered.nodes[0].text.text.push('!');
+        assert!(!snapshot.same_canvas(&reordered));
+        assert!(!snapshot.same_canvas(&TabSnapshot::default()));
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_pkv::{PkvStore, SetError};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::components::Doc;
+use crate::utils::ReflectableUuid;
+
+use super::{doc_from_value, normalize_tags, DocError};
+
+pub const DOC_INDEX_KEY: &str = "doc_index";
+
+/// Names and tags of every stored document, kept under a single key so the document list
+/// and search don't have to deserialize whole documents.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct DocIndex {
+    pub names: HashMap<ReflectableUuid, String>,
+    pub tags: HashMap<ReflectableUuid, Vec<String>>,
+}
+
+pub fn doc_key(doc_id: ReflectableUuid) -> String {
+    format!("doc_{}", doc_id.0)
+}
+
+pub fn load_doc_index(pkv: &PkvStore) -> DocIndex {
+    pkv.get(DOC_INDEX_KEY).unwrap_or_default()
+}
+
+/// Read a stored document and upgrade it to the current schema. Documents are read untyped,
+/// so ones stored before a field was added still load. `None` if there is no such document.
+pub fn load_stored_doc(pkv: &PkvStore, doc_id: ReflectableUuid) -> Option<Result<Doc, DocError>> {
+    match pkv.get::<StoredValue>(&doc_key(doc_id)) {
+        Ok(StoredValue(Value::Null)) | Err(bevy_pkv::GetError::NotFound) => None,
+        Ok(StoredValue(value)) => Some(doc_from_value(value)),
+        Err(e) => Some(Err(DocError::Malformed(e.to_string()))),
+    }
+}
+
+pub fn store_doc(pkv: &mut PkvStore, doc: &Doc) -> Result<(), SetError> {
+    pkv.set(doc_key(doc.id), doc)?;
+    let mut index = load_doc_index(pkv);
+    index
+        .tags
+        .insert(doc.id, normalize_tags(doc.tags.iter().cloned()));
+    index.names.insert(doc.id, doc.name.clone());
+    pkv.set(DOC_INDEX_KEY, &index)
+}
+
+/// `PkvStore` can't delete keys, so the document entry is overwritten with an empty value.
+pub fn remove_stored_doc(pkv: &mut PkvStore, doc_id: ReflectableUuid) -> Result<(), SetError> {
+    let mut index = load_doc_index(pkv);
+    if index.names.remove(&doc_id).is_none() {
+        return Ok(());
+    }
+    index.tags.remove(&doc_id);
+    pkv.set(DOC_INDEX_KEY, &index)?;
+    pkv.set(doc_key(doc_id), &())
+}
+
+/// Untyped stored value. The native store is MessagePack, where uuids are byte arrays that
+/// `serde_json::Value` can't hold, so they are read back as uuid strings.
+struct StoredValue(Value);
+
+impl<'de> Deserialize<'de> for StoredValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(StoredValueVisitor)
+            .map(StoredValue)
+    }
+}
+
+struct StoredValueVisitor;
+
+impl<'de> Visitor<'de> for StoredValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a stored value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Value, E> {
+        Ok(match Uuid::from_slice(value) {
+            Ok(uuid) => Value::String(uuid.to_string()),
+            Err(_) => value.iter().map(|byte| Value::from(*byte)).collect(),
+        })
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = vec![];
+        while let Some(StoredValue(value)) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut values = Map::new();
+        while let Some((StoredValue(key), StoredValue(value))) = map.next_entry()? {
+            let key = match key {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            values.insert(key, value);
+        }
+        Ok(Value::Object(values))
+    }
+}
+
+/// Move from the "docs", "names" and "tags" maps to a key per document. Each document is
+/// migrated on its own: the ones that fail are logged and left in the "docs" map, which is
+/// migrated again on next start, so a single bad document can't lose the others.
+pub fn migrate_storage(pkv: &mut PkvStore) {
+    let docs = match pkv.get::<StoredValue>("docs") {
+        Ok(StoredValue(Value::Object(docs))) if !docs.is_empty() => docs,
+        Ok(_) | Err(bevy_pkv::GetError::NotFound) => return,
+        Err(e) => {
+            error!("Failed to read documents for storage migration: {}", e);
+            return;
+        }
+    };
+    let tags = pkv
+        .get::<HashMap<ReflectableUuid, Vec<String>>>("tags")
+        .unwrap_or_default();
+    let mut index = load_doc_index(pkv);
+    let mut failed = Map::new();
+    for (key, value) in docs {
+        let Ok(id) = Uuid::parse_str(&key).map(ReflectableUuid) else {
+            error!("Failed to migrate document {}: invalid id", key);
+            failed.insert(key, value);
+            continue;
+        };
+        // already moved by an earlier run that didn't finish
+        if load_stored_doc(pkv, id).is_some() {
+            continue;
+        }
+        match doc_from_value(value.clone()) {
+            Ok(doc) => {
+                if let Err(e) = pkv.set(doc_key(id), &doc) {
+                    error!("Failed to migrate document {}: {}", key, e);
+                    failed.insert(key, value);
+                    continue;
+                }
+                index.names.insert(id, doc.name.clone());
+                if let Some(tags) = tags.get(&id) {
+                    index.tags.insert(id, tags.clone());
+                }
+            }
+            Err(e) => {
+                error!("Failed to migrate document {}: {}", key, e);
+                failed.insert(key, value);
+            }
+        }
+    }
+    if let Err(e) = finish_migration(pkv, &index, &failed) {
+        error!("Failed to finish storage migration: {}", e);
+    }
+}
+
+/// Store the index, and clear the legacy maps unless some documents are left in them.
+fn finish_migration(
+    pkv: &mut PkvStore,
+    index: &DocIndex,
+    failed: &Map<String, Value>,
+) -> Result<(), SetError> {
+    pkv.set(DOC_INDEX_KEY, index)?;
+    if !failed.is_empty() {
+        return pkv.set("docs", failed);
+    }
+    pkv.set("docs", &HashMap::<ReflectableUuid, Doc>::new())?;
+    pkv.set("names", &HashMap::<ReflectableUuid, String>::new())?;
+    pkv.set("tags", &HashMap::<ReflectableUuid, Vec<String>>::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{doc_from_json, DOC_SCHEMA_VERSION};
+
+    #[test]
+    fn test_migrate_storage() {
+        let mut pkv = PkvStore::new("test", "store");
+        pkv.clear().unwrap();
+        let doc = doc_from_json(include_str!("fixtures/doc_v0.json")).unwrap();
+        let mut other = doc.clone();
+        other.id = ReflectableUuid::generate();
+        other.name = "Other".to_string();
+        let docs = HashMap::from([(doc.id, doc.clone()), (other.id, other.clone())]);
+        let names = HashMap::from([(doc.id, doc.name.clone()), (other.id, other.name.clone())]);
+        let tags = HashMap::from([(doc.id, doc.tags.clone())]);
+        pkv.set("docs", &docs).unwrap();
+        pkv.set("names", &names).unwrap();
+        pkv.set("tags", &tags).unwrap();
+
+        migrate_storage(&mut pkv);
+
+        let index = load_doc_index(&pkv);
+        assert_eq!(index.names, names);
+        assert_eq!(index.tags, tags);
+        assert_eq!(
+            load_stored_doc(&pkv, other.id).unwrap().unwrap().name,
+            "Other"
+        );
+        assert_eq!(
+            load_stored_doc(&pkv, doc.id).unwrap().unwrap().tabs.len(),
+            2
+        );
+        let docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
+        assert!(docs.is_empty());
+
+        // documents moved already aren't overwritten
+        pkv.set("docs", &HashMap::from([(other.id, doc.clone())]))
+            .unwrap();
+        migrate_storage(&mut pkv);
+        assert_eq!(
+            load_stored_doc(&pkv, other.id).unwrap().unwrap().name,
+            "Other"
+        );
+
+        remove_stored_doc(&mut pkv, other.id).unwrap();
+        assert!(load_stored_doc(&pkv, other.id).is_none());
+        assert!(!load_doc_index(&pkv).names.contains_key(&other.id));
+    }
+
+    #[test]
+    fn test_load_stored_doc() {
+        let mut pkv = PkvStore::new("test", "stored_doc");
+        pkv.clear().unwrap();
+        // stored by a release without `version`, `tags` or `z_index`
+        let value: Value = serde_json::from_str(include_str!("fixtures/doc_v0.json")).unwrap();
+        let doc_id: ReflectableUuid = serde_json::from_value(value["id"].clone()).unwrap();
+        pkv.set(doc_key(doc_id), &value).unwrap();
+        let doc = load_stored_doc(&pkv, doc_id).unwrap().unwrap();
+        assert_eq!(doc.version, DOC_SCHEMA_VERSION);
+        assert_eq!(doc.tabs.len(), 2);
+
+        // current documents read back unchanged, uuids included
+        store_doc(&mut pkv, &doc).unwrap();
+        let stored = load_stored_doc(&pkv, doc_id).unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&stored).unwrap(),
+            serde_json::to_value(&doc).unwrap()
+        );
+
+        pkv.set(doc_key(doc_id), &serde_json::json!({"version": 99}))
+            .unwrap();
+        assert!(matches!(
+            load_stored_doc(&pkv, doc_id),
+            Some(Err(DocError::UnsupportedVersion(99)))
+        ));
+        assert!(load_stored_doc(&pkv, ReflectableUuid::generate()).is_none());
+    }
+
+    #[test]
+    fn test_migrate_legacy_storage() {
+        let mut pkv = PkvStore::new("test", "legacy_store");
+        pkv.clear().unwrap();
+        // documents as stored before versioning, keyed by their uuid
+        let legacy: Value = serde_json::from_str(include_str!("fixtures/doc_v0.json")).unwrap();
+        let doc_id: ReflectableUuid = serde_json::from_value(legacy["id"].clone()).unwrap();
+        let broken_id = ReflectableUuid::generate();
+        let broken = serde_json::json!({"id": broken_id, "name": "Broken", "tabs": 3});
+        let docs = HashMap::from([(doc_id, legacy.clone()), (broken_id, broken)]);
+        pkv.set("docs", &docs).unwrap();
+        pkv.set("names", &HashMap::from([(doc_id, "Board")]))
+            .unwrap();
+
+        migrate_storage(&mut pkv);
+
+        let doc = load_stored_doc(&pkv, doc_id).unwrap().unwrap();
+        assert_eq!(doc.version, DOC_SCHEMA_VERSION);
+        assert_eq!(load_doc_index(&pkv).names.len(), 1);
+        assert!(load_stored_doc(&pkv, broken_id).is_none());
+        // the broken document is kept for the next start, next to documents saved meanwhile
+        let mut untitled = doc.clone();
+        untitled.id = ReflectableUuid::generate();
+        store_doc(&mut pkv, &untitled).unwrap();
+        let StoredValue(Value::Object(left)) = pkv.get("docs").unwrap() else {
+            panic!("legacy documents were dropped");
+        };
+        assert_eq!(left.len(), 1);
+        assert!(left.contains_key(&broken_id.0.to_string()));
+
+        // repaired by hand, or readable by a newer release
+        let mut fixed = legacy;
+        fixed["id"] = serde_json::json!(broken_id);
+        pkv.set("docs", &HashMap::from([(broken_id, fixed)]))
+            .unwrap();
+        migrate_storage(&mut pkv);
+        let index = load_doc_index(&pkv);
+        assert_eq!(index.names.len(), 3);
+        assert_eq!(
+            load_stored_doc(&pkv, broken_id).unwrap().unwrap().id,
+            broken_id
+        );
+        let StoredValue(left) = pkv.get("docs").unwrap();
+        assert_eq!(left, serde_json::json!({}));
+    }
+}
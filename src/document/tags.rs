@@ -0,0 +1,40 @@
+/// Trim tags, drop a leading `#` and empty tags, and remove duplicates.
+/// Tags that only differ in case are duplicates; the first spelling is kept.
+pub fn normalize_tags(tags: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut normalized: Vec<String> = vec![];
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').trim();
+        if !tag.is_empty() && !has_tag(&normalized, tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// Tags typed into the tag editor, separated by commas or whitespace.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    normalize_tags(
+        text.split(|c: char| c == ',' || c.is_whitespace())
+            .map(str::to_string),
+    )
+}
+
+pub fn has_tag(tags: &[String], tag: &str) -> bool {
+    tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" #Idea, todo  idea,,#  later\n"),
+            vec!["Idea", "todo", "later"]
+        );
+        assert!(parse_tags(" , # ").is_empty());
+        assert!(has_tag(&parse_tags("Idea"), "idea"));
+        assert!(!has_tag(&parse_tags("ideas"), "idea"));
+    }
+}
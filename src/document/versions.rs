@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::TabSnapshot;
+use crate::canvas::arrow::components::{ArrowMeta, ArrowType};
+use crate::utils::ReflectableUuid;
+use crate::JsonNode;
+
+/// A named state of a tab. Unlike checkpoints, versions are kept until they are deleted
+/// together with their tab.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TabVersion {
+    pub id: ReflectableUuid,
+    pub name: String,
+    /// Milliseconds since the Unix epoch.
+    pub created: u64,
+    pub snapshot: TabSnapshot,
+}
+
+/// How a node or arrow of the current canvas differs from a version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffStatus {
+    /// Added since the version was taken.
+    Added,
+    /// Removed since the version was taken.
+    Removed,
+    /// Moved, resized, recolored, edited or hidden since the version was taken.
+    Changed,
+}
+
+/// Nodes and arrows that differ between a version and the current canvas.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VersionDiff {
+    pub nodes: HashMap<Uuid, DiffStatus>,
+    pub arrows: Vec<(ArrowMeta, DiffStatus)>,
+}
+
+impl VersionDiff {
+    pub fn arrow(&self, arrow: &ArrowMeta) -> Option<DiffStatus> {
+        self.arrows
+            .iter()
+            .find(|(other, _)| arrow_key(other) == arrow_key(arrow))
+            .map(|(_, status)| *status)
+    }
+}
+
+/// What changed on the canvas `current` since `version`. Drawings aren't compared, and
+/// neither is the z order of nodes since clicking a node brings it to the front.
+pub fn diff_version(version: &TabSnapshot, current: &TabSnapshot) -> VersionDiff {
+    let mut diff = VersionDiff::default();
+    for node in version.nodes.iter() {
+        match current.nodes.iter().find(|other| other.id == node.id) {
+            Some(other) if same_node(version, node, current, other) => {}
+            Some(_) => {
+                diff.nodes.insert(node.id, DiffStatus::Changed);
+            }
+            None => {
+                diff.nodes.insert(node.id, DiffStatus::Removed);
+            }
+        }
+    }
+    for node in current.nodes.iter() {
+        if !version.nodes.iter().any(|other| other.id == node.id) {
+            diff.nodes.insert(node.id, DiffStatus::Added);
+        }
+    }
+    for arrow in version.arrows.iter() {
+        match current
+            .arrows
+            .iter()
+            .find(|other| arrow_key(other) == arrow_key(arrow))
+        {
+            Some(other) if other == arrow => {}
+            Some(_) => diff.arrows.push((*arrow, DiffStatus::Changed)),
+            None => diff.arrows.push((*arrow, DiffStatus::Removed)),
+        }
+    }
+    for arrow in current.arrows.iter() {
+        if !version
+            .arrows
+            .iter()
+            .any(|other| arrow_key(other) == arrow_key(arrow))
+        {
+            diff.arrows.push((*arrow, DiffStatus::Added));
+        }
+    }
+    diff
+}
+
+/// The canvas shown while previewing `version`: the version itself, plus the nodes and
+/// arrows added since, so that every difference to `current` can be highlighted on it.
+pub fn preview_version(version: &TabSnapshot, current: &TabSnapshot) -> (TabSnapshot, VersionDiff) {
+    let diff = diff_version(version, current);
+    let mut preview = version.clone();
+    for node in current.nodes.iter() {
+        if diff.nodes.get(&node.id) == Some(&DiffStatus::Added) {
+            preview.nodes.push(node.clone());
+            if let Some(hash) = current.images.get(&node.id.to_string()) {
+                preview.images.insert(node.id.to_string(), hash.clone());
+            }
+        }
+    }
+    for (arrow, status) in diff.arrows.iter() {
+        if *status == DiffStatus::Added {
+            preview.arrows.push(*arrow);
+        }
+    }
+    preview.normalize();
+    (preview, diff)
+}
+
+/// `created` of a version as "YYYY-MM-DD HH:MM", in UTC.
+pub fn format_timestamp(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    let days = (minutes / (24 * 60)) as i64;
+    let (hour, minute) = ((minutes / 60) % 24, minutes % 60);
+    // civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year, month, day, hour, minute
+    )
+}
+
+fn same_node(
+    version: &TabSnapshot,
+    a: &JsonNode<String>,
+    current: &TabSnapshot,
+    b: &JsonNode<String>,
+) -> bool {
+    let id = a.id.to_string();
+    JsonNode {
+        z: b.z,
+        ..a.clone()
+    } == *b
+        && version.images.get(&id) == current.images.get(&id)
+}
+
+fn arrow_key(arrow: &ArrowMeta) -> (Uuid, Uuid, ArrowType) {
+    (arrow.start.id.0, arrow.end.id.0, arrow.arrow_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+
+    #[test]
+    fn test_diff_version() {
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let version = doc.tabs[0].checkpoints.back().unwrap().clone();
+        assert_eq!(diff_version(&version, &version), VersionDiff::default());
+
+        let mut current = version.clone();
+        current.nodes[0].z += 5.;
+        current.nodes[1].text.text = "edited".to_string();
+        let removed = current.nodes.remove(2);
+        current
+            .arrows
+            .retain(|arrow| arrow.start.id.0 != removed.id && arrow.end.id.0 != removed.id);
+        let mut added = current.nodes[0].clone();
+        added.id = Uuid::new_v4();
+        current.nodes.push(added.clone());
+        let mut arrow = current.arrows[0];
+        arrow.end.id = ReflectableUuid(added.id);
+        current.arrows.push(arrow);
+
+        let (preview, diff) = preview_version(&version, &current);
+        assert_eq!(diff.nodes.len(), 3);
+        assert_eq!(diff.nodes[&current.nodes[1].id], DiffStatus::Changed);
+        assert_eq!(diff.nodes[&removed.id], DiffStatus::Removed);
+        assert_eq!(diff.nodes[&added.id], DiffStatus::Added);
+        assert_eq!(diff.arrow(&arrow), Some(DiffStatus::Added));
+        let removed_arrows = version.arrows.len() + 1 - current.arrows.len();
+        assert_eq!(diff.arrows.len(), 1 + removed_arrows);
+        assert_eq!(preview.nodes.len(), version.nodes.len() + 1);
+        assert_eq!(preview.arrows.len(), version.arrows.len() + 1);
+        assert!(preview.nodes.iter().any(|node| node.id == removed.id));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_782_400_000), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(1_700_000_000_000), "2023-11-14 22:13");
+    }
+}
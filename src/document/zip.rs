@@ -0,0 +1,206 @@
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// Entry names are UTF-8.
+const UTF8_NAMES: u16 = 1 << 11;
+const ENCRYPTED: u16 = 1;
+const VERSION: u16 = 20;
+/// 1980-01-01, the earliest date zip can express. Bundles leave timestamps
+/// out, so the same document always gives the same bytes.
+const DOS_DATE: u16 = (1 << 5) | 1;
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// True for data that starts like a zip archive.
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(&LOCAL_HEADER.to_le_bytes())
+}
+
+/// Entries are written in the order they are added. There is no zip64 support, so
+/// archives are limited to 65534 entries and 4 GiB, adding past that is an error.
+/// The maximum values themselves are left to mark zip64 archives.
+#[derive(Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> Result<(), String> {
+        if self.entries == u16::MAX - 1 {
+            return Err("too many files for a zip archive".to_string());
+        }
+        let too_large = || format!("{} is too large for a zip archive", name);
+        let name_length = u16::try_from(name.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(contents.len()).map_err(|_| too_large())?;
+        let deflated = compress_to_vec(contents, COMPRESSION_LEVEL);
+        // images are usually compressed already
+        let (method, stored) = if deflated.len() < contents.len() {
+            (DEFLATED, deflated.as_slice())
+        } else {
+            (STORED, contents)
+        };
+        let crc = crc32fast::hash(contents);
+        let stored_size = u32::try_from(stored.len()).map_err(|_| too_large())?;
+        let offset = archive_offset(self.data.len())?;
+
+        let mut header = vec![];
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, UTF8_NAMES);
+        put_u16(&mut header, method);
+        put_u16(&mut header, 0);
+        put_u16(&mut header, DOS_DATE);
+        put_u32(&mut header, crc);
+        put_u32(&mut header, stored_size);
+        put_u32(&mut header, size);
+        put_u16(&mut header, name_length);
+        put_u16(&mut header, 0);
+
+        put_u32(&mut self.data, LOCAL_HEADER);
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(stored);
+
+        put_u32(&mut self.central_directory, CENTRAL_HEADER);
+        put_u16(&mut self.central_directory, VERSION);
+        self.central_directory.extend_from_slice(&header);
+        // comment length, disk, internal and external attributes
+        put_u16(&mut self.central_directory, 0);
+        put_u16(&mut self.central_directory, 0);
+        put_u16(&mut self.central_directory, 0);
+        put_u32(&mut self.central_directory, 0);
+        put_u32(&mut self.central_directory, offset);
+        self.central_directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        let offset = archive_offset(self.data.len())?;
+        let size = archive_offset(self.central_directory.len())?;
+        archive_offset(self.data.len() + self.central_directory.len())?;
+        self.data.append(&mut self.central_directory);
+        put_u32(&mut self.data, END_OF_CENTRAL_DIRECTORY);
+        put_u16(&mut self.data, 0);
+        put_u16(&mut self.data, 0);
+        put_u16(&mut self.data, self.entries);
+        put_u16(&mut self.data, self.entries);
+        put_u32(&mut self.data, size);
+        put_u32(&mut self.data, offset);
+        put_u16(&mut self.data, 0);
+        Ok(self.data)
+    }
+}
+
+/// Offsets and sizes in the archive, which must fit the 32 bits zip has for them.
+fn archive_offset(len: usize) -> Result<u32, String> {
+    u32::try_from(len)
+        .ok()
+        .filter(|&offset| offset < u32::MAX)
+        .ok_or_else(|| "archive is too large for zip".to_string())
+}
+
+/// Names and contents of the entries of a zip archive, in central directory order.
+/// Only what bundles need is supported: stored or deflated entries, no encryption,
+/// no zip64 and no archives split over several files.
+pub fn read_zip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // the end record is followed by a comment of up to 64k
+    let end = (0..=data.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE))
+        .rev()
+        .find(|&at| u32_at(data, at) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or("not a zip archive")?;
+    let truncated = || "truncated zip archive".to_string();
+    let entries = u16_at(data, end + 10).ok_or_else(truncated)?;
+    let mut at = u32_at(data, end + 16).ok_or_else(truncated)? as usize;
+    // zip64 archives keep the real values elsewhere and these at their maximum
+    if entries == u16::MAX || at == u32::MAX as usize {
+        return Err("zip64 archives are not supported".to_string());
+    }
+    let mut files = vec![];
+    for _ in 0..entries {
+        if u32_at(data, at) != Some(CENTRAL_HEADER) {
+            return Err("broken zip central directory".to_string());
+        }
+        let flags = u16_at(data, at + 8).ok_or_else(truncated)?;
+        let method = u16_at(data, at + 10).ok_or_else(truncated)?;
+        let crc = u32_at(data, at + 16).ok_or_else(truncated)?;
+        let compressed_size = u32_at(data, at + 20).ok_or_else(truncated)? as usize;
+        let size = u32_at(data, at + 24).ok_or_else(truncated)? as usize;
+        let name_length = u16_at(data, at + 28).ok_or_else(truncated)? as usize;
+        let extra_length = u16_at(data, at + 30).ok_or_else(truncated)? as usize;
+        let comment_length = u16_at(data, at + 32).ok_or_else(truncated)? as usize;
+        let offset = u32_at(data, at + 42).ok_or_else(truncated)? as usize;
+        let name = data
+            .get(at + 46..at + 46 + name_length)
+            .ok_or_else(truncated)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_length + extra_length + comment_length;
+
+        if flags & ENCRYPTED != 0 {
+            return Err(format!("{} is encrypted", name));
+        }
+        if u32_at(data, offset) != Some(LOCAL_HEADER) {
+            return Err(format!("broken zip entry {}", name));
+        }
+        let local_name_length = u16_at(data, offset + 26).ok_or_else(truncated)? as usize;
+        let local_extra_length = u16_at(data, offset + 28).ok_or_else(truncated)? as usize;
+        let start = offset + 30 + local_name_length + local_extra_length;
+        let stored = data
+            .get(start..start + compressed_size)
+            .ok_or_else(truncated)?;
+        let contents = match method {
+            STORED => stored.to_vec(),
+            DEFLATED => decompress_to_vec_with_limit(stored, size)
+                .map_err(|e| format!("{}: {}", name, e))?,
+            method => return Err(format!("{} uses unsupported compression {}", name, method)),
+        };
+        if contents.len() != size || crc32fast::hash(&contents) != crc {
+            return Err(format!("{} is corrupted", name));
+        }
+        files.push((name, contents));
+    }
+    Ok(files)
+}
+
+fn put_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip_limits() {
+        let mut zip = ZipWriter::default();
+        for entry in 0..u16::MAX - 1 {
+            zip.add(&entry.to_string(), b"").unwrap();
+        }
+        assert!(zip.add("one more", b"").is_err());
+        let data = zip.finish().unwrap();
+        let files = read_zip(&data).unwrap();
+        assert_eq!(files.len(), u16::MAX as usize - 1);
+        assert_eq!(files.last().unwrap().0, (u16::MAX - 2).to_string());
+
+        let mut zip = ZipWriter::default();
+        assert!(zip.add(&"a".repeat(u16::MAX as usize + 1), b"").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::canvas::arrow::components::ArrowType;
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::{NodeType, TextPos};
+
+use super::Graph;
+
+/// Render a tab as a Graphviz digraph. Node shapes follow the node type, parallel
+/// arrows are drawn bold and backgrounds are kept as fill colors.
+pub fn snapshot_to_dot(snapshot: &TabSnapshot, theme: &Theme) -> String {
+    let graph = Graph::new(snapshot, theme);
+    let mut lines = vec!["digraph velo {".to_string()];
+    for node in graph.nodes.iter() {
+        let mut attributes = vec![format!(
+            "label={}",
+            label(&node.node.text.text, &node.node.text.pos)
+        )];
+        let mut style = vec![];
+        match node.node.node_type {
+            NodeType::Rect => {
+                attributes.push("shape=box".to_string());
+                style.push("rounded");
+            }
+            NodeType::Circle => attributes.push("shape=circle".to_string()),
+            NodeType::Paper => attributes.push("shape=note".to_string()),
+        }
+        match &node.fill {
+            Some(fill) => {
+                style.push("filled");
+                attributes.push(format!("fillcolor=\"{}\"", fill));
+            }
+            None => attributes.push("color=transparent".to_string()),
+        }
+        if !style.is_empty() {
+            attributes.push(format!("style=\"{}\"", style.join(",")));
+        }
+        lines.push(format!("    {} [{}];", node.key, attributes.join(" ")));
+    }
+    for edge in graph.edges.iter() {
+        let attributes = match edge.arrow_type {
+            ArrowType::Line => " [dir=none]",
+            ArrowType::Arrow => "",
+            ArrowType::DoubleArrow => " [dir=both]",
+            ArrowType::ParallelLine => " [dir=none style=bold]",
+            ArrowType::ParallelArrow => " [style=bold]",
+            ArrowType::ParallelDoubleArrow => " [dir=both style=bold]",
+        };
+        lines.push(format!("    {} -> {}{};", edge.start, edge.end, attributes));
+    }
+    lines.push("}".to_string());
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Quoted label, with lines left aligned for top left text like on the canvas.
+fn label(text: &str, pos: &TextPos) -> String {
+    let line_end = match pos {
+        TextPos::Center => "\\n",
+        TextPos::TopLeft => "\\l",
+    };
+    let lines: Vec<String> = text
+        .trim_end()
+        .lines()
+        .map(|line| line.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect();
+    match pos {
+        TextPos::Center => format!("\"{}\"", lines.join(line_end)),
+        TextPos::TopLeft => format!("\"{}{}\"", lines.join(line_end), line_end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::active_snapshot;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_dot_golden() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let snapshot = active_snapshot(&doc).unwrap();
+        let dot = snapshot_to_dot(snapshot, &velo_light());
+        assert_eq!(dot, include_str!("fixtures/board.dot"));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Color;
+
+use crate::canvas::arrow::components::ArrowType;
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+use crate::JsonNode;
+
+use super::ThemeColors;
+
+/// Length of the uuid prefix used as node key.
+const KEY_LENGTH: usize = 8;
+
+/// The visible nodes and arrows of a tab, for text formats that leave the layout to
+/// their renderer. Nodes are ordered by id and edges by their keys, so moving things
+/// around on the canvas doesn't change the output.
+pub struct Graph<'a> {
+    pub nodes: Vec<GraphNode<'a>>,
+    pub edges: Vec<GraphEdge>,
+}
+
+pub struct GraphNode<'a> {
+    /// Short identifier that is stable for the node.
+    pub key: String,
+    pub node: &'a JsonNode<String>,
+    /// Background as `#rrggbb` or `#rrggbbaa`, `None` when the node is transparent.
+    pub fill: Option<String>,
+}
+
+pub struct GraphEdge {
+    pub start: String,
+    pub end: String,
+    pub arrow_type: ArrowType,
+}
+
+impl<'a> Graph<'a> {
+    pub fn new(snapshot: &'a TabSnapshot, theme: &Theme) -> Self {
+        let colors = ThemeColors::new(theme);
+        let mut visible: Vec<&JsonNode<String>> =
+            snapshot.nodes.iter().filter(|node| node.visible).collect();
+        visible.sort_by_key(|node| node.id);
+
+        let mut prefixes: HashMap<String, usize> = HashMap::new();
+        for node in visible.iter() {
+            *prefixes.entry(short_key(node)).or_default() += 1;
+        }
+        let nodes: Vec<GraphNode> = visible
+            .into_iter()
+            .map(|node| {
+                let key = match short_key(node) {
+                    key if prefixes[&key] == 1 => key,
+                    _ => format!("n{}", node.id.simple()),
+                };
+                let fill = hex_color(colors.get(&node.bg_color).unwrap_or(theme.node_bg));
+                GraphNode { key, node, fill }
+            })
+            .collect();
+
+        let keys: HashMap<_, _> = nodes.iter().map(|n| (n.node.id, n.key.clone())).collect();
+        let mut edges: Vec<GraphEdge> = vec![];
+        for arrow in snapshot.arrows.iter().filter(|arrow| arrow.visible) {
+            let (Some(start), Some(end)) = (keys.get(&arrow.start.id.0), keys.get(&arrow.end.id.0))
+            else {
+                continue;
+            };
+            let is_duplicate = edges.iter().any(|edge| {
+                edge.start == *start && edge.end == *end && edge.arrow_type == arrow.arrow_type
+            });
+            if !is_duplicate {
+                edges.push(GraphEdge {
+                    start: start.clone(),
+                    end: end.clone(),
+                    arrow_type: arrow.arrow_type,
+                });
+            }
+        }
+        edges.sort_by(|a, b| (&a.start, &a.end).cmp(&(&b.start, &b.end)));
+        Graph { nodes, edges }
+    }
+}
+
+fn short_key(node: &JsonNode<String>) -> String {
+    format!("n{}", &node.id.simple().to_string()[..KEY_LENGTH])
+}
+
+/// `#rrggbb`, or `#rrggbbaa` for translucent colors, `None` when fully transparent.
+pub fn hex_color(color: Color) -> Option<String> {
+    let [r, g, b, a] = color.as_rgba_u8();
+    match a {
+        0 => None,
+        255 => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        _ => Some(format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)),
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::canvas::arrow::components::{ArrowConnectPos, ArrowType};
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+
+use super::{hex_color, ThemeColors};
+
+/// JSON Canvas color presets and the theme colors they stand for.
+pub const COLOR_PRESETS: [(&str, &str); 4] = [
+    ("1", "color_change_4"),
+    ("3", "paper_node_bg"),
+    ("4", "color_change_5"),
+    ("5", "color_change_3"),
+];
+
+/// A board in the open JSON Canvas format, which Obsidian uses for `.canvas` files.
+/// Coordinates are the top left corner of a node, with y growing downwards.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct JsonCanvas {
+    #[serde(default)]
+    pub nodes: Vec<CanvasNode>,
+    #[serde(default)]
+    pub edges: Vec<CanvasEdge>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct CanvasNode {
+    pub id: String,
+    /// `text`, `file`, `link` or `group`.
+    #[serde(rename = "type")]
+    pub node_type: String,
+    #[serde(serialize_with = "coordinate")]
+    pub x: f32,
+    #[serde(serialize_with = "coordinate")]
+    pub y: f32,
+    #[serde(serialize_with = "coordinate")]
+    pub width: f32,
+    #[serde(serialize_with = "coordinate")]
+    pub height: f32,
+    /// Preset number or `#rrggbb`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasEdge {
+    pub id: String,
+    pub from_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_side: Option<String>,
+    /// `none` (the default) or `arrow`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_end: Option<String>,
+    pub to_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_side: Option<String>,
+    /// `arrow` (the default) or `none`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// The format asks for integer coordinates, fractions are only kept when there are any.
+fn coordinate<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.fract() == 0. && value.abs() < i32::MAX as f32 {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f32(*value)
+    }
+}
+
+pub fn side_name(pos: ArrowConnectPos) -> &'static str {
+    match pos {
+        ArrowConnectPos::Top => "top",
+        ArrowConnectPos::Right => "right",
+        ArrowConnectPos::Bottom => "bottom",
+        ArrowConnectPos::Left => "left",
+    }
+}
+
+/// Render a tab as JSON Canvas. Text nodes keep their ids, so boards can go back and
+/// forth between the tools. Node shapes, hidden nodes, drawings and images have no
+/// JSON Canvas counterpart and are left out, and parallel arrows become straight ones.
+pub fn snapshot_to_json_canvas(snapshot: &TabSnapshot, theme: &Theme) -> String {
+    let colors = ThemeColors::new(theme);
+    let mut visible: Vec<_> = snapshot.nodes.iter().filter(|node| node.visible).collect();
+    // later nodes are drawn on top
+    visible.sort_by(|a, b| a.z.total_cmp(&b.z));
+    let nodes = visible
+        .iter()
+        .map(|node| {
+            let color = match COLOR_PRESETS
+                .iter()
+                .find(|(_, name)| *name == node.bg_color)
+            {
+                Some((preset, _)) => Some(preset.to_string()),
+                None if node.bg_color == "node_bg" => None,
+                None => colors.get(&node.bg_color).and_then(hex_color),
+            };
+            CanvasNode {
+                id: node.id.to_string(),
+                node_type: "text".to_string(),
+                x: node.x - node.width / 2.,
+                y: -(node.y + node.height / 2.),
+                width: node.width,
+                height: node.height,
+                color,
+                text: Some(node.text.text.clone()),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let is_visible = |id: uuid::Uuid| visible.iter().any(|node| node.id == id);
+    let edges = snapshot
+        .arrows
+        .iter()
+        .filter(|arrow| arrow.visible && is_visible(arrow.start.id.0) && is_visible(arrow.end.id.0))
+        .map(|arrow| {
+            let (from_end, to_end) = match arrow.arrow_type {
+                ArrowType::Line | ArrowType::ParallelLine => (None, Some("none".to_string())),
+                ArrowType::Arrow | ArrowType::ParallelArrow => (None, None),
+                ArrowType::DoubleArrow | ArrowType::ParallelDoubleArrow => {
+                    (Some("arrow".to_string()), None)
+                }
+            };
+            CanvasEdge {
+                id: format!(
+                    "{}-{}-{}-{}",
+                    arrow.start.id.0,
+                    side_name(arrow.start.pos),
+                    arrow.end.id.0,
+                    side_name(arrow.end.pos)
+                ),
+                from_node: arrow.start.id.0.to_string(),
+                from_side: Some(side_name(arrow.start.pos).to_string()),
+                from_end,
+                to_node: arrow.end.id.0.to_string(),
+                to_side: Some(side_name(arrow.end.pos).to_string()),
+                to_end,
+                ..Default::default()
+            }
+        })
+        .collect();
+    let canvas = JsonCanvas { nodes, edges };
+    // tab indented like the files Obsidian writes
+    let mut output = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"\t");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    canvas
+        .serialize(&mut serializer)
+        .expect("canvas serializes to json");
+    String::from_utf8(output).expect("json is utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::active_snapshot;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_json_canvas_golden() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let snapshot = active_snapshot(&doc).unwrap();
+        let canvas = snapshot_to_json_canvas(snapshot, &velo_light());
+        assert_eq!(canvas, include_str!("fixtures/board.canvas"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use crate::canvas::arrow::components::ArrowType;
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::NodeType;
+
+use super::Graph;
+
+/// Render a tab as a Mermaid flowchart. Node shapes follow the node type, with the
+/// flag shape for paper so plain boxes stay regular nodes on import. Parallel arrows
+/// are drawn as thick links and every background color becomes a class named after
+/// the theme color.
+pub fn snapshot_to_mermaid(snapshot: &TabSnapshot, theme: &Theme) -> String {
+    let graph = Graph::new(snapshot, theme);
+    let mut lines = vec!["flowchart TD".to_string()];
+    // class name -> (style, node keys)
+    let mut classes: BTreeMap<&str, (String, Vec<&str>)> = BTreeMap::new();
+    for node in graph.nodes.iter() {
+        let label = escape(node.node.text.text.trim_end());
+        let shape = match node.node.node_type {
+            NodeType::Rect => format!("(\"{}\")", label),
+            NodeType::Circle => format!("((\"{}\"))", label),
+            NodeType::Paper => format!(">\"{}\"]", label),
+        };
+        lines.push(format!("    {}{}", node.key, shape));
+        let style = match &node.fill {
+            Some(fill) => format!("fill:{}", fill),
+            None => "fill:none,stroke:none".to_string(),
+        };
+        classes
+            .entry(node.node.bg_color.as_str())
+            .or_insert_with(|| (style, vec![]))
+            .1
+            .push(&node.key);
+    }
+    for edge in graph.edges.iter() {
+        let link = match edge.arrow_type {
+            ArrowType::Line => "---",
+            ArrowType::Arrow => "-->",
+            ArrowType::DoubleArrow => "<-->",
+            ArrowType::ParallelLine => "===",
+            ArrowType::ParallelArrow => "==>",
+            ArrowType::ParallelDoubleArrow => "<==>",
+        };
+        lines.push(format!("    {} {} {}", edge.start, link, edge.end));
+    }
+    for (name, (style, keys)) in classes.iter() {
+        lines.push(format!("    classDef {} {}", name, style));
+        lines.push(format!("    class {} {}", keys.join(","), name));
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Labels are quoted, so only quotes, entity starts, html and line breaks need care.
+fn escape(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+        .replace('\n', "<br/>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::active_snapshot;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_mermaid_golden() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let snapshot = active_snapshot(&doc).unwrap();
+        let mermaid = snapshot_to_mermaid(snapshot, &velo_light());
+        assert_eq!(mermaid, include_str!("fixtures/board.mmd"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::io;
+use std::path::Path;
+
+use crate::components::Doc;
+use crate::document::{doc_to_bundle, doc_to_canonical_json, TabSnapshot};
+use crate::themes::Theme;
+use crate::utils::ReflectableUuid;
+
+pub mod dot;
+pub mod graph;
+pub mod json_canvas;
+pub mod mermaid;
+pub mod opml;
+pub mod outline;
+pub mod png;
+pub mod scene;
+pub mod svg;
+
+pub use dot::*;
+pub use graph::*;
+pub use json_canvas::*;
+pub use mermaid::*;
+pub use opml::*;
+pub use outline::*;
+pub use png::*;
+pub use scene::*;
+pub use svg::*;
+
+/// Latest checkpoint of the active tab, which is what export formats render.
+pub fn active_snapshot(doc: &Doc) -> Option<&TabSnapshot> {
+    doc.tabs
+        .iter()
+        .find(|tab| tab.is_active)
+        .and_then(|tab| tab.checkpoints.back())
+}
+
+/// Part of a snapshot with only the nodes and drawings in `ids`,
+/// and the arrows between those nodes.
+pub fn select(snapshot: &TabSnapshot, ids: &[ReflectableUuid]) -> TabSnapshot {
+    let is_selected = |id: &ReflectableUuid| ids.contains(id);
+    TabSnapshot {
+        nodes: snapshot
+            .nodes
+            .iter()
+            .filter(|node| is_selected(&ReflectableUuid(node.id)))
+            .cloned()
+            .collect(),
+        arrows: snapshot
+            .arrows
+            .iter()
+            .filter(|arrow| is_selected(&arrow.start.id) && is_selected(&arrow.end.id))
+            .copied()
+            .collect(),
+        drawings: snapshot
+            .drawings
+            .iter()
+            .filter(|drawing| is_selected(&drawing.id))
+            .cloned()
+            .collect(),
+        images: snapshot
+            .images
+            .iter()
+            .filter(|(node_id, _)| ids.iter().any(|id| id.0.to_string() == **node_id))
+            .map(|(node_id, hash)| (node_id.clone(), hash.clone()))
+            .collect(),
+    }
+}
+
+/// Pixels per canvas unit asked for with a `@2x` style suffix on the file name.
+fn scale_from_path(path: &Path) -> f32 {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit_once('@'))
+        .and_then(|(_, scale)| scale.strip_suffix('x'))
+        .and_then(|scale| scale.parse::<f32>().ok())
+        .filter(|scale| *scale > 0.)
+        .unwrap_or(1.)
+}
+
+/// Write `doc` to `path` in the format picked by the file extension.
+/// Anything that isn't an export format is written as a velo JSON document, in the
+/// canonical form that keeps diffs between saves small.
+pub fn write_doc(path: &Path, doc: &Doc, theme: &Theme, fonts: &mut RasterFonts) -> io::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    let empty = TabSnapshot::default();
+    let snapshot = active_snapshot(doc).unwrap_or(&empty);
+    let contents = match extension.as_deref() {
+        Some("svg") => snapshot_to_svg(snapshot, &doc.images, theme).into_bytes(),
+        Some("md") | Some("markdown") => snapshot_to_outline(snapshot).into_bytes(),
+        Some("mmd") | Some("mermaid") => snapshot_to_mermaid(snapshot, theme).into_bytes(),
+        Some("dot") | Some("gv") => snapshot_to_dot(snapshot, theme).into_bytes(),
+        Some("canvas") => snapshot_to_json_canvas(snapshot, theme).into_bytes(),
+        Some("opml") => {
+            let title = doc
+                .tabs
+                .iter()
+                .find(|tab| tab.is_active)
+                .map_or("velo", |tab| tab.name.as_str());
+            snapshot_to_opml(snapshot, title).into_bytes()
+        }
+        Some("velo") => doc_to_bundle(doc).map_err(io::Error::other)?,
+        Some("png") => {
+            let image =
+                snapshot_to_image(snapshot, &doc.images, theme, fonts, scale_from_path(path));
+            encode_png(&image).map_err(io::Error::other)?
+        }
+        _ => doc_to_canonical_json(doc)
+            .map_err(io::Error::other)?
+            .into_bytes(),
+    };
+    std::fs::write(path, contents)
+}
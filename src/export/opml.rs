@@ -0,0 +1,79 @@
+use crate::document::TabSnapshot;
+
+use super::{outline_tree, OutlineItem, SEE_ABOVE};
+
+/// Render the arrow graph of a tab as an OPML 2.0 outline, see [`outline_tree`].
+/// The first line of a node is the outline text and the rest goes into the `_note`
+/// attribute most outliners show below it. `_velo_id` holds the node id, so references
+/// to a node read back as that node.
+pub fn snapshot_to_opml(snapshot: &TabSnapshot, title: &str) -> String {
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        r#"<opml version="2.0">"#.to_string(),
+        "  <head>".to_string(),
+        format!("    <title>{}</title>", escape(title)),
+        "  </head>".to_string(),
+        "  <body>".to_string(),
+    ];
+    for item in outline_tree(snapshot).iter() {
+        write_outline(item, 2, &mut lines);
+    }
+    lines.push("  </body>".to_string());
+    lines.push("</opml>".to_string());
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+fn write_outline(item: &OutlineItem, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    if item.is_reference {
+        let reference = format!("{} {}", item.first_line(), SEE_ABOVE);
+        lines.push(format!(
+            r#"{}<outline text="{}" _velo_id="{}"/>"#,
+            indent,
+            escape(reference.trim_start()),
+            item.node.id
+        ));
+        return;
+    }
+    let text = item.node.text.text.trim();
+    let (text, note) = text.split_once('\n').unwrap_or((text, ""));
+    let mut attributes = format!(r#"text="{}""#, escape(text.trim_end()));
+    if !note.trim().is_empty() {
+        attributes.push_str(&format!(r#" _note="{}""#, escape(note.trim_end())));
+    }
+    attributes.push_str(&format!(r#" _velo_id="{}""#, item.node.id));
+    if item.children.is_empty() {
+        lines.push(format!("{}<outline {}/>", indent, attributes));
+        return;
+    }
+    lines.push(format!("{}<outline {}>", indent, attributes));
+    for child in item.children.iter() {
+        write_outline(child, depth + 1, lines);
+    }
+    lines.push(format!("{}</outline>", indent));
+}
+
+/// Escape for text and attribute values. Line breaks are kept as character
+/// references, attribute values would turn them into spaces otherwise.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::active_snapshot;
+
+    #[test]
+    fn test_opml_golden() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let snapshot = active_snapshot(&doc).unwrap();
+        let opml = snapshot_to_opml(snapshot, "Board");
+        assert_eq!(opml, include_str!("fixtures/board.opml"));
+    }
+}
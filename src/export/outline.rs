@@ -0,0 +1,225 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use crate::document::TabSnapshot;
+use crate::JsonNode;
+
+/// Marks a node that is already in the outline under another parent, or that closes a cycle.
+pub const SEE_ABOVE: &str = "(see above)";
+
+/// A node in the outline of a tab, with the nodes its arrows point to.
+pub struct OutlineItem<'a> {
+    pub node: &'a JsonNode<String>,
+    /// The node is written out elsewhere in the outline, this item only refers to it.
+    pub is_reference: bool,
+    pub children: Vec<OutlineItem<'a>>,
+}
+
+impl<'a> OutlineItem<'a> {
+    /// First line of the node text, which is what references show.
+    pub fn first_line(&self) -> &'a str {
+        self.node
+            .text
+            .text
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+}
+
+/// Follow the arrows of a tab from the nodes without incoming arrows, arrows point from
+/// parent to child. A node with several parents is written out under the first one and
+/// referred to under the others; the same goes for arrows that lead back into a cycle.
+pub fn outline_tree(snapshot: &TabSnapshot) -> Vec<OutlineItem<'_>> {
+    let nodes: HashMap<Uuid, &JsonNode<String>> = snapshot
+        .nodes
+        .iter()
+        .filter(|node| node.visible)
+        .map(|node| (node.id, node))
+        .collect();
+    let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    let mut has_parent: HashSet<Uuid> = HashSet::new();
+    for arrow in snapshot.arrows.iter().filter(|arrow| arrow.visible) {
+        let (start, end) = (arrow.start.id.0, arrow.end.id.0);
+        if start == end || !nodes.contains_key(&start) || !nodes.contains_key(&end) {
+            continue;
+        }
+        let siblings = children.entry(start).or_default();
+        if !siblings.contains(&end) {
+            siblings.push(end);
+            has_parent.insert(end);
+        }
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| reading_order(nodes[a], nodes[b]));
+    }
+    let mut by_position: Vec<&JsonNode<String>> = nodes.values().copied().collect();
+    by_position.sort_by(|a, b| reading_order(a, b));
+
+    let mut outline = Outline {
+        nodes: &nodes,
+        children: &children,
+        visited: HashSet::new(),
+    };
+    let mut items = vec![];
+    for node in by_position
+        .iter()
+        .filter(|node| !has_parent.contains(&node.id))
+    {
+        items.push(outline.item(node.id));
+    }
+    // cycles that no root leads into start at their first node in reading order
+    for node in by_position.iter() {
+        if !outline.visited.contains(&node.id) {
+            items.push(outline.item(node.id));
+        }
+    }
+    items
+}
+
+struct Outline<'m, 'a> {
+    nodes: &'m HashMap<Uuid, &'a JsonNode<String>>,
+    children: &'m HashMap<Uuid, Vec<Uuid>>,
+    visited: HashSet<Uuid>,
+}
+
+impl<'m, 'a> Outline<'m, 'a> {
+    fn item(&mut self, id: Uuid) -> OutlineItem<'a> {
+        let node = self.nodes[&id];
+        if !self.visited.insert(id) {
+            return OutlineItem {
+                node,
+                is_reference: true,
+                children: vec![],
+            };
+        }
+        let children = self
+            .children
+            .get(&id)
+            .map(|children| children.iter().map(|child| self.item(*child)).collect())
+            .unwrap_or_default();
+        OutlineItem {
+            node,
+            is_reference: false,
+            children,
+        }
+    }
+}
+
+/// Render the arrow graph of a tab as a nested Markdown list, see [`outline_tree`].
+/// References to nodes that are already in the list show their first line.
+pub fn snapshot_to_outline(snapshot: &TabSnapshot) -> String {
+    let mut lines = vec![];
+    for item in outline_tree(snapshot).iter() {
+        write_markdown(item, 0, &mut lines);
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+fn write_markdown(item: &OutlineItem, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    if item.is_reference {
+        let reference = format!("{} {}", item.first_line(), SEE_ABOVE);
+        lines.push(format!("{}- {}", indent, reference.trim_start()));
+        return;
+    }
+    let mut text = item.node.text.text.trim_end().lines();
+    let first_line = text.next().unwrap_or_default();
+    lines.push(format!("{}- {}", indent, first_line).trim_end().to_string());
+    // continuation lines stay inside the list item
+    for line in text {
+        lines.push(format!("{}  {}", indent, line).trim_end().to_string());
+    }
+    for child in item.children.iter() {
+        write_markdown(child, depth + 1, lines);
+    }
+}
+
+/// Top to bottom, then left to right, as the board is read.
+fn reading_order(a: &JsonNode<String>, b: &JsonNode<String>) -> Ordering {
+    b.y.total_cmp(&a.y)
+        .then(a.x.total_cmp(&b.x))
+        .then(a.id.cmp(&b.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+    use crate::utils::ReflectableUuid;
+
+    fn node(text: &str, x: f32, y: f32) -> JsonNode<String> {
+        JsonNode {
+            id: Uuid::new_v4(),
+            x,
+            y,
+            text: crate::JsonNodeText {
+                text: text.to_string(),
+                ..Default::default()
+            },
+            visible: true,
+            ..Default::default()
+        }
+    }
+
+    fn arrow(start: &JsonNode<String>, end: &JsonNode<String>) -> ArrowMeta {
+        ArrowMeta {
+            visible: true,
+            arrow_type: ArrowType::Arrow,
+            start: ArrowConnect {
+                id: ReflectableUuid(start.id),
+                pos: ArrowConnectPos::Bottom,
+            },
+            end: ArrowConnect {
+                id: ReflectableUuid(end.id),
+                pos: ArrowConnectPos::Top,
+            },
+        }
+    }
+
+    #[test]
+    fn test_outline() {
+        let idea = node("# Idea\nwith **details**", 0., 100.);
+        let left = node("left", -100., 0.);
+        let right = node("right", 100., 0.);
+        let shared = node("shared\nsecond line", 0., -100.);
+        let other = node("other root", 300., 100.);
+        let cycle_a = node("cycle a", 0., -300.);
+        let cycle_b = node("cycle b", 0., -400.);
+        let snapshot = TabSnapshot {
+            arrows: vec![
+                arrow(&idea, &right),
+                arrow(&idea, &left),
+                arrow(&left, &shared),
+                arrow(&right, &shared),
+                arrow(&shared, &idea),
+                arrow(&cycle_a, &cycle_b),
+                arrow(&cycle_b, &cycle_a),
+            ],
+            nodes: vec![shared, cycle_b, right, other, left, cycle_a, idea],
+            ..Default::default()
+        };
+        // `shared` points back to `idea`, so `idea` has a parent too and the whole
+        // group only shows up through the cycle fallback, starting from the topmost node
+        assert_eq!(
+            snapshot_to_outline(&snapshot),
+            "\
+- other root
+- # Idea
+  with **details**
+  - left
+    - shared
+      second line
+      - # Idea (see above)
+  - right
+    - shared (see above)
+- cycle a
+  - cycle b
+    - cycle a (see above)
+"
+        );
+    }
+}
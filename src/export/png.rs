@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use base64::{engine::general_purpose, Engine};
+use bevy::prelude::*;
+use bevy_cosmic_edit::{create_cosmic_font_system, get_x_offset, get_y_offset, CosmicFontConfig};
+use bevy_prototype_lyon::prelude::tess::{
+    self, BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions,
+    StrokeTessellator, StrokeVertex, VertexBuffers,
+};
+use cosmic_text::{AttrsList, Buffer, BufferLine, FontSystem, Metrics, Shaping, SwashCache};
+use image::{imageops::FilterType, ImageFormat, ImageOutputFormat, ImageResult, Rgba, RgbaImage};
+
+use super::{Scene, SceneItem, SceneNode};
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::{markdown_lines, node_text_attrs};
+use crate::utils::{bevy_color_to_cosmic, VELO_FONTS};
+use crate::TextPos;
+
+/// Samples per pixel along each axis, for anti-aliased shape edges.
+const SUPERSAMPLING: usize = 4;
+/// Maximum distance in pixels between curves and the polygons drawn for them.
+const TOLERANCE: f32 = 0.05;
+/// Largest width or height of a rasterized tab, larger tabs are scaled down to fit.
+const MAX_IMAGE_SIDE: f32 = 8192.;
+/// Most pixels of a rasterized tab, about 128 MB of RGBA.
+const MAX_IMAGE_PIXELS: f32 = 8192. * 4096.;
+
+/// Fonts and glyph cache for laying out node text without a window.
+/// Fonts are loaded on first use, since that takes a moment.
+#[derive(Default)]
+pub struct RasterFonts(Option<(FontSystem, SwashCache)>);
+
+impl RasterFonts {
+    fn get(&mut self) -> &mut (FontSystem, SwashCache) {
+        self.0.get_or_insert_with(|| {
+            let font_system = create_cosmic_font_system(CosmicFontConfig {
+                fonts_dir_path: None,
+                font_bytes: Some(VELO_FONTS.to_vec()),
+                load_system_fonts: false,
+            });
+            (font_system, SwashCache::new())
+        })
+    }
+}
+
+/// Rasterize a tab on the CPU, in the colors of `theme`.
+/// `scale` is the number of pixels per canvas unit, lowered for tabs that would exceed
+/// `MAX_IMAGE_SIDE` or `MAX_IMAGE_PIXELS`.
+pub fn snapshot_to_image(
+    snapshot: &TabSnapshot,
+    images: &HashMap<String, String>,
+    theme: &Theme,
+    fonts: &mut RasterFonts,
+    scale: f32,
+) -> RgbaImage {
+    let scene = Scene::new(snapshot, images, theme);
+    let scale = fit_scale(scene.size(), scale);
+    let size = (scene.size() * scale).ceil();
+    let mut canvas = RgbaImage::new(size.x as u32, size.y as u32);
+    if let Some(color) = theme.canvas_bg_color {
+        for pixel in canvas.pixels_mut() {
+            *pixel = Rgba(color.as_rgba_u8());
+        }
+    }
+    // canvas coordinates to pixels, y grows downwards in images
+    let to_pixels = tess::math::Transform::new(
+        scale,
+        0.,
+        0.,
+        -scale,
+        -scene.min.x * scale,
+        scene.max.y * scale,
+    );
+    for item in scene.items.iter() {
+        match item {
+            SceneItem::Node(node) => {
+                let path = node.path.clone().transformed(&to_pixels);
+                fill_path(&mut canvas, &path, node.fill);
+                stroke_path(&mut canvas, &path, scale, node.border);
+                let top_left = Vec2::new(
+                    node.node.x - node.node.width / 2. - scene.min.x,
+                    scene.max.y - node.node.y - node.node.height / 2.,
+                ) * scale;
+                let size = Vec2::new(node.node.width, node.node.height) * scale;
+                if let Some(image) = node.image {
+                    draw_image(&mut canvas, image, top_left, size);
+                }
+                draw_text(&mut canvas, fonts, theme, node, top_left, size, scale);
+            }
+            SceneItem::Arrow(path) => {
+                let path = path.clone().transformed(&to_pixels);
+                stroke_path(&mut canvas, &path, 1.5 * scale, theme.arrow);
+            }
+            SceneItem::Drawing { path, color, .. } => {
+                let path = path.clone().transformed(&to_pixels);
+                stroke_path(&mut canvas, &path, 2. * scale, *color);
+            }
+        }
+    }
+    canvas
+}
+
+/// Highest scale up to `scale` at which an image of `size` canvas units stays in bounds.
+fn fit_scale(size: Vec2, scale: f32) -> f32 {
+    let pixels = size * scale;
+    let fit = (MAX_IMAGE_SIDE / pixels.max_element())
+        .min((MAX_IMAGE_PIXELS / (pixels.x * pixels.y)).sqrt());
+    if fit >= 1. {
+        return scale;
+    }
+    warn!(
+        "Scaling the image down to fit {} pixels per side and {} pixels",
+        MAX_IMAGE_SIDE, MAX_IMAGE_PIXELS
+    );
+    // leaves room for rounding the size up to whole pixels
+    scale * fit * 0.999
+}
+
+pub fn encode_png(image: &RgbaImage) -> ImageResult<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+fn fill_path(canvas: &mut RgbaImage, path: &tess::path::Path, color: Color) {
+    let mut geometry: VertexBuffers<Vec2, u32> = VertexBuffers::new();
+    let result = FillTessellator::new().tessellate_path(
+        path,
+        &FillOptions::tolerance(TOLERANCE),
+        &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
+            Vec2::new(vertex.position().x, vertex.position().y)
+        }),
+    );
+    match result {
+        Ok(()) => paint_triangles(canvas, &geometry, color),
+        Err(e) => warn!("Skipping shape that can't be filled: {:?}", e),
+    }
+}
+
+fn stroke_path(canvas: &mut RgbaImage, path: &tess::path::Path, width: f32, color: Color) {
+    let mut geometry: VertexBuffers<Vec2, u32> = VertexBuffers::new();
+    let result = StrokeTessellator::new().tessellate_path(
+        path,
+        &StrokeOptions::tolerance(TOLERANCE).with_line_width(width),
+        &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
+            Vec2::new(vertex.position().x, vertex.position().y)
+        }),
+    );
+    match result {
+        Ok(()) => paint_triangles(canvas, &geometry, color),
+        Err(e) => warn!("Skipping shape that can't be stroked: {:?}", e),
+    }
+}
+
+/// Paint the triangles of one shape. Coverage is summed over the whole shape before
+/// blending, so edges shared by triangles don't show up as seams.
+fn paint_triangles(canvas: &mut RgbaImage, geometry: &VertexBuffers<Vec2, u32>, color: Color) {
+    if color.a() == 0. || geometry.vertices.is_empty() {
+        return;
+    }
+    let (width, height) = canvas.dimensions();
+    let (min, max) = geometry.vertices.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), vertex| (min.min(*vertex), max.max(*vertex)),
+    );
+    let x0 = min.x.floor().max(0.) as u32;
+    let y0 = min.y.floor().max(0.) as u32;
+    let x1 = (max.x.ceil().max(0.) as u32).min(width);
+    let y1 = (max.y.ceil().max(0.) as u32).min(height);
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+    let row = (x1 - x0) as usize;
+    let mut coverage = vec![0f32; row * (y1 - y0) as usize];
+    let step = 1. / SUPERSAMPLING as f32;
+    let sample_weight = step * step;
+    for triangle in geometry.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| geometry.vertices[triangle[i] as usize]);
+        let (tri_min, tri_max) = (a.min(b).min(c), a.max(b).max(c));
+        let tx0 = (tri_min.x.floor().max(x0 as f32)) as u32;
+        let ty0 = (tri_min.y.floor().max(y0 as f32)) as u32;
+        let tx1 = (tri_max.x.ceil().max(0.) as u32).min(x1);
+        let ty1 = (tri_max.y.ceil().max(0.) as u32).min(y1);
+        for y in ty0..ty1 {
+            for x in tx0..tx1 {
+                let mut covered = 0.;
+                for sy in 0..SUPERSAMPLING {
+                    for sx in 0..SUPERSAMPLING {
+                        let point = Vec2::new(
+                            x as f32 + (sx as f32 + 0.5) * step,
+                            y as f32 + (sy as f32 + 0.5) * step,
+                        );
+                        if in_triangle(point, a, b, c) {
+                            covered += sample_weight;
+                        }
+                    }
+                }
+                coverage[(y - y0) as usize * row + (x - x0) as usize] += covered;
+            }
+        }
+    }
+    let color = color.as_rgba_u8();
+    for (i, covered) in coverage.into_iter().enumerate() {
+        if covered > 0. {
+            let (x, y) = (x0 + (i % row) as u32, y0 + (i / row) as u32);
+            blend(canvas.get_pixel_mut(x, y), color, covered.min(1.));
+        }
+    }
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let edge = |from: Vec2, to: Vec2| (to - from).perp_dot(p - from);
+    let (ab, bc, ca) = (edge(a, b), edge(b, c), edge(c, a));
+    (ab >= 0. && bc >= 0. && ca >= 0.) || (ab <= 0. && bc <= 0. && ca <= 0.)
+}
+
+/// Background images cover the node and are cropped to fit, same as on the canvas.
+fn draw_image(canvas: &mut RgbaImage, image: &str, top_left: Vec2, size: Vec2) {
+    let decoded = general_purpose::STANDARD
+        .decode(image.as_bytes())
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| {
+            image::load_from_memory_with_format(&bytes, ImageFormat::Png).map_err(|e| e.to_string())
+        });
+    let decoded = match decoded {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            warn!("Skipping node image that can't be decoded: {}", e);
+            return;
+        }
+    };
+    let image = decoded
+        .resize_to_fill(size.x as u32, size.y as u32, FilterType::Triangle)
+        .to_rgba8();
+    let (left, top) = (top_left.x.round() as i64, top_left.y.round() as i64);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if let Some(target) = pixel_at(canvas, left + x as i64, top + y as i64) {
+            blend(target, pixel.0, 1.);
+        }
+    }
+}
+
+/// Lay out node text from its markdown, the way the canvas shows nodes that aren't edited.
+fn draw_text(
+    canvas: &mut RgbaImage,
+    fonts: &mut RasterFonts,
+    theme: &Theme,
+    node: &SceneNode,
+    top_left: Vec2,
+    size: Vec2,
+    scale: f32,
+) {
+    if node.node.text.text.is_empty() {
+        return;
+    }
+    let metrics = Metrics::new(node.font_size, node.line_height).scale(scale);
+    // text on images scaled down this far would be less than a pixel high
+    if metrics.line_height < 1. {
+        return;
+    }
+    let (font_system, swash_cache) = fonts.get();
+    let mut buffer = Buffer::new(font_system, metrics);
+    buffer.set_size(font_system, size.x, size.y);
+    let attrs = node_text_attrs(theme);
+    match markdown_lines(theme, &node.node.text.text) {
+        Ok(markdown_lines) => {
+            buffer.lines.clear();
+            for line in markdown_lines.lines {
+                let mut line_text = String::new();
+                let mut attrs_list = AttrsList::new(attrs.as_attrs());
+                for (text, attrs) in line.iter() {
+                    let start = line_text.len();
+                    line_text.push_str(text);
+                    attrs_list.add_span(start..line_text.len(), attrs.as_attrs());
+                }
+                buffer
+                    .lines
+                    .push(BufferLine::new(line_text, attrs_list, Shaping::Advanced));
+            }
+        }
+        // markdown that can't be styled is shown as it was typed
+        Err(_) => buffer.set_text(
+            font_system,
+            &node.node.text.text,
+            attrs.as_attrs(),
+            Shaping::Advanced,
+        ),
+    }
+    buffer.shape_until_scroll(font_system);
+    let (offset_x, offset_y) = match node.node.text.pos {
+        TextPos::Center => (get_x_offset(&buffer), get_y_offset(&buffer)),
+        TextPos::TopLeft => (0, 0),
+    };
+    let (left, top) = (top_left.x.round() as i64, top_left.y.round() as i64);
+    buffer.draw(
+        font_system,
+        swash_cache,
+        bevy_color_to_cosmic(theme.font),
+        |x, y, w, h, color| {
+            for row in 0..h as i32 {
+                for col in 0..w as i32 {
+                    // text outside of the node is cut off
+                    let (x, y) = (x + col + offset_x, y + row + offset_y);
+                    if x < 0 || y < 0 || x as f32 >= size.x || y as f32 >= size.y {
+                        continue;
+                    }
+                    if let Some(target) = pixel_at(canvas, left + x as i64, top + y as i64) {
+                        blend(target, [color.r(), color.g(), color.b(), color.a()], 1.);
+                    }
+                }
+            }
+        },
+    );
+}
+
+fn pixel_at(canvas: &mut RgbaImage, x: i64, y: i64) -> Option<&mut Rgba<u8>> {
+    if x < 0 || y < 0 || x >= canvas.width() as i64 || y >= canvas.height() as i64 {
+        return None;
+    }
+    Some(canvas.get_pixel_mut(x as u32, y as u32))
+}
+
+/// Paint `color` over `pixel`, with `coverage` being how much of the pixel it covers.
+fn blend(pixel: &mut Rgba<u8>, color: [u8; 4], coverage: f32) {
+    let src_alpha = color[3] as f32 / 255. * coverage;
+    if src_alpha <= 0. {
+        return;
+    }
+    let dst_alpha = pixel[3] as f32 / 255.;
+    let alpha = src_alpha + dst_alpha * (1. - src_alpha);
+    for i in 0..3 {
+        let value =
+            (color[i] as f32 * src_alpha + pixel[i] as f32 * dst_alpha * (1. - src_alpha)) / alpha;
+        pixel[i] = value.round() as u8;
+    }
+    pixel[3] = (alpha * 255.).round() as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::{active_snapshot, select};
+    use crate::themes::velo_light;
+    use crate::utils::ReflectableUuid;
+
+    #[test]
+    fn test_snapshot_to_image() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let snapshot = active_snapshot(&doc).unwrap();
+        let theme = velo_light();
+        let mut fonts = RasterFonts::default();
+
+        let image = snapshot_to_image(snapshot, &doc.images, &theme, &mut fonts, 1.);
+        // same area as the SVG export
+        assert_eq!(image.dimensions(), (540, 330));
+        // transparent outside of the content
+        assert_eq!(image.get_pixel(2, 2).0[3], 0);
+        // circle node filled in its theme color
+        let circle = theme.color_change_3.as_rgba_u8();
+        assert_eq!(image.get_pixel(390, 40).0, circle);
+        // arrow from the rectangle to the circle
+        let [r, _, b, a] = image.get_pixel(240, 70).0;
+        assert!(a > 128 && b > r);
+        // the paper node has text in its top left corner
+        let text = (160..220)
+            .flat_map(|x| (200..225).map(move |y| (x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y).0[0] < 128)
+            .count();
+        assert!(text > 20);
+
+        let image = snapshot_to_image(snapshot, &doc.images, &theme, &mut fonts, 2.);
+        assert_eq!(image.dimensions(), (1080, 660));
+
+        let circle_id = snapshot.nodes[1].id;
+        let selection = select(snapshot, &[ReflectableUuid(circle_id)]);
+        let image = snapshot_to_image(&selection, &doc.images, &theme, &mut fonts, 1.);
+        assert_eq!(image.dimensions(), (140, 140));
+        assert_eq!(image.get_pixel(70, 40).0, circle);
+
+        let png = encode_png(&image).unwrap();
+        let decoded = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+        assert_eq!(decoded.to_rgba8(), image);
+    }
+
+    #[test]
+    fn test_oversized_image() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let mut snapshot = active_snapshot(&doc).unwrap().clone();
+        snapshot.nodes[0].x = 1e7;
+        let theme = velo_light();
+        let mut fonts = RasterFonts::default();
+
+        let image = snapshot_to_image(&snapshot, &doc.images, &theme, &mut fonts, 2.);
+        let (width, height) = image.dimensions();
+        assert!(width <= MAX_IMAGE_SIDE as u32 && width > MAX_IMAGE_SIDE as u32 / 2);
+        assert!(height >= 1);
+
+        assert_eq!(fit_scale(Vec2::new(100., 100.), 2.), 2.);
+        let scale = fit_scale(Vec2::new(20000., 20000.), 1.);
+        let side = (20000. * scale).ceil();
+        assert!(side * side <= MAX_IMAGE_PIXELS);
+        assert!(fit_scale(Vec2::splat(f32::INFINITY), 1.) == 0.);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{tess, PathBuilder};
+use serde_json::{Map, Value};
+
+use crate::canvas::arrow::build_arrow;
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos};
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+use crate::ui_plugin::{node_path, NodeType};
+use crate::JsonNode;
+
+/// Space around the drawn content.
+const MARGIN: f32 = 20.;
+
+/// What a tab shows on the canvas, with theme colors resolved and paths in canvas
+/// coordinates. Items are in paint order, so formats only have to draw them one by one.
+pub struct Scene<'a> {
+    /// Bottom left corner of the exported area.
+    pub min: Vec2,
+    /// Top right corner of the exported area.
+    pub max: Vec2,
+    pub items: Vec<SceneItem<'a>>,
+}
+
+pub enum SceneItem<'a> {
+    Node(SceneNode<'a>),
+    Arrow(tess::path::Path),
+    Drawing {
+        points: Vec<Vec2>,
+        path: tess::path::Path,
+        color: Color,
+    },
+}
+
+pub struct SceneNode<'a> {
+    pub node: &'a JsonNode<String>,
+    pub path: tess::path::Path,
+    pub fill: Color,
+    pub border: Color,
+    /// Encoded background image.
+    pub image: Option<&'a String>,
+    pub font_size: f32,
+    pub line_height: f32,
+}
+
+impl<'a> Scene<'a> {
+    /// `images` are the encoded images of the document the snapshot belongs to.
+    pub fn new(
+        snapshot: &'a TabSnapshot,
+        images: &'a HashMap<String, String>,
+        theme: &Theme,
+    ) -> Self {
+        let colors = ThemeColors::new(theme);
+        let nodes: Vec<&JsonNode<String>> =
+            snapshot.nodes.iter().filter(|node| node.visible).collect();
+
+        let mut bounds: Option<(Vec2, Vec2)> = None;
+        let mut add_bounds = |point: Vec2| {
+            bounds = Some(match bounds {
+                Some((min, max)) => (min.min(point), max.max(point)),
+                None => (point, point),
+            });
+        };
+        // z index of every item, like on the canvas
+        let mut items: Vec<(f32, SceneItem)> = vec![];
+        for node in nodes.iter() {
+            let center = Vec2::new(node.x, node.y);
+            let size = Vec2::new(node.width, node.height);
+            add_bounds(center - size / 2.);
+            add_bounds(center + size / 2.);
+            let fill = colors.get(&node.bg_color).unwrap_or(theme.node_bg);
+            let is_transparent = fill == Color::NONE;
+            let border = if node.node_type != NodeType::Paper && !is_transparent {
+                theme.node_border
+            } else {
+                Color::NONE
+            };
+            // transparent nodes are used as big headings
+            let (font_size, line_height) = if is_transparent {
+                (3. * theme.font_size, 3. * theme.line_height)
+            } else {
+                (theme.font_size, theme.line_height)
+            };
+            let path = node_path(&node.node_type, node.width, node.height)
+                .0
+                .transformed(&tess::math::Transform::translation(node.x, node.y));
+            let image = snapshot
+                .images
+                .get(&node.id.to_string())
+                .and_then(|hash| images.get(hash));
+            items.push((
+                node.z + 0.001,
+                SceneItem::Node(SceneNode {
+                    node,
+                    path,
+                    fill,
+                    border,
+                    image,
+                    font_size,
+                    line_height,
+                }),
+            ));
+        }
+        for arrow in snapshot.arrows.iter().filter(|arrow| arrow.visible) {
+            let (Some((start, start_z)), Some((end, end_z))) = (
+                connector_position(&nodes, arrow.start),
+                connector_position(&nodes, arrow.end),
+            ) else {
+                continue;
+            };
+            add_bounds(start);
+            add_bounds(end);
+            let path = build_arrow(start, end, *arrow).0;
+            items.push((f32::max(start_z, end_z), SceneItem::Arrow(path)));
+        }
+        for drawing in snapshot.drawings.iter() {
+            let offset = Vec2::new(drawing.x, drawing.y);
+            let points: Vec<Vec2> = drawing.points.iter().map(|p| offset + *p).collect();
+            let Some(start) = points.first() else {
+                continue;
+            };
+            let mut path_builder = PathBuilder::new();
+            path_builder.move_to(*start);
+            for point in points.iter() {
+                add_bounds(*point);
+                path_builder.line_to(*point);
+            }
+            let color = colors.get(&drawing.drawing_color).unwrap_or(theme.font);
+            items.push((
+                drawing.z,
+                SceneItem::Drawing {
+                    path: path_builder.build().0,
+                    points,
+                    color,
+                },
+            ));
+        }
+        items.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (min, max) = bounds.unwrap_or_default();
+        Scene {
+            min: min - MARGIN,
+            max: max + MARGIN,
+            items: items.into_iter().map(|(_, item)| item).collect(),
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+}
+
+/// Where an arrow attaches to a node, and the z index of that node.
+fn connector_position(nodes: &[&JsonNode<String>], connect: ArrowConnect) -> Option<(Vec2, f32)> {
+    let node = nodes.iter().find(|node| node.id == connect.id.0)?;
+    let offset = match connect.pos {
+        ArrowConnectPos::Left => Vec2::new(-node.width / 2., 0.),
+        ArrowConnectPos::Bottom => Vec2::new(0., -node.height / 2.),
+        ArrowConnectPos::Top => Vec2::new(0., node.height / 2.),
+        ArrowConnectPos::Right => Vec2::new(node.width / 2., 0.),
+    };
+    Some((Vec2::new(node.x, node.y) + offset, node.z))
+}
+
+/// Theme colors by field name, which is how nodes and drawings store their colors.
+pub struct ThemeColors(Map<String, Value>);
+
+impl ThemeColors {
+    pub fn new(theme: &Theme) -> Self {
+        match serde_json::to_value(theme) {
+            Ok(Value::Object(colors)) => ThemeColors(colors),
+            _ => ThemeColors(Map::new()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Color> {
+        serde_json::from_value(self.0.get(name)?.clone()).ok()
+    }
+}
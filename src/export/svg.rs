@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::{tess, tess::path::PathEvent};
+
+use super::{Scene, SceneItem, SceneNode};
+use crate::document::TabSnapshot;
+use crate::themes::Theme;
+use crate::TextPos;
+
+/// Distance of top left aligned text from the node border.
+const TEXT_PADDING: f32 = 5.;
+
+/// Render a tab as a standalone SVG document, in the colors of `theme`.
+/// `images` are the encoded images of the document the snapshot belongs to.
+pub fn snapshot_to_svg(
+    snapshot: &TabSnapshot,
+    images: &HashMap<String, String>,
+    theme: &Theme,
+) -> String {
+    let scene = Scene::new(snapshot, images, theme);
+    let (min, size) = (coords(Vec2::new(scene.min.x, scene.max.y)), scene.size());
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">",
+        num(size.x),
+        num(size.y),
+        num(min.0),
+        num(min.1),
+        num(size.x),
+        num(size.y),
+    );
+    if let Some(color) = theme.canvas_bg_color {
+        let _ = writeln!(
+            svg,
+            "<rect {} width=\"{}\" height=\"{}\"{}/>",
+            min.xy_attrs(),
+            num(size.x),
+            num(size.y),
+            paint("fill", color),
+        );
+    }
+    for item in scene.items.iter() {
+        match item {
+            SceneItem::Node(node) => svg.push_str(&node_svg(node, theme)),
+            SceneItem::Arrow(path) => {
+                let _ = write!(
+                    svg,
+                    "<path d=\"{}\" fill=\"none\"{} stroke-width=\"1.5\"/>",
+                    path_data(path),
+                    paint("stroke", theme.arrow),
+                );
+            }
+            SceneItem::Drawing { points, color, .. } => {
+                let points: Vec<String> = points.iter().map(|p| coords(*p).to_string()).collect();
+                let _ = write!(
+                    svg,
+                    "<polyline points=\"{}\" fill=\"none\"{} stroke-width=\"2\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                    points.join(" "),
+                    paint("stroke", *color),
+                );
+            }
+        }
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn node_svg(scene_node: &SceneNode, theme: &Theme) -> String {
+    let node = scene_node.node;
+    let mut svg = String::from("<g>");
+    let _ = write!(
+        svg,
+        "<path d=\"{}\"{}{} stroke-width=\"1\"/>",
+        path_data(&scene_node.path),
+        paint("fill", scene_node.fill),
+        paint("stroke", scene_node.border),
+    );
+    let top_left = coords(Vec2::new(
+        node.x - node.width / 2.,
+        node.y + node.height / 2.,
+    ));
+    if let Some(image) = scene_node.image {
+        // same as the canvas, the image covers the node and is cropped to fit
+        let _ = write!(
+            svg,
+            "<image {} width=\"{}\" height=\"{}\" preserveAspectRatio=\"xMidYMid slice\" xlink:href=\"data:image/png;base64,{}\"/>",
+            top_left.xy_attrs(),
+            num(node.width),
+            num(node.height),
+            image,
+        );
+    }
+    let lines: Vec<&str> = node.text.text.lines().collect();
+    if !lines.is_empty() {
+        let line_height = scene_node.line_height;
+        let (anchor, baseline, x, first_y) = match node.text.pos {
+            TextPos::Center => (
+                "middle",
+                "central",
+                node.x,
+                node.y + (lines.len() - 1) as f32 * line_height / 2.,
+            ),
+            TextPos::TopLeft => (
+                "start",
+                "hanging",
+                node.x - node.width / 2. + TEXT_PADDING,
+                node.y + node.height / 2. - TEXT_PADDING,
+            ),
+        };
+        let _ = write!(
+            svg,
+            "<text font-family=\"{}\" font-size=\"{}\" text-anchor=\"{}\" dominant-baseline=\"{}\"{}>",
+            escape(&theme.font_name),
+            num(scene_node.font_size),
+            anchor,
+            baseline,
+            paint("fill", theme.font),
+        );
+        for (i, line) in lines.iter().enumerate() {
+            let position = coords(Vec2::new(x, first_y - i as f32 * line_height));
+            let _ = write!(
+                svg,
+                "<tspan {}>{}</tspan>",
+                position.xy_attrs(),
+                escape(line)
+            );
+        }
+        svg.push_str("</text>");
+    }
+    svg.push_str("</g>");
+    svg
+}
+
+fn path_data(path: &tess::path::Path) -> String {
+    let point = |p: tess::math::Point| coords(Vec2::new(p.x, p.y));
+    let mut data = vec![];
+    for event in path.iter() {
+        match event {
+            PathEvent::Begin { at } => data.push(format!("M{}", point(at))),
+            PathEvent::Line { to, .. } => data.push(format!("L{}", point(to))),
+            PathEvent::Quadratic { ctrl, to, .. } => {
+                data.push(format!("Q{} {}", point(ctrl), point(to)))
+            }
+            PathEvent::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => data.push(format!("C{} {} {}", point(ctrl1), point(ctrl2), point(to))),
+            PathEvent::End { close: true, .. } => data.push("Z".to_string()),
+            PathEvent::End { close: false, .. } => {}
+        }
+    }
+    data.join(" ")
+}
+
+/// A canvas position in SVG coordinates, where y grows downwards.
+struct Coords(f32, f32);
+
+impl Coords {
+    fn xy_attrs(&self) -> String {
+        format!("x=\"{}\" y=\"{}\"", num(self.0), num(self.1))
+    }
+}
+
+impl std::fmt::Display for Coords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", num(self.0), num(self.1))
+    }
+}
+
+fn coords(point: Vec2) -> Coords {
+    Coords(point.x, -point.y)
+}
+
+/// Color attribute, with an opacity attribute for translucent colors.
+fn paint(attribute: &str, color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    match a {
+        0 => format!(" {}=\"none\"", attribute),
+        255 => format!(" {}=\"#{:02x}{:02x}{:02x}\"", attribute, r, g, b),
+        _ => format!(
+            " {0}=\"#{1:02x}{2:02x}{3:02x}\" {0}-opacity=\"{4}\"",
+            attribute,
+            r,
+            g,
+            b,
+            num(color.a())
+        ),
+    }
+}
+
+/// Numbers with at most two decimals, so output doesn't depend on float noise.
+fn num(value: f32) -> String {
+    let value = format!("{:.2}", value);
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    match value {
+        "-0" => "0".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::active_snapshot;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_svg_golden() {
+        let doc = doc_from_json(include_str!("fixtures/board.json")).unwrap();
+        let snapshot = active_snapshot(&doc).unwrap();
+        let svg = snapshot_to_svg(snapshot, &doc.images, &velo_light());
+        assert_eq!(svg, include_str!("fixtures/board.svg"));
+    }
+
+    #[test]
+    fn test_num() {
+        assert_eq!(num(1.0), "1");
+        assert_eq!(num(-0.001), "0");
+        assert_eq!(num(12.345), "12.35");
+        assert_eq!(num(-7.5), "-7.5");
+    }
+}
@@ -0,0 +1,564 @@
+use std::collections::HashMap;
+
+use crate::canvas::arrow::components::ArrowType;
+use crate::ui_plugin::{NodeType, TextPos};
+
+use super::{parse_color, Direction, Fill, ImportError, ParsedGraph};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Identifier, number or quoted string.
+    Id(String),
+    /// `<...>` label, kept without the outer brackets.
+    Html(String),
+    Edge,
+    Symbol(char),
+}
+
+type Attributes = HashMap<String, String>;
+
+/// Read a Graphviz `graph` or `digraph`. Subgraphs are flattened, and attributes
+/// velo has no use for, like positions or fonts, are ignored.
+pub fn parse_dot(text: &str) -> Result<ParsedGraph, ImportError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        next: 0,
+        graph: ParsedGraph::default(),
+        is_directed: true,
+    };
+    parser.parse_graph()?;
+    Ok(parser.graph)
+}
+
+/// Split DOT source into tokens, each with its line number.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, ImportError> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    let mut at_line_start = true;
+    while let Some(c) = chars.next() {
+        let token_line = line;
+        match c {
+            '\n' => {
+                line += 1;
+                at_line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // preprocessor output lines
+            '#' if at_line_start => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if previous == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            previous = c;
+                        }
+                        None => return Err(error(token_line, "unclosed comment")),
+                    }
+                }
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if chars.peek() == Some(&'"') => {
+                            value.push(chars.next().unwrap())
+                        }
+                        // line continuation
+                        Some('\\') if chars.peek() == Some(&'\n') => {
+                            chars.next();
+                            line += 1;
+                        }
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            value.push(c);
+                        }
+                        None => return Err(error(token_line, "unclosed string")),
+                    }
+                }
+                tokens.push((Token::Id(value), token_line));
+            }
+            '<' => {
+                let mut value = String::new();
+                let mut depth = 1;
+                loop {
+                    let c = chars.next();
+                    match c {
+                        Some('<') => depth += 1,
+                        Some('>') if depth == 1 => break,
+                        Some('>') => depth -= 1,
+                        Some('\n') => line += 1,
+                        Some(_) => {}
+                        None => return Err(error(token_line, "unclosed html label")),
+                    }
+                    value.extend(c);
+                }
+                tokens.push((Token::Html(html_text(&value)), token_line));
+            }
+            '-' if matches!(chars.peek(), Some('>') | Some('-')) => {
+                chars.next();
+                tokens.push((Token::Edge, token_line));
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' | '+' => {
+                tokens.push((Token::Symbol(c), token_line));
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut value = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Id(value), token_line));
+            }
+            c => return Err(error(token_line, &format!("unexpected \"{}\"", c))),
+        }
+        at_line_start = false;
+    }
+    Ok(tokens)
+}
+
+/// Text of an html label, with line breaks kept and other markup dropped.
+fn html_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(length) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = rest[start + 1..start + length].trim().to_lowercase();
+        // kept as a label escape, like line breaks in quoted labels
+        if tag.starts_with("br") {
+            text.push_str("\\n");
+        }
+        rest = &rest[start + length + 1..];
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+fn error(line: usize, message: &str) -> ImportError {
+    ImportError {
+        line,
+        message: message.to_string(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    graph: ParsedGraph,
+    is_directed: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(token, _)| token.clone());
+        self.next += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ImportError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(error(self.line(), &format!("expected \"{}\"", symbol)))
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case(keyword))
+    }
+
+    /// An identifier, with `"a" + "b"` concatenations joined.
+    fn id(&mut self) -> Result<String, ImportError> {
+        let mut value = match self.advance() {
+            Some(Token::Id(id)) => id,
+            Some(Token::Html(html)) => html,
+            _ => {
+                self.next -= 1;
+                return Err(error(self.line(), "expected an identifier"));
+            }
+        };
+        while self.eat('+') {
+            match self.advance() {
+                Some(Token::Id(id)) => value += &id,
+                _ => return Err(error(self.line(), "expected a string after \"+\"")),
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_graph(&mut self) -> Result<(), ImportError> {
+        if self.is_keyword("strict") {
+            self.next += 1;
+        }
+        if self.is_keyword("digraph") {
+            self.is_directed = true;
+        } else if self.is_keyword("graph") {
+            self.is_directed = false;
+        } else {
+            return Err(error(self.line(), "expected \"graph\" or \"digraph\""));
+        }
+        self.next += 1;
+        if !matches!(self.peek(), Some(Token::Symbol('{'))) {
+            self.id()?;
+        }
+        self.expect('{')?;
+        self.parse_statements(&Attributes::new(), &Attributes::new())?;
+        Ok(())
+    }
+
+    /// Statements up to the closing brace, with the node and edge defaults of the
+    /// enclosing graph. Returns the nodes mentioned, for edges to subgraphs.
+    fn parse_statements(
+        &mut self,
+        node_defaults: &Attributes,
+        edge_defaults: &Attributes,
+    ) -> Result<Vec<String>, ImportError> {
+        let mut node_defaults = node_defaults.clone();
+        let mut edge_defaults = edge_defaults.clone();
+        let mut keys = vec![];
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('}')) => {
+                    self.next += 1;
+                    return Ok(keys);
+                }
+                None => return Err(error(self.line(), "expected \"}\"")),
+                _ => {}
+            }
+            let is_defaults = ["node", "edge", "graph"]
+                .iter()
+                .any(|keyword| self.is_keyword(keyword))
+                && self.tokens.get(self.next + 1).map(|(token, _)| token)
+                    == Some(&Token::Symbol('['));
+            if is_defaults {
+                let Some(Token::Id(kind)) = self.advance() else {
+                    unreachable!()
+                };
+                let attributes = self.attributes()?;
+                match kind.to_lowercase().as_str() {
+                    "node" => node_defaults.extend(attributes),
+                    "edge" => edge_defaults.extend(attributes),
+                    _ => self.graph_attributes(&attributes),
+                }
+            } else if self.tokens.get(self.next + 1).map(|(token, _)| token)
+                == Some(&Token::Symbol('='))
+                && !self.is_keyword("subgraph")
+            {
+                let name = self.id()?;
+                self.expect('=')?;
+                let value = self.id()?;
+                self.graph_attributes(&Attributes::from([(name, value)]));
+            } else {
+                keys.extend(self.parse_edge_or_node(&node_defaults, &edge_defaults)?);
+            }
+            while self.eat(';') || self.eat(',') {}
+        }
+    }
+
+    /// A node statement, a subgraph, or a chain of those joined by edges.
+    fn parse_edge_or_node(
+        &mut self,
+        node_defaults: &Attributes,
+        edge_defaults: &Attributes,
+    ) -> Result<Vec<String>, ImportError> {
+        let mut operands = vec![self.operand(node_defaults, edge_defaults)?];
+        while self.peek() == Some(&Token::Edge) {
+            self.next += 1;
+            operands.push(self.operand(node_defaults, edge_defaults)?);
+        }
+        let mut attributes = Attributes::new();
+        if self.peek() == Some(&Token::Symbol('[')) {
+            attributes = self.attributes()?;
+        }
+        if let [(keys, is_node)] = operands.as_slice() {
+            if *is_node {
+                let index = self.graph.node_index(&keys[0]);
+                let mut node_attributes = node_defaults.clone();
+                node_attributes.extend(attributes);
+                self.node_attributes(index, &node_attributes);
+            }
+            return Ok(keys.clone());
+        }
+        let mut merged = edge_defaults.clone();
+        merged.extend(attributes);
+        let (arrow_type, is_reversed) = self.arrow_type(&merged);
+        for pair in operands.windows(2) {
+            for start in pair[0].0.iter() {
+                for end in pair[1].0.iter() {
+                    match is_reversed {
+                        false => self.graph.add_edge(start, end, arrow_type),
+                        true => self.graph.add_edge(end, start, arrow_type),
+                    }
+                }
+            }
+        }
+        Ok(operands.into_iter().flat_map(|(keys, _)| keys).collect())
+    }
+
+    /// Node id or subgraph, and whether it is a single node.
+    fn operand(
+        &mut self,
+        node_defaults: &Attributes,
+        edge_defaults: &Attributes,
+    ) -> Result<(Vec<String>, bool), ImportError> {
+        if self.is_keyword("subgraph") || self.peek() == Some(&Token::Symbol('{')) {
+            if self.is_keyword("subgraph") {
+                self.next += 1;
+                if self.peek() != Some(&Token::Symbol('{')) {
+                    self.id()?;
+                }
+            }
+            self.expect('{')?;
+            return Ok((self.parse_statements(node_defaults, edge_defaults)?, false));
+        }
+        let key = self.id()?;
+        // ports only tell where arrows attach
+        while self.eat(':') {
+            self.id()?;
+        }
+        let is_new = !self.graph.nodes.iter().any(|node| node.key == key);
+        let index = self.graph.node_index(&key);
+        if is_new {
+            self.node_attributes(index, node_defaults);
+        }
+        Ok((vec![key], true))
+    }
+
+    /// `[a=b, c=d][e=f]` lists.
+    fn attributes(&mut self) -> Result<Attributes, ImportError> {
+        let mut attributes = Attributes::new();
+        while self.eat('[') {
+            while !self.eat(']') {
+                let name = self.id()?;
+                let value = if self.eat('=') {
+                    self.id()?
+                } else {
+                    "true".to_string()
+                };
+                attributes.insert(name.to_lowercase(), value);
+                while self.eat(';') || self.eat(',') {}
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn graph_attributes(&mut self, attributes: &Attributes) {
+        if let Some(rankdir) = attributes.get("rankdir") {
+            self.graph.direction = match rankdir.to_uppercase().as_str() {
+                "LR" => Direction::LeftRight,
+                "RL" => Direction::RightLeft,
+                "BT" => Direction::BottomUp,
+                _ => Direction::TopDown,
+            };
+        }
+    }
+
+    fn node_attributes(&mut self, index: usize, attributes: &Attributes) {
+        let node = &mut self.graph.nodes[index];
+        if let Some(label) = attributes.get("label") {
+            let (label, text_pos) = label_text(label, &node.key);
+            node.label = label;
+            node.text_pos = text_pos;
+        }
+        if let Some(shape) = attributes.get("shape") {
+            node.node_type = match shape.to_lowercase().as_str() {
+                "circle" | "doublecircle" | "ellipse" | "oval" | "point" => NodeType::Circle,
+                "note" | "tab" | "folder" => NodeType::Paper,
+                _ => NodeType::Rect,
+            };
+        }
+        let style = attributes.get("style").map(|style| style.to_lowercase());
+        let is_filled = style.as_ref().is_some_and(|style| style.contains("filled"));
+        let fill = match (attributes.get("fillcolor"), attributes.get("color")) {
+            (Some(fill), _) if is_filled => parse_color(first_color(fill)),
+            (None, Some(color)) if is_filled => parse_color(first_color(color)),
+            // unfilled nodes without a border are bare text
+            (_, Some(color)) => parse_color(first_color(color))
+                .filter(|color| color.a() == 0.)
+                .map(|_| bevy::prelude::Color::NONE),
+            _ => None,
+        };
+        let styles_fill = ["style", "fillcolor", "color"]
+            .iter()
+            .any(|name| attributes.contains_key(*name));
+        if styles_fill {
+            node.fill = fill.map(Fill::Color);
+        }
+    }
+
+    /// Arrow type of an edge, and whether it points back from the end to the start.
+    fn arrow_type(&self, attributes: &Attributes) -> (ArrowType, bool) {
+        let default_dir = if self.is_directed { "forward" } else { "none" };
+        let dir = attributes.get("dir").map_or(default_dir, String::as_str);
+        let has_head = |name: &str| {
+            attributes
+                .get(name)
+                .is_none_or(|shape| shape.as_str() != "none")
+        };
+        let head = matches!(dir, "forward" | "both") && has_head("arrowhead");
+        let tail = matches!(dir, "back" | "both") && has_head("arrowtail");
+        let is_bold = attributes
+            .get("style")
+            .is_some_and(|style| style.to_lowercase().contains("bold"))
+            || attributes
+                .get("penwidth")
+                .and_then(|width| width.parse::<f32>().ok())
+                .is_some_and(|width| width >= 2.);
+        let arrow_type = match (head || tail, head && tail, is_bold) {
+            (false, _, false) => ArrowType::Line,
+            (false, _, true) => ArrowType::ParallelLine,
+            (true, false, false) => ArrowType::Arrow,
+            (true, false, true) => ArrowType::ParallelArrow,
+            (true, true, false) => ArrowType::DoubleArrow,
+            (true, true, true) => ArrowType::ParallelDoubleArrow,
+        };
+        (arrow_type, tail && !head)
+    }
+}
+
+/// Color lists like `red:blue` fill with gradients, the first color is close enough.
+fn first_color(color: &str) -> &str {
+    color.split([':', ';']).next().unwrap_or_default()
+}
+
+/// Label escapes: `\n`, `\l` and `\r` end centered, left and right aligned lines,
+/// and `\N` stands for the node name.
+fn label_text(label: &str, key: &str) -> (String, TextPos) {
+    let mut text = String::new();
+    let mut text_pos = TextPos::Center;
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('r') => text.push('\n'),
+            Some('l') => {
+                text_pos = TextPos::TopLeft;
+                text.push('\n');
+            }
+            Some('N') => text += key,
+            Some(c) => text.push(c),
+            None => text.push('\\'),
+        }
+    }
+    (text.trim_end_matches('\n').to_string(), text_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::prelude::Color;
+
+    #[test]
+    fn test_parse_dot() {
+        let graph = parse_dot(
+            r##"
+// design notes
+digraph "design" {
+    rankdir=LR
+    node [shape=box style=filled fillcolor="#ffecac"];
+    start [label="Start\nhere"];
+    todo [shape=note label="left\laligned\l" style=""];
+    done [shape=circle, fillcolor=lightblue];
+    title [label="Title" style="" color=transparent];
+    start -> todo -> done [style=bold];
+    done -> start [dir=both];
+    /* clusters are flattened */
+    subgraph cluster_a { a -- b [dir=none] }
+    c -> { a b } [dir=back];
+    port:n -> done:w;
+}
+"##,
+        )
+        .unwrap();
+        assert_eq!(graph.direction, Direction::LeftRight);
+        let keys: Vec<&str> = graph.nodes.iter().map(|node| node.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["start", "todo", "done", "title", "a", "b", "c", "port"]
+        );
+        assert_eq!(graph.nodes[0].label, "Start\nhere");
+        assert_eq!(
+            graph.nodes[0].fill,
+            Some(Fill::Color(Color::hex("ffecac").unwrap()))
+        );
+        assert_eq!(graph.nodes[1].node_type, NodeType::Paper);
+        assert_eq!(graph.nodes[1].label, "left\naligned");
+        assert_eq!(graph.nodes[1].text_pos, TextPos::TopLeft);
+        assert_eq!(graph.nodes[1].fill, None);
+        assert_eq!(graph.nodes[2].node_type, NodeType::Circle);
+        assert_eq!(graph.nodes[3].fill, Some(Fill::Color(Color::NONE)));
+        assert_eq!(graph.nodes[6].label, "c");
+        let edges: Vec<(&str, &str, ArrowType)> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.start.as_str(), edge.end.as_str(), edge.arrow_type))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("start", "todo", ArrowType::ParallelArrow),
+                ("todo", "done", ArrowType::ParallelArrow),
+                ("done", "start", ArrowType::DoubleArrow),
+                ("a", "b", ArrowType::Line),
+                ("a", "c", ArrowType::Arrow),
+                ("b", "c", ArrowType::Arrow),
+                ("port", "done", ArrowType::Arrow),
+            ]
+        );
+        assert_eq!(
+            parse_dot("digraph { a -> }").map(|_| ()),
+            Err(error(1, "expected an identifier"))
+        );
+    }
+}
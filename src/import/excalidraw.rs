@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Vec2;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+use crate::document::TabSnapshot;
+use crate::export::ThemeColors;
+use crate::themes::Theme;
+use crate::ui_plugin::{DrawingJsonNode, NodeType, TextPos};
+use crate::utils::ReflectableUuid;
+use crate::{JsonNode, JsonNodeText};
+
+use super::{nearest_color, nearest_node_color, parse_color, DRAWING_COLORS};
+
+/// Length of the lines drawn for arrowheads that don't end on a node.
+const ARROWHEAD_LENGTH: f32 = 15.;
+/// Font size Excalidraw uses when an element doesn't say.
+const DEFAULT_FONT_SIZE: f32 = 20.;
+
+/// An `.excalidraw` file, of which only the elements matter.
+#[derive(Deserialize, Default, Debug)]
+pub struct ExcalidrawFile {
+    #[serde(default)]
+    pub elements: Vec<ExcalidrawElement>,
+}
+
+/// The element fields velo uses. Excalidraw coordinates are the top left corner
+/// of an element, with y growing downwards, and points are relative to that corner.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExcalidrawElement {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub element_type: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Clockwise rotation around the center, in radians.
+    pub angle: f32,
+    pub stroke_color: String,
+    pub background_color: String,
+    pub is_deleted: bool,
+    pub text: String,
+    pub original_text: Option<String>,
+    pub container_id: Option<String>,
+    pub font_size: Option<f32>,
+    pub text_align: String,
+    pub vertical_align: String,
+    pub points: Vec<[f32; 2]>,
+    pub start_binding: Option<ExcalidrawBinding>,
+    pub end_binding: Option<ExcalidrawBinding>,
+    pub start_arrowhead: Option<String>,
+    pub end_arrowhead: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExcalidrawBinding {
+    pub element_id: String,
+}
+
+/// Turn an Excalidraw sketch into a tab. Rectangles, diamonds and ellipses become
+/// nodes with the text bound to them, other text becomes transparent text nodes,
+/// and arrows between two shapes become arrows. Everything else, like free hand
+/// lines, loose arrows or images, is kept as drawings of its outline.
+pub fn excalidraw_to_snapshot(file: &ExcalidrawFile, theme: &Theme) -> TabSnapshot {
+    let colors = ThemeColors::new(theme);
+    let elements: Vec<&ExcalidrawElement> = file
+        .elements
+        .iter()
+        .filter(|element| !element.is_deleted)
+        .collect();
+    let z = |index: usize| 1. + index as f32 * 0.01;
+    let mut snapshot = TabSnapshot::default();
+    // velo node of every shape element
+    let mut shapes: HashMap<&str, usize> = HashMap::new();
+    for (index, element) in elements.iter().enumerate() {
+        let node_type = match element.element_type.as_str() {
+            "rectangle" | "diamond" => NodeType::Rect,
+            "ellipse" => NodeType::Circle,
+            _ => continue,
+        };
+        let fill = parse_color(&element.background_color).filter(|color| color.a() > 0.);
+        let bg_color = fill
+            .and_then(|fill| nearest_node_color(&colors, fill))
+            .unwrap_or("node_bg");
+        shapes.insert(&element.id, snapshot.nodes.len());
+        snapshot.nodes.push(JsonNode {
+            id: Uuid::new_v4(),
+            node_type,
+            x: element.x + element.width / 2.,
+            y: -(element.y + element.height / 2.),
+            z: z(index),
+            width: element.width,
+            height: element.height,
+            bg_color: bg_color.to_string(),
+            visible: true,
+            ..Default::default()
+        });
+    }
+
+    for (index, element) in elements.iter().enumerate() {
+        match element.element_type.as_str() {
+            "rectangle" | "diamond" | "ellipse" => {}
+            "text" => {
+                let text = element.original_text.as_deref().unwrap_or(&element.text);
+                let pos = match (element.text_align.as_str(), element.vertical_align.as_str()) {
+                    ("left", "top") => TextPos::TopLeft,
+                    _ => TextPos::Center,
+                };
+                let container = element
+                    .container_id
+                    .as_deref()
+                    .and_then(|id| shapes.get(id));
+                if let Some(&node) = container {
+                    let node = &mut snapshot.nodes[node];
+                    node.text = JsonNodeText {
+                        text: text.to_string(),
+                        pos,
+                    };
+                    continue;
+                }
+                // transparent nodes show their text at three times the font size
+                let scale = 3. * theme.font_size / element.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+                snapshot.nodes.push(JsonNode {
+                    id: Uuid::new_v4(),
+                    x: element.x + element.width / 2.,
+                    y: -(element.y + element.height / 2.),
+                    z: z(index),
+                    width: element.width * scale,
+                    height: element.height * scale,
+                    text: JsonNodeText {
+                        text: text.to_string(),
+                        pos,
+                    },
+                    bg_color: "color_none".to_string(),
+                    visible: true,
+                    ..Default::default()
+                });
+            }
+            "arrow" => {
+                let bound = |binding: &Option<ExcalidrawBinding>| {
+                    let id = binding.as_ref()?.element_id.as_str();
+                    shapes.get(id).copied()
+                };
+                match (bound(&element.start_binding), bound(&element.end_binding)) {
+                    (Some(start), Some(end)) if start != end => {
+                        let arrow = connect(&snapshot.nodes, element, start, end);
+                        snapshot.arrows.push(arrow);
+                    }
+                    _ => snapshot.drawings.push(drawing(element, &colors, z(index))),
+                }
+            }
+            _ => snapshot.drawings.push(drawing(element, &colors, z(index))),
+        }
+    }
+    snapshot
+}
+
+/// Arrow between two shape nodes, attached to the sides the Excalidraw arrow ends at.
+fn connect(
+    nodes: &[JsonNode<String>],
+    element: &ExcalidrawElement,
+    start: usize,
+    end: usize,
+) -> ArrowMeta {
+    let origin = Vec2::new(element.x, element.y);
+    let first = element.points.first().copied().unwrap_or_default();
+    let last = element.points.last().copied().unwrap_or_default();
+    let side = |node: &JsonNode<String>, point: [f32; 2]| {
+        // back to velo coordinates, relative to the node center
+        let point = origin + Vec2::from(point);
+        let offset = Vec2::new(point.x - node.x, -point.y - node.y);
+        let offset = offset / Vec2::new(node.width, node.height).max(Vec2::ONE);
+        if offset.x.abs() > offset.y.abs() {
+            if offset.x > 0. {
+                ArrowConnectPos::Right
+            } else {
+                ArrowConnectPos::Left
+            }
+        } else if offset.y > 0. {
+            ArrowConnectPos::Top
+        } else {
+            ArrowConnectPos::Bottom
+        }
+    };
+    let mut start = ArrowConnect {
+        id: ReflectableUuid(nodes[start].id),
+        pos: side(&nodes[start], first),
+    };
+    let mut end = ArrowConnect {
+        id: ReflectableUuid(nodes[end].id),
+        pos: side(&nodes[end], last),
+    };
+    let arrow_type = match (
+        element.start_arrowhead.is_some(),
+        element.end_arrowhead.is_some(),
+    ) {
+        (true, true) => ArrowType::DoubleArrow,
+        (false, true) => ArrowType::Arrow,
+        (true, false) => {
+            // velo arrows point at their end
+            std::mem::swap(&mut start, &mut end);
+            ArrowType::Arrow
+        }
+        (false, false) => ArrowType::Line,
+    };
+    ArrowMeta {
+        visible: true,
+        arrow_type,
+        start,
+        end,
+    }
+}
+
+/// Polyline of an element's points, or of its outline when it has none.
+/// Arrowheads are drawn as two short lines at the end of the polyline.
+fn drawing(element: &ExcalidrawElement, colors: &ThemeColors, z: f32) -> DrawingJsonNode<String> {
+    let size = Vec2::new(element.width, element.height);
+    let mut points: Vec<Vec2> = if element.points.is_empty() {
+        vec![Vec2::ZERO, size * Vec2::X, size, size * Vec2::Y, Vec2::ZERO]
+    } else {
+        element
+            .points
+            .iter()
+            .map(|point| Vec2::from(*point))
+            .collect()
+    };
+    if element.element_type == "arrow" {
+        if element.end_arrowhead.is_some() {
+            add_arrowhead(&mut points);
+        }
+        if element.start_arrowhead.is_some() {
+            points.reverse();
+            add_arrowhead(&mut points);
+        }
+    }
+    // rotate around the element center, then flip to velo coordinates
+    let center = size / 2.;
+    let rotation = Vec2::from_angle(element.angle);
+    let points = points
+        .into_iter()
+        .map(|point| center + rotation.rotate(point - center))
+        .map(|point| Vec2::new(point.x, -point.y))
+        .collect();
+    let drawing_color = parse_color(&element.stroke_color)
+        .and_then(|color| nearest_color(colors, &DRAWING_COLORS, color))
+        .unwrap_or("drawing_two_points_btn");
+    DrawingJsonNode {
+        x: element.x,
+        y: -element.y,
+        z,
+        id: ReflectableUuid(Uuid::new_v4()),
+        points,
+        drawing_color: drawing_color.to_string(),
+        width: 2.,
+    }
+}
+
+fn add_arrowhead(points: &mut Vec<Vec2>) {
+    let [.., from, tip] = points[..] else {
+        return;
+    };
+    let Some(back) = (from - tip).try_normalize() else {
+        return;
+    };
+    let back = back * ARROWHEAD_LENGTH;
+    let wing = Vec2::from_angle(std::f32::consts::FRAC_PI_6);
+    points.extend([
+        tip + wing.rotate(back),
+        tip,
+        tip + wing.rotate(back) * Vec2::NEG_ONE,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_excalidraw() {
+        let file: ExcalidrawFile = serde_json::from_str(
+            r##"{"type": "excalidraw", "version": 2, "elements": [
+                {"id": "box", "type": "rectangle", "x": 0, "y": 0, "width": 200, "height": 100,
+                 "strokeColor": "#1e1e1e", "backgroundColor": "#a5d8ff", "angle": 0},
+                {"id": "label", "type": "text", "x": 60, "y": 40, "width": 80, "height": 25,
+                 "text": "Hello", "originalText": "Hello", "containerId": "box",
+                 "textAlign": "center", "verticalAlign": "middle"},
+                {"id": "round", "type": "ellipse", "x": 400, "y": 0, "width": 100, "height": 100,
+                 "backgroundColor": "transparent"},
+                {"id": "gone", "type": "diamond", "x": 0, "y": 0, "width": 10, "height": 10,
+                 "isDeleted": true},
+                {"id": "link", "type": "arrow", "x": 200, "y": 50, "width": 200, "height": 0,
+                 "points": [[0, 0], [200, 0]], "startArrowhead": null, "endArrowhead": "arrow",
+                 "startBinding": {"elementId": "box", "focus": 0, "gap": 1},
+                 "endBinding": {"elementId": "round", "focus": 0, "gap": 1}},
+                {"id": "loose", "type": "arrow", "x": 0, "y": 200, "width": 100, "height": 0,
+                 "points": [[0, 0], [100, 0]], "endArrowhead": "arrow", "strokeColor": "#e03131"},
+                {"id": "pen", "type": "freedraw", "x": 10, "y": 300, "width": 5, "height": 5,
+                 "points": [[0, 0], [5, 5]]},
+                {"id": "note", "type": "text", "x": 300, "y": 300, "width": 100, "height": 25,
+                 "text": "free", "fontSize": 42, "textAlign": "left", "verticalAlign": "top"},
+                {"id": "picture", "type": "image", "x": 0, "y": 400, "width": 50, "height": 20}
+            ]}"##,
+        )
+        .unwrap();
+        let snapshot = excalidraw_to_snapshot(&file, &velo_light());
+
+        assert_eq!(snapshot.nodes.len(), 3);
+        let [box_node, round, note] = &snapshot.nodes[..] else {
+            unreachable!()
+        };
+        assert_eq!((box_node.x, box_node.y), (100., -50.));
+        assert_eq!(box_node.node_type, NodeType::Rect);
+        assert_eq!(box_node.text.text, "Hello");
+        assert_eq!(box_node.bg_color, "color_change_3");
+        assert_eq!(round.node_type, NodeType::Circle);
+        assert_eq!(round.bg_color, "node_bg");
+        assert_eq!(note.text.text, "free");
+        assert_eq!(note.text.pos, TextPos::TopLeft);
+        assert_eq!(note.bg_color, "color_none");
+        assert_eq!((note.width, note.height), (100., 25.));
+
+        assert_eq!(snapshot.arrows.len(), 1);
+        let arrow = snapshot.arrows[0];
+        assert_eq!(arrow.arrow_type, ArrowType::Arrow);
+        assert_eq!(
+            (arrow.start.id.0, arrow.start.pos),
+            (box_node.id, ArrowConnectPos::Right)
+        );
+        assert_eq!(
+            (arrow.end.id.0, arrow.end.pos),
+            (round.id, ArrowConnectPos::Left)
+        );
+
+        assert_eq!(snapshot.drawings.len(), 3);
+        let loose = &snapshot.drawings[0];
+        assert_eq!((loose.x, loose.y), (0., -200.));
+        // the line and both wings of the arrowhead
+        assert_eq!(loose.points.len(), 5);
+        assert_eq!(loose.points[1], Vec2::new(100., 0.));
+        assert_eq!(loose.drawing_color, "drawing_pencil_btn");
+        assert_eq!(snapshot.drawings[1].points[1], Vec2::new(5., -5.));
+        // images become their outline
+        assert_eq!(snapshot.drawings[2].points.len(), 5);
+        assert_eq!(snapshot.drawings[2].points[2], Vec2::new(50., -20.));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Vec2;
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+use crate::document::TabSnapshot;
+use crate::export::{CanvasNode, JsonCanvas, ThemeColors, COLOR_PRESETS};
+use crate::themes::Theme;
+use crate::ui_plugin::{NodeType, TextPos};
+use crate::utils::ReflectableUuid;
+use crate::{JsonNode, JsonNodeText};
+
+use super::{facing_sides, nearest_node_color, parse_color};
+
+/// Turn a JSON Canvas board into a tab. File and link nodes show their path or url,
+/// groups are left out and the nodes in them are kept. Node ids that are uuids are
+/// kept, so a board exported from velo comes back with the same ids.
+pub fn json_canvas_to_snapshot(canvas: &JsonCanvas, theme: &Theme) -> TabSnapshot {
+    let colors = ThemeColors::new(theme);
+    let mut ids: HashMap<&str, Uuid> = HashMap::new();
+    let mut nodes: Vec<JsonNode<String>> = vec![];
+    for node in canvas.nodes.iter() {
+        let Some(text) = node_text(node) else {
+            continue;
+        };
+        let id = Uuid::parse_str(&node.id).unwrap_or_else(|_| Uuid::new_v4());
+        ids.insert(&node.id, id);
+        nodes.push(JsonNode {
+            id,
+            node_type: NodeType::Rect,
+            x: node.x + node.width / 2.,
+            y: -(node.y + node.height / 2.),
+            z: 1. + nodes.len() as f32 * 0.01,
+            width: node.width,
+            height: node.height,
+            text: JsonNodeText {
+                text,
+                pos: TextPos::Center,
+            },
+            bg_color: node
+                .color
+                .as_deref()
+                .and_then(|color| color_name(&colors, color))
+                .unwrap_or("node_bg")
+                .to_string(),
+            visible: true,
+            tags: vec![],
+        });
+    }
+
+    let center = |id: Uuid| {
+        let node = nodes.iter().find(|node| node.id == id)?;
+        Some(Vec2::new(node.x, node.y))
+    };
+    let mut arrows = vec![];
+    for edge in canvas.edges.iter() {
+        let (Some(&from), Some(&to)) = (
+            ids.get(edge.from_node.as_str()),
+            ids.get(edge.to_node.as_str()),
+        ) else {
+            continue;
+        };
+        if from == to {
+            continue;
+        }
+        let (Some(from_center), Some(to_center)) = (center(from), center(to)) else {
+            continue;
+        };
+        let (facing_from, facing_to) = facing_sides(from_center, to_center);
+        let from_side = edge.from_side.as_deref().and_then(side_pos);
+        let to_side = edge.to_side.as_deref().and_then(side_pos);
+        let mut start = ArrowConnect {
+            id: ReflectableUuid(from),
+            pos: from_side.unwrap_or(facing_from),
+        };
+        let mut end = ArrowConnect {
+            id: ReflectableUuid(to),
+            pos: to_side.unwrap_or(facing_to),
+        };
+        let from_arrow = edge.from_end.as_deref() == Some("arrow");
+        let to_arrow = edge.to_end.as_deref() != Some("none");
+        let arrow_type = match (from_arrow, to_arrow) {
+            (true, true) => ArrowType::DoubleArrow,
+            (false, true) => ArrowType::Arrow,
+            (true, false) => {
+                // velo arrows point at their end
+                std::mem::swap(&mut start, &mut end);
+                ArrowType::Arrow
+            }
+            (false, false) => ArrowType::Line,
+        };
+        arrows.push(ArrowMeta {
+            visible: true,
+            arrow_type,
+            start,
+            end,
+        });
+    }
+    TabSnapshot {
+        nodes,
+        arrows,
+        ..Default::default()
+    }
+}
+
+fn node_text(node: &CanvasNode) -> Option<String> {
+    match node.node_type.as_str() {
+        "text" => Some(node.text.clone().unwrap_or_default()),
+        "file" => node.file.clone(),
+        "link" => node.url.clone(),
+        _ => None,
+    }
+}
+
+fn side_pos(side: &str) -> Option<ArrowConnectPos> {
+    match side {
+        "top" => Some(ArrowConnectPos::Top),
+        "right" => Some(ArrowConnectPos::Right),
+        "bottom" => Some(ArrowConnectPos::Bottom),
+        "left" => Some(ArrowConnectPos::Left),
+        _ => None,
+    }
+}
+
+/// Theme color of a preset number or hex color.
+fn color_name(colors: &ThemeColors, color: &str) -> Option<&'static str> {
+    if let Some((_, name)) = COLOR_PRESETS.iter().find(|(preset, _)| *preset == color) {
+        return Some(name);
+    }
+    match color {
+        // orange and purple have no theme color of their own
+        "2" => Some("color_change_4"),
+        "6" => Some("color_change_1"),
+        _ => nearest_node_color(colors, parse_color(color)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::{active_snapshot, snapshot_to_json_canvas};
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_json_canvas_round_trip() {
+        let theme = velo_light();
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let original = active_snapshot(&doc).unwrap();
+        let text = snapshot_to_json_canvas(original, &theme);
+        let canvas: JsonCanvas = serde_json::from_str(&text).unwrap();
+        let imported = json_canvas_to_snapshot(&canvas, &theme);
+
+        let visible: Vec<_> = original.nodes.iter().filter(|node| node.visible).collect();
+        assert_eq!(imported.nodes.len(), visible.len());
+        for node in imported.nodes.iter() {
+            let original = visible.iter().find(|other| other.id == node.id).unwrap();
+            assert_eq!(
+                (node.x, node.y, node.width, node.height),
+                (original.x, original.y, original.width, original.height)
+            );
+            assert_eq!(node.text.text, original.text.text);
+            // transparent nodes have no JSON Canvas color
+            if original.bg_color != "color_none" {
+                assert_eq!(node.bg_color, original.bg_color);
+            }
+        }
+        let straight = |arrow_type| match arrow_type {
+            ArrowType::ParallelLine => ArrowType::Line,
+            ArrowType::ParallelArrow => ArrowType::Arrow,
+            ArrowType::ParallelDoubleArrow => ArrowType::DoubleArrow,
+            arrow_type => arrow_type,
+        };
+        let expected: Vec<_> = original
+            .arrows
+            .iter()
+            .filter(|arrow| visible.iter().any(|node| node.id == arrow.end.id.0))
+            .map(|arrow| (arrow.start, arrow.end, straight(arrow.arrow_type)))
+            .collect();
+        let arrows: Vec<_> = imported
+            .arrows
+            .iter()
+            .map(|arrow| (arrow.start, arrow.end, arrow.arrow_type))
+            .collect();
+        assert_eq!(arrows, expected);
+
+        let obsidian: JsonCanvas = serde_json::from_str(
+            r##"{"nodes":[
+                {"id":"a1","type":"text","text":"Hello","x":0,"y":0,"width":250,"height":60,"color":"2"},
+                {"id":"b2","type":"link","url":"https://velo.app","x":0,"y":200,"width":250,"height":60,"color":"#ffecac"},
+                {"id":"g","type":"group","label":"Group","x":-20,"y":-20,"width":300,"height":300}
+            ],"edges":[{"id":"e","fromNode":"b2","fromEnd":"arrow","toNode":"a1","toEnd":"none"}]}"##,
+        )
+        .unwrap();
+        let imported = json_canvas_to_snapshot(&obsidian, &theme);
+        assert_eq!(imported.nodes.len(), 2);
+        assert_eq!((imported.nodes[1].x, imported.nodes[1].y), (125., -230.));
+        assert_eq!(imported.nodes[1].text.text, "https://velo.app");
+        assert_eq!(imported.nodes[0].bg_color, "color_change_4");
+        assert_eq!(imported.nodes[1].bg_color, "paper_node_bg");
+        let arrow = imported.arrows[0];
+        assert_eq!(arrow.arrow_type, ArrowType::Arrow);
+        assert_eq!(arrow.start.id.0, imported.nodes[0].id);
+        assert_eq!(arrow.start.pos, ArrowConnectPos::Bottom);
+        assert_eq!(arrow.end.pos, ArrowConnectPos::Top);
+    }
+}
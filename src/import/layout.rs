@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Vec2;
+
+use super::ParsedGraph;
+
+/// Which way arrows between ranks point.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    #[default]
+    TopDown,
+    BottomUp,
+    LeftRight,
+    RightLeft,
+}
+
+/// Distance between ranks and between nodes of a rank, in node sizes.
+const RANK_SPACING: f32 = 2.;
+const NODE_SPACING: f32 = 1.5;
+
+/// Layered placement of the graph, one position per node in the order of `graph.nodes`.
+/// Every node sits one rank after its furthest parent, arrows that close a cycle are
+/// left out of the ranking, and nodes of a rank are ordered by where their parents are.
+pub fn layout(graph: &ParsedGraph, node_size: Vec2) -> Vec<Vec2> {
+    let count = graph.nodes.len();
+    let index: HashMap<&str, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.key.as_str(), i))
+        .collect();
+    let mut children: Vec<Vec<usize>> = vec![vec![]; count];
+    for edge in graph.edges.iter() {
+        let (Some(&start), Some(&end)) =
+            (index.get(edge.start.as_str()), index.get(edge.end.as_str()))
+        else {
+            continue;
+        };
+        if start != end && !children[start].contains(&end) {
+            children[start].push(end);
+        }
+    }
+
+    let order = topological_order(&children);
+    let mut ranks = vec![0; count];
+    let mut parents: Vec<Vec<usize>> = vec![vec![]; count];
+    let position_in_order: Vec<usize> = {
+        let mut position = vec![0; count];
+        for (i, node) in order.iter().enumerate() {
+            position[*node] = i;
+        }
+        position
+    };
+    for &node in order.iter() {
+        for &child in children[node].iter() {
+            // edges against the order close a cycle
+            if position_in_order[child] > position_in_order[node] {
+                ranks[child] = ranks[child].max(ranks[node] + 1);
+                parents[child].push(node);
+            }
+        }
+    }
+
+    let rank_count = ranks.iter().max().map_or(0, |max| max + 1);
+    let mut layers: Vec<Vec<usize>> = vec![vec![]; rank_count];
+    for node in 0..count {
+        layers[ranks[node]].push(node);
+    }
+    // slot of every node within its layer, used to order the following layers
+    let mut slots = vec![0.; count];
+    for layer in layers.iter_mut() {
+        let barycenter = |node: usize| {
+            let parents = &parents[node];
+            if parents.is_empty() {
+                node as f32
+            } else {
+                parents.iter().map(|parent| slots[*parent]).sum::<f32>() / parents.len() as f32
+            }
+        };
+        let mut keyed: Vec<(f32, usize)> = layer
+            .iter()
+            .map(|node| (barycenter(*node), *node))
+            .collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        *layer = keyed.into_iter().map(|(_, node)| node).collect();
+        for (slot, node) in layer.iter().enumerate() {
+            slots[*node] = slot as f32;
+        }
+    }
+
+    let mut positions = vec![Vec2::ZERO; count];
+    for (rank, layer) in layers.iter().enumerate() {
+        let middle = (layer.len() as f32 - 1.) / 2.;
+        for (slot, node) in layer.iter().enumerate() {
+            let along = rank as f32 * RANK_SPACING;
+            let across = (slot as f32 - middle) * NODE_SPACING;
+            // y grows upwards on the canvas
+            positions[*node] = match graph.direction {
+                Direction::TopDown => Vec2::new(across, -along),
+                Direction::BottomUp => Vec2::new(across, along),
+                Direction::LeftRight => Vec2::new(along, -across),
+                Direction::RightLeft => Vec2::new(-along, -across),
+            } * node_size;
+        }
+    }
+    positions
+}
+
+/// Depth first post order, reversed. Nodes are visited in declaration order,
+/// so the first declared node of a cycle comes first.
+fn topological_order(children: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; children.len()];
+    let mut order = vec![];
+    for root in 0..children.len() {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next_child)) = stack.pop() {
+            if let Some(&child) = children[node].get(next_child) {
+                stack.push((node, next_child + 1));
+                if !visited[child] {
+                    visited[child] = true;
+                    stack.push((child, 0));
+                }
+            } else {
+                order.push(node);
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::arrow::components::ArrowType;
+
+    #[test]
+    fn test_layout() {
+        let mut graph = ParsedGraph::default();
+        graph.add_edge("a", "b", ArrowType::Arrow);
+        graph.add_edge("a", "c", ArrowType::Arrow);
+        graph.add_edge("b", "d", ArrowType::Arrow);
+        graph.add_edge("c", "d", ArrowType::Arrow);
+        graph.add_edge("d", "a", ArrowType::Arrow);
+        graph.node_index("lonely");
+        let size = Vec2::new(100., 50.);
+        assert_eq!(
+            layout(&graph, size),
+            vec![
+                Vec2::new(-75., 0.),
+                Vec2::new(-75., -100.),
+                Vec2::new(75., -100.),
+                Vec2::new(0., -200.),
+                Vec2::new(75., 0.),
+            ]
+        );
+        graph.direction = Direction::LeftRight;
+        assert_eq!(layout(&graph, size)[3], Vec2::new(400., 0.));
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+
+use crate::canvas::arrow::components::ArrowType;
+use crate::ui_plugin::NodeType;
+
+use super::{parse_color, Direction, Fill, ImportError, ParsedGraph};
+
+/// Opening and closing brackets of Mermaid node shapes, longest first.
+const SHAPES: [(&str, &str, NodeType); 12] = [
+    ("(((", ")))", NodeType::Circle),
+    ("((", "))", NodeType::Circle),
+    ("([", "])", NodeType::Rect),
+    ("[[", "]]", NodeType::Rect),
+    ("[(", ")]", NodeType::Rect),
+    ("[/", "/]", NodeType::Rect),
+    ("[\\", "\\]", NodeType::Rect),
+    ("{{", "}}", NodeType::Rect),
+    ("(", ")", NodeType::Rect),
+    ("[", "]", NodeType::Rect),
+    ("{", "}", NodeType::Rect),
+    (">", "]", NodeType::Paper),
+];
+
+/// Link ends that close a link with text in the middle, like `-- text -->`.
+const LINK_ENDS: [&str; 6] = ["-->", "---", "==>", "===", ".->", ".-"];
+
+/// Statements that don't describe nodes or links.
+const IGNORED: [&str; 8] = [
+    "subgraph",
+    "end",
+    "direction",
+    "linkStyle",
+    "click",
+    "accTitle",
+    "accDescr",
+    "title",
+];
+
+/// Read a Mermaid `flowchart` (or `graph`). Subgraphs are flattened and link labels
+/// are dropped, because velo arrows have no text.
+pub fn parse_mermaid(text: &str) -> Result<ParsedGraph, ImportError> {
+    let mut graph = ParsedGraph::default();
+    let mut class_fills: HashMap<String, Fill> = HashMap::new();
+    let mut node_classes: Vec<(String, String)> = vec![];
+    let mut node_styles: Vec<(String, Fill)> = vec![];
+    let mut has_header = false;
+    let mut in_front_matter = false;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| ImportError {
+            line: index + 1,
+            message,
+        };
+        if !has_header && line == "---" {
+            in_front_matter = !in_front_matter;
+            continue;
+        }
+        if in_front_matter || line.is_empty() || line.starts_with("%%") {
+            continue;
+        }
+        if !has_header {
+            let mut words = line.trim_end_matches(';').split_whitespace();
+            if !matches!(words.next(), Some("flowchart") | Some("graph")) {
+                return Err(error("expected a flowchart".to_string()));
+            }
+            graph.direction = match words.next() {
+                None | Some("TD") | Some("TB") => Direction::TopDown,
+                Some("BT") => Direction::BottomUp,
+                Some("LR") => Direction::LeftRight,
+                Some("RL") => Direction::RightLeft,
+                Some(other) => return Err(error(format!("unknown direction \"{}\"", other))),
+            };
+            has_header = true;
+            continue;
+        }
+        for statement in split_statements(line) {
+            let mut words = statement.split_whitespace();
+            match words.next() {
+                Some("classDef") => {
+                    let names = words.next().unwrap_or_default();
+                    if let Some(fill) = style_fill(&words.collect::<Vec<_>>().join(" ")) {
+                        for name in names.split(',') {
+                            class_fills.insert(name.to_string(), fill.clone());
+                        }
+                    }
+                }
+                Some("class") => {
+                    let (Some(keys), Some(class)) = (words.next(), words.next()) else {
+                        return Err(error("expected node ids and a class name".to_string()));
+                    };
+                    for key in keys.split(',') {
+                        node_classes.push((key.to_string(), class.to_string()));
+                    }
+                }
+                Some("style") => {
+                    let key = words.next().unwrap_or_default();
+                    if let Some(fill) = style_fill(&words.collect::<Vec<_>>().join(" ")) {
+                        node_styles.push((key.to_string(), fill));
+                    }
+                }
+                Some(keyword) if IGNORED.contains(&keyword) => {}
+                _ => {
+                    let classes = parse_chain(&mut graph, statement).map_err(error)?;
+                    node_classes.extend(classes);
+                }
+            }
+        }
+    }
+    if !has_header {
+        return Err(ImportError {
+            line: 1,
+            message: "expected a flowchart".to_string(),
+        });
+    }
+
+    // the theme color name of a class wins over its fill, so exports round trip
+    for (key, class) in node_classes {
+        let index = graph.node_index(&key);
+        let fill = match class_fills.get(&class) {
+            _ if super::NODE_COLORS.contains(&class.as_str()) => Some(Fill::Name(class)),
+            Some(fill) => Some(fill.clone()),
+            None => None,
+        };
+        if fill.is_some() {
+            graph.nodes[index].fill = fill;
+        }
+    }
+    for (key, fill) in node_styles {
+        let index = graph.node_index(&key);
+        graph.nodes[index].fill = Some(fill);
+    }
+    Ok(graph)
+}
+
+/// Statements on a line are separated by semicolons outside of quotes.
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                statements.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&line[start..]);
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// `fill:#f9f,stroke:#333` style lists.
+fn style_fill(styles: &str) -> Option<Fill> {
+    styles
+        .split(',')
+        .filter_map(|style| style.split_once(':'))
+        .find(|(name, _)| name.trim() == "fill")
+        .and_then(|(_, value)| parse_color(value))
+        .map(Fill::Color)
+}
+
+/// Nodes joined by links, like `a & b --> c[Text] -.-> d`.
+/// Returns the classes attached with `:::`.
+fn parse_chain(graph: &mut ParsedGraph, statement: &str) -> Result<Vec<(String, String)>, String> {
+    let mut cursor = Cursor(statement);
+    let mut classes = vec![];
+    let mut previous = parse_group(graph, &mut cursor, &mut classes)?;
+    loop {
+        cursor.skip_whitespace();
+        if cursor.0.is_empty() {
+            return Ok(classes);
+        }
+        let arrow_type = parse_link(&mut cursor)?;
+        let next = parse_group(graph, &mut cursor, &mut classes)?;
+        for start in previous.iter() {
+            for end in next.iter() {
+                graph.add_edge(start, end, arrow_type);
+            }
+        }
+        previous = next;
+    }
+}
+
+/// One or more nodes joined with `&`.
+fn parse_group(
+    graph: &mut ParsedGraph,
+    cursor: &mut Cursor,
+    classes: &mut Vec<(String, String)>,
+) -> Result<Vec<String>, String> {
+    let mut keys = vec![parse_node(graph, cursor, classes)?];
+    loop {
+        cursor.skip_whitespace();
+        if !cursor.eat("&") {
+            return Ok(keys);
+        }
+        cursor.skip_whitespace();
+        keys.push(parse_node(graph, cursor, classes)?);
+    }
+}
+
+fn parse_node(
+    graph: &mut ParsedGraph,
+    cursor: &mut Cursor,
+    classes: &mut Vec<(String, String)>,
+) -> Result<String, String> {
+    let key = cursor.take_while(|c| c.is_alphanumeric() || c == '_');
+    if key.is_empty() {
+        return Err(format!("expected a node id at \"{}\"", cursor.0));
+    }
+    let index = graph.node_index(key);
+    if let Some((open, close, node_type)) =
+        SHAPES.iter().find(|(open, ..)| cursor.0.starts_with(open))
+    {
+        cursor.eat(open);
+        let label = if cursor.eat("\"") {
+            let label = cursor.take_while(|c| c != '"');
+            if !cursor.eat("\"") {
+                return Err(format!("unclosed quote in node {}", key));
+            }
+            label
+        } else {
+            let end = cursor.0.find(close).unwrap_or(cursor.0.len());
+            let label = &cursor.0[..end];
+            cursor.0 = &cursor.0[end..];
+            label
+        };
+        if !cursor.eat(close) {
+            return Err(format!("expected \"{}\" to close node {}", close, key));
+        }
+        let node = &mut graph.nodes[index];
+        node.label = unescape(label.trim().trim_matches('`'));
+        node.node_type = node_type.clone();
+    }
+    if cursor.eat(":::") {
+        let class = cursor.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        classes.push((key.to_string(), class.to_string()));
+    }
+    Ok(key.to_string())
+}
+
+/// Links like `-->`, `<==>`, `-.-`, `-- text -->` or `-->|text|`.
+/// Thick links are parallel arrows, dotted links are drawn like solid ones.
+fn parse_link(cursor: &mut Cursor) -> Result<ArrowType, String> {
+    let is_link_char = |c: char| matches!(c, '<' | '-' | '=' | '.' | '>');
+    let mut link = cursor.take_while(is_link_char).to_string();
+    if matches!(link.as_str(), "--" | "==" | "-.") {
+        // text between the two halves of the link
+        let Some((end, close)) = LINK_ENDS
+            .iter()
+            .filter_map(|close| Some((cursor.0.find(close)?, close)))
+            .min()
+        else {
+            return Err(format!("unclosed link text at \"{}\"", cursor.0));
+        };
+        cursor.0 = &cursor.0[end..];
+        link += cursor.take_while(is_link_char);
+        debug_assert!(link.ends_with(close));
+    }
+    // circle and cross ends are shown as plain lines
+    if !link.ends_with('>') && (cursor.0.starts_with('o') || cursor.0.starts_with('x')) {
+        let after = &cursor.0[1..];
+        if after.is_empty() || after.starts_with(char::is_whitespace) {
+            cursor.0 = after;
+        }
+    }
+    if link.len() < 2 {
+        return Err(format!("expected a link at \"{}\"", cursor.0));
+    }
+    cursor.skip_whitespace();
+    if cursor.eat("|") {
+        cursor.take_while(|c| c != '|');
+        cursor.eat("|");
+    }
+    cursor.skip_whitespace();
+    let is_thick = link.contains('=');
+    let arrow_type = match (link.starts_with('<'), link.ends_with('>'), is_thick) {
+        (true, true, false) => ArrowType::DoubleArrow,
+        (true, true, true) => ArrowType::ParallelDoubleArrow,
+        (_, true, false) => ArrowType::Arrow,
+        (_, true, true) => ArrowType::ParallelArrow,
+        (_, false, false) => ArrowType::Line,
+        (_, false, true) => ArrowType::ParallelLine,
+    };
+    Ok(arrow_type)
+}
+
+/// Entity codes and line breaks in labels.
+fn unescape(label: &str) -> String {
+    let label = label
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n");
+    let mut text = String::new();
+    let mut rest = label.as_str();
+    while let Some(start) = rest.find('#') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .map(|end| &rest[1..end + 1])
+            .filter(|entity| entity.chars().all(char::is_alphanumeric));
+        let decoded = entity.and_then(|entity| match entity {
+            "quot" => Some('"'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "nbsp" => Some(' '),
+            code => code.parse::<u32>().ok().and_then(char::from_u32),
+        });
+        match (entity, decoded) {
+            (Some(entity), Some(decoded)) => {
+                text.push(decoded);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                text.push('#');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+struct Cursor<'a>(&'a str);
+
+impl<'a> Cursor<'a> {
+    fn eat(&mut self, prefix: &str) -> bool {
+        match self.0.strip_prefix(prefix) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let end = self.0.find(|c| !predicate(c)).unwrap_or(self.0.len());
+        let (taken, rest) = self.0.split_at(end);
+        self.0 = rest;
+        taken
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.0 = self.0.trim_start();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui_plugin::TextPos;
+    use bevy::prelude::Color;
+
+    #[test]
+    fn test_parse_mermaid() {
+        let graph = parse_mermaid(
+            "\
+%% design notes
+flowchart LR
+    start([Start]) --> check{\"Is it #quot;ok#quot;?\"}
+    check -- yes --> done((Done)):::node_bg
+    check -.->|no| note>note<br/>here] & retry[Retry]
+    retry <==> start; retry --- done
+    subgraph group
+        a ==> b
+    end
+    classDef warn fill:#ef4444,stroke:#333
+    class retry warn
+    style a fill:none
+",
+        )
+        .unwrap();
+        assert_eq!(graph.direction, Direction::LeftRight);
+        let keys: Vec<&str> = graph.nodes.iter().map(|node| node.key.as_str()).collect();
+        assert_eq!(keys, ["start", "check", "done", "note", "retry", "a", "b"]);
+        assert_eq!(graph.nodes[1].label, "Is it \"ok\"?");
+        assert_eq!(graph.nodes[2].node_type, NodeType::Circle);
+        assert_eq!(graph.nodes[2].fill, Some(Fill::Name("node_bg".to_string())));
+        assert_eq!(graph.nodes[3].node_type, NodeType::Paper);
+        assert_eq!(graph.nodes[3].label, "note\nhere");
+        assert_eq!(graph.nodes[3].text_pos, TextPos::Center);
+        assert_eq!(
+            graph.nodes[4].fill,
+            Some(Fill::Color(Color::hex("ef4444").unwrap()))
+        );
+        assert_eq!(graph.nodes[5].fill, Some(Fill::Color(Color::NONE)));
+        let edges: Vec<(&str, &str, ArrowType)> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.start.as_str(), edge.end.as_str(), edge.arrow_type))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("start", "check", ArrowType::Arrow),
+                ("check", "done", ArrowType::Arrow),
+                ("check", "note", ArrowType::Arrow),
+                ("check", "retry", ArrowType::Arrow),
+                ("retry", "start", ArrowType::ParallelDoubleArrow),
+                ("retry", "done", ArrowType::Line),
+                ("a", "b", ArrowType::ParallelArrow),
+            ]
+        );
+        assert_eq!(
+            parse_mermaid("flowchart TD\n  a --> \n"),
+            Err(ImportError {
+                line: 2,
+                message: "expected a node id at \"\"".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use bevy::prelude::{Color, Vec2, Vec3};
+use uuid::Uuid;
+
+use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos, ArrowMeta, ArrowType};
+use crate::document::TabSnapshot;
+use crate::export::{JsonCanvas, ThemeColors};
+use crate::themes::Theme;
+use crate::ui_plugin::{NodeType, TextPos};
+use crate::utils::ReflectableUuid;
+use crate::{JsonNode, JsonNodeText};
+
+pub mod dot;
+pub mod excalidraw;
+pub mod json_canvas;
+pub mod layout;
+pub mod mermaid;
+pub mod opml;
+
+pub use dot::*;
+pub use excalidraw::*;
+pub use json_canvas::*;
+pub use layout::*;
+pub use mermaid::*;
+pub use opml::*;
+
+/// Theme colors a node background can be set to on the canvas.
+const NODE_COLORS: [&str; 8] = [
+    "node_bg",
+    "paper_node_bg",
+    "color_change_1",
+    "color_change_2",
+    "color_change_3",
+    "color_change_4",
+    "color_change_5",
+    // keep last, it is only picked for fully transparent colors
+    "color_none",
+];
+
+/// Theme colors a drawing can be drawn with.
+const DRAWING_COLORS: [&str; 7] = [
+    "color_change_1",
+    "color_change_2",
+    "color_change_3",
+    "color_change_4",
+    "color_change_5",
+    "drawing_pencil_btn",
+    "drawing_two_points_btn",
+];
+
+#[derive(Debug, PartialEq)]
+pub struct ImportError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Diagram read from text, before it is laid out.
+#[derive(Default, Debug, PartialEq)]
+pub struct ParsedGraph {
+    pub direction: Direction,
+    pub nodes: Vec<ParsedNode>,
+    pub edges: Vec<ParsedEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedNode {
+    /// Identifier of the node in the source text.
+    pub key: String,
+    pub label: String,
+    pub node_type: NodeType,
+    pub text_pos: TextPos,
+    pub fill: Option<Fill>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fill {
+    /// Name of a theme color, as written by the exports.
+    Name(String),
+    /// Any other color, which gets the closest theme color.
+    Color(Color),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedEdge {
+    pub start: String,
+    pub end: String,
+    pub arrow_type: ArrowType,
+}
+
+impl ParsedGraph {
+    /// Index of the node with `key`, which is added with its key as label
+    /// if it wasn't seen before, like diagram languages do.
+    pub fn node_index(&mut self, key: &str) -> usize {
+        if let Some(index) = self.nodes.iter().position(|node| node.key == key) {
+            return index;
+        }
+        self.nodes.push(ParsedNode {
+            key: key.to_string(),
+            label: key.to_string(),
+            node_type: NodeType::Rect,
+            text_pos: TextPos::Center,
+            fill: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn add_edge(&mut self, start: &str, end: &str, arrow_type: ArrowType) {
+        self.node_index(start);
+        self.node_index(end);
+        self.edges.push(ParsedEdge {
+            start: start.to_string(),
+            end: end.to_string(),
+            arrow_type,
+        });
+    }
+}
+
+enum DiagramFormat {
+    Mermaid,
+    Dot,
+    JsonCanvas,
+    Excalidraw,
+    Opml,
+}
+
+/// Parse `text` as a Mermaid flowchart, a DOT graph, a JSON Canvas board, an Excalidraw
+/// sketch or an OPML outline and turn it into a tab. Returns `None` when the text is none of these, so callers can try other formats.
+pub fn diagram_to_snapshot(text: &str, theme: &Theme) -> Option<Result<TabSnapshot, ImportError>> {
+    let graph = match detect(text)? {
+        DiagramFormat::Mermaid => parse_mermaid(text),
+        DiagramFormat::Dot => parse_dot(text),
+        DiagramFormat::Opml => parse_opml(text),
+        DiagramFormat::JsonCanvas => {
+            return Some(
+                serde_json::from_str::<JsonCanvas>(text)
+                    .map(|canvas| json_canvas_to_snapshot(&canvas, theme))
+                    .map_err(|e| ImportError {
+                        line: e.line(),
+                        message: e.to_string(),
+                    }),
+            );
+        }
+        DiagramFormat::Excalidraw => {
+            return Some(
+                serde_json::from_str::<ExcalidrawFile>(text)
+                    .map(|file| excalidraw_to_snapshot(&file, theme))
+                    .map_err(|e| ImportError {
+                        line: e.line(),
+                        message: e.to_string(),
+                    }),
+            );
+        }
+    };
+    Some(graph.map(|graph| graph_to_snapshot(&graph, theme)))
+}
+
+/// Look at the first keyword, skipping comments and Mermaid front matter.
+/// JSON objects are Excalidraw sketches when they say so, and JSON Canvas boards
+/// when they have a `nodes` or `edges` list, and the only XML velo reads is OPML.
+fn detect(text: &str) -> Option<DiagramFormat> {
+    if text.trim_start().starts_with('{') {
+        return match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(serde_json::Value::Object(object))
+                if object
+                    .get("type")
+                    .and_then(|value| value.as_str())
+                    .is_some_and(|value| value.starts_with("excalidraw")) =>
+            {
+                Some(DiagramFormat::Excalidraw)
+            }
+            Ok(serde_json::Value::Object(object))
+                if ["nodes", "edges"]
+                    .iter()
+                    .any(|key| object.get(*key).is_some_and(|value| value.is_array())) =>
+            {
+                Some(DiagramFormat::JsonCanvas)
+            }
+            _ => None,
+        };
+    }
+    if text.trim_start().starts_with('<') {
+        return text.contains("<opml").then_some(DiagramFormat::Opml);
+    }
+    let mut in_front_matter = false;
+    for line in text.lines().map(str::trim) {
+        if line == "---" {
+            in_front_matter = !in_front_matter;
+            continue;
+        }
+        if in_front_matter
+            || line.is_empty()
+            || line.starts_with("%%")
+            || line.starts_with("//")
+            || line.starts_with('#')
+        {
+            continue;
+        }
+        let keyword = line
+            .split(|c: char| c.is_whitespace() || c == '{')
+            .next()
+            .unwrap_or_default();
+        return match keyword {
+            "flowchart" => Some(DiagramFormat::Mermaid),
+            "digraph" | "strict" => Some(DiagramFormat::Dot),
+            // both languages can start with `graph`, only Mermaid follows it with a direction
+            "graph" => match line.split_whitespace().nth(1) {
+                _ if line.contains('{') => Some(DiagramFormat::Dot),
+                None | Some("TD" | "TB" | "BT" | "LR" | "RL") => Some(DiagramFormat::Mermaid),
+                _ => Some(DiagramFormat::Dot),
+            },
+            _ => None,
+        };
+    }
+    None
+}
+
+/// Place the parsed nodes and connect the arrows to the sides that face each other.
+pub fn graph_to_snapshot(graph: &ParsedGraph, theme: &Theme) -> TabSnapshot {
+    let colors = ThemeColors::new(theme);
+    let positions = layout(graph, Vec2::new(theme.node_width, theme.node_height));
+    let nodes: Vec<JsonNode<String>> = graph
+        .nodes
+        .iter()
+        .zip(positions.iter())
+        .enumerate()
+        .map(|(index, (node, position))| JsonNode {
+            id: Uuid::new_v4(),
+            node_type: node.node_type.clone(),
+            x: position.x,
+            y: position.y,
+            z: 1. + index as f32 * 0.01,
+            width: theme.node_width,
+            height: theme.node_height,
+            text: JsonNodeText {
+                text: node.label.clone(),
+                pos: node.text_pos.clone(),
+            },
+            bg_color: color_name(&colors, node),
+            visible: true,
+            tags: vec![],
+        })
+        .collect();
+    let index_of = |key: &str| graph.nodes.iter().position(|node| node.key == key);
+    let arrows = graph
+        .edges
+        .iter()
+        .filter_map(|edge| {
+            let (start, end) = (index_of(&edge.start)?, index_of(&edge.end)?);
+            if start == end {
+                return None;
+            }
+            let (start_pos, end_pos) = facing_sides(positions[start], positions[end]);
+            Some(ArrowMeta {
+                visible: true,
+                arrow_type: edge.arrow_type,
+                start: ArrowConnect {
+                    id: ReflectableUuid(nodes[start].id),
+                    pos: start_pos,
+                },
+                end: ArrowConnect {
+                    id: ReflectableUuid(nodes[end].id),
+                    pos: end_pos,
+                },
+            })
+        })
+        .collect();
+    TabSnapshot {
+        nodes,
+        arrows,
+        ..Default::default()
+    }
+}
+
+/// Sides of two nodes that an arrow between them should connect.
+pub fn facing_sides(start: Vec2, end: Vec2) -> (ArrowConnectPos, ArrowConnectPos) {
+    let delta = end - start;
+    if delta.y.abs() >= delta.x.abs() {
+        if delta.y < 0. {
+            (ArrowConnectPos::Bottom, ArrowConnectPos::Top)
+        } else {
+            (ArrowConnectPos::Top, ArrowConnectPos::Bottom)
+        }
+    } else if delta.x > 0. {
+        (ArrowConnectPos::Right, ArrowConnectPos::Left)
+    } else {
+        (ArrowConnectPos::Left, ArrowConnectPos::Right)
+    }
+}
+
+fn color_name(colors: &ThemeColors, node: &ParsedNode) -> String {
+    let default = match node.node_type {
+        NodeType::Paper => "paper_node_bg",
+        _ => "node_bg",
+    };
+    let name = match &node.fill {
+        Some(Fill::Name(name)) if NODE_COLORS.contains(&name.as_str()) => name.as_str(),
+        Some(Fill::Color(color)) => nearest_node_color(colors, *color).unwrap_or(default),
+        _ => default,
+    };
+    name.to_string()
+}
+
+/// Theme color a node background closest to `color` gets.
+pub fn nearest_node_color(colors: &ThemeColors, color: Color) -> Option<&'static str> {
+    if color.a() == 0. {
+        return Some("color_none");
+    }
+    nearest_color(colors, &NODE_COLORS[..NODE_COLORS.len() - 1], color)
+}
+
+/// Theme color out of `names` that is closest to `color`.
+pub fn nearest_color(
+    colors: &ThemeColors,
+    names: &[&'static str],
+    color: Color,
+) -> Option<&'static str> {
+    names
+        .iter()
+        .filter_map(|name| Some((*name, colors.get(name)?)))
+        .min_by(|a, b| distance(a.1, color).total_cmp(&distance(b.1, color)))
+        .map(|(name, _)| name)
+}
+
+fn distance(a: Color, b: Color) -> f32 {
+    Vec3::from_slice(&a.as_rgba_f32()[..3])
+        .distance_squared(Vec3::from_slice(&b.as_rgba_f32()[..3]))
+}
+
+/// CSS and Graphviz colors: hex notation and the handful of names diagrams usually use.
+pub fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim().to_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        return Color::hex(hex).ok();
+    }
+    let color = match value.as_str() {
+        "none" | "transparent" => Color::NONE,
+        "white" => Color::WHITE,
+        "black" => Color::BLACK,
+        "red" => Color::RED,
+        "green" => Color::GREEN,
+        "blue" => Color::BLUE,
+        "yellow" => Color::YELLOW,
+        "orange" => Color::ORANGE,
+        "pink" => Color::PINK,
+        "gray" | "grey" => Color::GRAY,
+        "lightgray" | "lightgrey" => Color::rgb_u8(211, 211, 211),
+        "lightblue" => Color::rgb_u8(173, 216, 230),
+        "lightyellow" => Color::rgb_u8(255, 255, 224),
+        _ => return None,
+    };
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::{active_snapshot, snapshot_to_dot, snapshot_to_mermaid};
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_export_round_trip() {
+        let theme = velo_light();
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let original = active_snapshot(&doc).unwrap();
+        let summary = |snapshot: &TabSnapshot| {
+            let mut nodes: Vec<_> = snapshot
+                .nodes
+                .iter()
+                .filter(|node| node.visible)
+                .map(|node| {
+                    let text = node.text.text.clone();
+                    (text, node.node_type.clone(), node.bg_color.clone())
+                })
+                .collect();
+            nodes.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut arrows: Vec<_> = snapshot
+                .arrows
+                .iter()
+                .filter(|arrow| {
+                    snapshot
+                        .nodes
+                        .iter()
+                        .filter(|node| node.visible)
+                        .any(|node| node.id == arrow.end.id.0)
+                })
+                .map(|arrow| format!("{:?}", arrow.arrow_type))
+                .collect();
+            arrows.sort();
+            (nodes, arrows)
+        };
+        for text in [
+            snapshot_to_mermaid(original, &theme),
+            snapshot_to_dot(original, &theme),
+        ] {
+            let imported = diagram_to_snapshot(&text, &theme).unwrap().unwrap();
+            assert_eq!(summary(&imported), summary(original), "{}", text);
+        }
+        assert!(diagram_to_snapshot("{\"tabs\": []}", &theme).is_none());
+        // documents from older releases or other tools get the document error instead
+        assert!(diagram_to_snapshot("{\"name\": \"Board\"}", &theme).is_none());
+        assert!(diagram_to_snapshot("{\"nodes\": {}}", &theme).is_none());
+        assert!(diagram_to_snapshot("{\"edges\": []}", &theme).is_some());
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::canvas::arrow::components::ArrowType;
+use crate::export::SEE_ABOVE;
+
+use super::{Direction, ImportError, ParsedGraph};
+
+/// Read the outline elements of an OPML file into a tree that grows to the right,
+/// with parallel arrows from every outline to the ones nested in it. The `_note`
+/// of an outline goes below its text. Outlines that only refer to one that is
+/// already in the tree, like the ones velo writes for nodes with several parents,
+/// point at that node instead of making a copy of it. They are found by `_velo_id`,
+/// or by their text in files written by other outliners.
+pub fn parse_opml(text: &str) -> Result<ParsedGraph, ImportError> {
+    let mut graph = ParsedGraph {
+        direction: Direction::LeftRight,
+        ..Default::default()
+    };
+    let mut reader = Reader::from_str(text);
+    // keys of the open outline elements
+    let mut parents: Vec<String> = vec![];
+    // keys of the outlines by `_velo_id`
+    let mut ids: HashMap<String, String> = HashMap::new();
+    loop {
+        let event = reader.read_event();
+        let error = |e: quick_xml::Error| ImportError {
+            line: text[..reader.buffer_position().min(text.len())]
+                .matches('\n')
+                .count()
+                + 1,
+            message: e.to_string(),
+        };
+        match event.map_err(error)? {
+            Event::Start(element) if element.name().as_ref() == b"outline" => {
+                let key =
+                    add_outline(&mut graph, &mut ids, parents.last(), &element).map_err(error)?;
+                parents.push(key);
+            }
+            Event::Empty(element) if element.name().as_ref() == b"outline" => {
+                add_outline(&mut graph, &mut ids, parents.last(), &element).map_err(error)?;
+            }
+            Event::End(element) if element.name().as_ref() == b"outline" => {
+                parents.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(graph)
+}
+
+/// Add the node of an outline element under `parent` and return its key.
+fn add_outline(
+    graph: &mut ParsedGraph,
+    ids: &mut HashMap<String, String>,
+    parent: Option<&String>,
+    element: &BytesStart,
+) -> Result<String, quick_xml::Error> {
+    let mut text = String::new();
+    let mut note = String::new();
+    let mut id = None;
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        match attribute.key.as_ref() {
+            b"text" => text = attribute.unescape_value()?.into_owned(),
+            b"_note" => note = attribute.unescape_value()?.into_owned(),
+            b"_velo_id" => id = Some(attribute.unescape_value()?.into_owned()),
+            _ => {}
+        }
+    }
+    let reference = match &id {
+        Some(id) => ids.get(id).cloned(),
+        None => {
+            text.strip_suffix(SEE_ABOVE)
+                .map(str::trim_end)
+                .filter(|_| note.is_empty())
+                .and_then(|first_line| {
+                    graph.nodes.iter().find(|node| {
+                        node.label.lines().next().unwrap_or_default().trim() == first_line
+                    })
+                })
+                .map(|node| node.key.clone())
+        }
+    };
+    let key = match reference {
+        Some(key) => key,
+        None => {
+            let key = format!("o{}", graph.nodes.len());
+            if let Some(id) = id {
+                ids.insert(id, key.clone());
+            }
+            let index = graph.node_index(&key);
+            graph.nodes[index].label = match note.trim_end() {
+                "" => text,
+                note => format!("{}\n{}", text, note),
+            };
+            key
+        }
+    };
+    if let Some(parent) = parent {
+        graph.add_edge(parent, &key, ArrowType::ParallelArrow);
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::doc_from_json;
+    use crate::export::{active_snapshot, snapshot_to_opml};
+    use crate::import::graph_to_snapshot;
+    use crate::themes::velo_light;
+
+    #[test]
+    fn test_opml_round_trip() {
+        let graph = parse_opml(
+            r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <head><title>Plan</title></head>
+  <body>
+    <outline text="Launch" _note="Q3 &amp; Q4">
+      <outline text="Website"/>
+      <outline text="Docs"><outline text="API"/></outline>
+    </outline>
+    <outline text="Later"/>
+  </body>
+</opml>"#,
+        )
+        .unwrap();
+        let labels: Vec<_> = graph.nodes.iter().map(|node| node.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            ["Launch\nQ3 & Q4", "Website", "Docs", "API", "Later"]
+        );
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.start.as_str(), edge.end.as_str(), edge.arrow_type))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("o0", "o1", ArrowType::ParallelArrow),
+                ("o0", "o2", ArrowType::ParallelArrow),
+                ("o2", "o3", ArrowType::ParallelArrow),
+            ]
+        );
+
+        let error = parse_opml("<opml>\n<body>\n<outline text=\"a\">\n</body>").unwrap_err();
+        assert_eq!(error.line, 4);
+
+        // velo outlines come back the way they were written
+        let theme = velo_light();
+        let doc = doc_from_json(include_str!("../export/fixtures/board.json")).unwrap();
+        let graph = parse_opml(&snapshot_to_opml(active_snapshot(&doc).unwrap(), "Board")).unwrap();
+        let snapshot = graph_to_snapshot(&graph, &theme);
+        let opml = snapshot_to_opml(&snapshot, "Board");
+        assert_eq!(parse_opml(&opml).unwrap(), graph);
+    }
+
+    #[test]
+    fn test_opml_references() {
+        let outline = |ids: bool| {
+            let id = |id: &str| match ids {
+                true => format!(r#" _velo_id="{}""#, id),
+                false => String::new(),
+            };
+            parse_opml(&format!(
+                r#"<opml><body>
+<outline text="Task"{}/>
+<outline text="Task"{}/>
+<outline text="Review"><outline text="Task (see above)"{}/></outline>
+</body></opml>"#,
+                id("a"),
+                id("b"),
+                id("b")
+            ))
+            .unwrap()
+        };
+        // nodes with the same first line are told apart by id
+        let graph = outline(true);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges[0].start, "o2");
+        assert_eq!(graph.edges[0].end, "o1");
+        // other outliners only have the text to go by
+        let graph = outline(false);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges[0].end, "o0");
+    }
+}
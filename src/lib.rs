@@ -1,5 +1,10 @@
 mod canvas;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
 mod components;
+mod document;
+mod export;
+mod import;
 mod resources;
 mod systems;
 mod themes;
@@ -15,6 +20,8 @@ use bevy_pancam::PanCamPlugin;
 use bevy_pkv::PkvStore;
 use canvas::CanvasPlugin;
 use resources::FontSystemState;
+#[cfg(not(target_arch = "wasm32"))]
+pub use resources::OpenFilesRequest;
 use systems::*;
 use ui_plugin::*;
 
@@ -38,6 +45,8 @@ impl Plugin for VeloPlugin {
                             prevent_default_event_handling: false,
                             ..default()
                         }),
+                        // closing with unsaved changes asks for confirmation first
+                        close_when_requested: false,
                         ..default()
                     })
                     .build()
@@ -6,5 +6,15 @@ fn main() {
     console_error_panic_hook::set_once();
     #[cfg(not(target_arch = "wasm32"))]
     std::env::set_var("RUST_LOG", "warn,velo=info,tantivy=warn");
-    App::new().add_plugins(VeloPlugin).run();
+    let mut app = App::new();
+    app.add_plugins(VeloPlugin);
+    // documents to open, as in `velo path/to/doc.json`
+    #[cfg(not(target_arch = "wasm32"))]
+    app.insert_resource(velo::OpenFilesRequest {
+        paths: std::env::args_os()
+            .skip(1)
+            .map(std::path::PathBuf::from)
+            .collect(),
+    });
+    app.run();
 }
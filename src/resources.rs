@@ -1,11 +1,17 @@
 use crate::components::Doc;
 #[cfg(not(target_arch = "wasm32"))]
+use crate::document::FolderSync;
+use crate::document::{
+    CanvasChange, CommandHistory, Conflict, JournalEntry, Side, TabSnapshot, VersionDiff,
+};
+#[cfg(not(target_arch = "wasm32"))]
 use crate::ui_plugin::SearchIndexState;
 use crate::utils::ReflectableUuid;
 use bevy::prelude::*;
 use bevy_cosmic_edit::CosmicFont;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use uuid::Uuid;
 
 #[derive(Resource, Default)]
 pub struct AppState {
@@ -15,6 +21,25 @@ pub struct AppState {
     #[cfg(not(target_arch = "wasm32"))]
     pub search_index: Option<SearchIndexState>,
     pub doc_list_ui: HashSet<ReflectableUuid>,
+    /// Documents changed in memory since they were last written to the store.
+    pub unsaved_docs: HashSet<ReflectableUuid>,
+    /// Tabs whose canvas differs from their last checkpoint.
+    pub unsaved_tabs: HashSet<ReflectableUuid>,
+    /// Only documents and nodes with this tag are shown at full strength.
+    pub tag_filter: Option<String>,
+}
+
+impl AppState {
+    pub fn has_unsaved_changes(&self, doc_id: ReflectableUuid) -> bool {
+        if self.unsaved_docs.contains(&doc_id) {
+            return true;
+        }
+        self.docs.get(&doc_id).is_some_and(|doc| {
+            doc.tabs
+                .iter()
+                .any(|tab| self.unsaved_tabs.contains(&tab.id))
+        })
+    }
 }
 
 #[derive(Resource, Debug)]
@@ -23,6 +48,13 @@ pub struct SaveDocRequest {
     pub path: Option<PathBuf>, // Save current document to file
 }
 
+/// Copy the selection, or the whole tab when nothing is selected, to the clipboard as an image.
+#[derive(Resource, Debug)]
+pub struct CopyImageRequest {
+    /// Pixels per canvas unit.
+    pub scale: f32,
+}
+
 #[derive(Resource, Debug)]
 pub struct SaveTabRequest {
     pub doc_id: ReflectableUuid,
@@ -38,7 +70,148 @@ pub struct LoadDocRequest {
 pub struct LoadTabRequest {
     pub doc_id: ReflectableUuid,
     pub tab_id: ReflectableUuid,
-    pub drop_last_checkpoint: bool, // Useful for undo functionality
+    /// Preview this named version of the tab instead of loading its last checkpoint.
+    pub version: Option<ReflectableUuid>,
+}
+
+/// Keep the canvas of the active tab as a named version.
+#[derive(Resource, Debug)]
+pub struct SaveVersionRequest {
+    pub name: String,
+}
+
+/// A version of a tab shown on the canvas for review. Nothing done on the canvas is saved
+/// while it is, and loading any tab ends the preview.
+#[derive(Resource, Debug)]
+pub struct VersionPreview {
+    pub tab_id: ReflectableUuid,
+    pub version_id: ReflectableUuid,
+    pub name: String,
+    pub diff: VersionDiff,
+}
+
+/// Merge an imported copy of a document that is already in the list into it.
+#[derive(Resource, Debug)]
+pub struct MergeDocRequest {
+    pub doc: Doc,
+}
+
+/// Conflicts left by the last merge, each resolved to the local side until a side is
+/// picked for it.
+#[derive(Resource, Debug)]
+pub struct MergeConflicts {
+    pub doc_id: ReflectableUuid,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Apply one side of a merge conflict.
+#[derive(Resource, Debug)]
+pub struct ResolveConflictRequest {
+    pub index: usize,
+    pub side: Side,
+}
+
+/// Folder set with `sync_folder` in the config, polled for document files.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource, Debug)]
+pub struct FolderSyncState {
+    pub sync: FolderSync,
+    pub timer: Timer,
+    /// Whether the last scan failed to read the folder.
+    pub failing: bool,
+}
+
+/// Files to open, given on the command line or dropped onto the window. Documents are
+/// sent through `CommChannels` one at a time, like imports from a file.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource, Debug, Default)]
+pub struct OpenFilesRequest {
+    pub paths: std::collections::VecDeque<PathBuf>,
+}
+
+/// Undo or redo the last canvas edit of the active tab.
+#[derive(Resource, Debug, PartialEq, Eq)]
+pub enum UndoRequest {
+    Undo,
+    Redo,
+}
+
+/// Canvas edits that can be undone, per tab.
+#[derive(Resource, Default)]
+pub struct CanvasHistory {
+    pub tabs: HashMap<ReflectableUuid, CommandHistory>,
+    /// Most edits kept per tab, unlimited when `None`.
+    pub limit: Option<usize>,
+    /// Active tab and its canvas as of the last recorded edit.
+    pub canvas: Option<(ReflectableUuid, TabSnapshot)>,
+    /// Node images, so that undoing the deletion of a node brings its image back.
+    pub images: HashMap<ReflectableUuid, Handle<Image>>,
+    /// Entities touched by each edit not recorded yet.
+    pub pending: Vec<HashSet<Uuid>>,
+}
+
+impl CanvasHistory {
+    /// Drop the edits of a tab that was deleted.
+    pub fn remove_tab(&mut self, tab_id: &ReflectableUuid) {
+        self.tabs.remove(tab_id);
+        if matches!(&self.canvas, Some((canvas_tab, _)) if canvas_tab == tab_id) {
+            self.canvas = None;
+        }
+        self.prune_images();
+    }
+
+    /// Keep only the images of nodes on the canvas or in edits that can still be undone
+    /// or redone.
+    pub fn prune_images(&mut self) {
+        let mut used: HashSet<Uuid> = HashSet::new();
+        if let Some((_, canvas)) = &self.canvas {
+            used.extend(canvas.nodes.iter().map(|node| node.id));
+        }
+        for command in self.tabs.values().flat_map(CommandHistory::commands) {
+            for change in command.changes.iter() {
+                if let CanvasChange::Node { before, after } = change {
+                    used.extend(before.iter().chain(after.iter()).map(|node| node.id));
+                }
+            }
+        }
+        self.images.retain(|id, _| used.contains(&id.0));
+    }
+}
+
+/// Replace the tags of a node on the canvas.
+#[derive(Resource, Debug)]
+pub struct SetNodeTagsRequest {
+    pub node_id: ReflectableUuid,
+    pub tags: Vec<String>,
+}
+
+/// Save everything that is unsaved and close the app.
+#[derive(Resource, Debug)]
+pub struct ExitRequest;
+
+/// Write-ahead journal of canvas edits that haven't reached the store yet.
+#[derive(Resource, Debug)]
+pub struct Journal {
+    pub path: PathBuf,
+    /// Whether entries were appended since the journal was last cleared.
+    pub pending: bool,
+    /// Hashes of the images already written to the journal, each image is only written once.
+    pub images: HashSet<String>,
+    /// Entries appended since the journal was last cleared or compacted.
+    pub entries: usize,
+}
+
+/// Journal left behind by a previous session that didn't shut down cleanly.
+#[derive(Resource, Debug)]
+pub struct PendingJournal {
+    pub path: PathBuf,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// Answer to the restore prompt for a `PendingJournal`.
+#[derive(Resource, Debug)]
+pub struct JournalReplayRequest {
+    pub replay: bool,
 }
 
 #[derive(Resource, Default)]
@@ -49,6 +49,9 @@ pub struct Theme {
     pub node_bg: Color,
     pub node_border: Color,
     pub node_found_color: Color,
+    pub version_added: Color,
+    pub version_changed: Color,
+    pub version_removed: Color,
     pub node_height: f32,
     pub node_manipulation_bg: Color,
     pub node_manipulation: Color,
@@ -114,6 +117,9 @@ pub fn velo_light() -> Theme {
         node_bg: Color::WHITE,
         node_border: Color::BLACK.with_a(0.8),
         node_found_color: Color::TEAL,
+        version_added: Color::rgb(67. / 255., 160. / 255., 71. / 255.),
+        version_changed: Color::rgb(251. / 255., 140. / 255., 0.),
+        version_removed: Color::rgb(229. / 255., 57. / 255., 53. / 255.),
         node_height: 144.,
         node_manipulation_bg: Color::rgb(207.0 / 255.0, 216.0 / 255.0, 220.0 / 255.0),
         node_manipulation: Color::BLACK,
@@ -182,6 +188,9 @@ pub fn velo_dark() -> Theme {
         node_bg: Color::DARK_GRAY,
         node_border: Color::rgb(0.3, 0.3, 0.3),
         node_found_color: Color::TEAL,
+        version_added: Color::rgb(67. / 255., 160. / 255., 71. / 255.),
+        version_changed: Color::rgb(251. / 255., 140. / 255., 0.),
+        version_removed: Color::rgb(229. / 255., 57. / 255., 53. / 255.),
         node_height: 144.,
         node_manipulation_bg: Color::rgb(0.2, 0.2, 0.2),
         node_manipulation: Color::rgb(240. / 255.0, 240. / 255.0, 240. / 255.0),
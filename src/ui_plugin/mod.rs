@@ -3,10 +3,13 @@ use bevy::prelude::*;
 
 use serde::{Deserialize, Serialize};
 
-use crate::resources::AppState;
+use crate::resources::{AppState, CanvasHistory, JournalReplayRequest, PendingJournal};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resources::{FolderSyncState, OpenFilesRequest};
 
 use crate::canvas::arrow::components::{ArrowConnect, ArrowType};
 use crate::canvas::arrow::events::{CreateArrow, RedrawArrow};
+use crate::document::Issue;
 use crate::utils::ReflectableUuid;
 use std::path::PathBuf;
 use uuid::Uuid;
@@ -84,6 +87,51 @@ use drawing::*;
 mod active_editor_changed;
 use active_editor_changed::*;
 
+#[path = "systems/autosave.rs"]
+mod autosave;
+use autosave::*;
+
+#[path = "systems/journal.rs"]
+mod journal;
+use journal::*;
+
+#[path = "systems/history.rs"]
+mod history;
+use history::*;
+
+#[path = "systems/versions.rs"]
+mod versions;
+use versions::*;
+#[path = "systems/merge.rs"]
+mod merge;
+use merge::*;
+
+#[path = "systems/toasts.rs"]
+mod toasts;
+use toasts::*;
+
+#[path = "systems/tags.rs"]
+mod tags;
+use tags::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/copy_image.rs"]
+mod copy_image;
+#[cfg(not(target_arch = "wasm32"))]
+use copy_image::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/folder_sync.rs"]
+mod folder_sync;
+#[cfg(not(target_arch = "wasm32"))]
+use folder_sync::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "systems/open_files.rs"]
+mod open_files;
+#[cfg(not(target_arch = "wasm32"))]
+use open_files::*;
+
 pub struct UiPlugin;
 
 #[derive(Event, Default)]
@@ -92,6 +140,12 @@ pub struct AddRect<T> {
     pub image: Option<Handle<Image>>,
 }
 
+/// An edit of the canvas that can be undone, with the nodes and drawings it touched.
+#[derive(Event)]
+pub struct CanvasEdit {
+    pub ids: Vec<ReflectableUuid>,
+}
+
 #[derive(Event)]
 pub struct SaveStore {
     pub doc_id: ReflectableUuid,
@@ -123,6 +177,63 @@ pub struct CommChannels {
     pub rx: Receiver<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationKind {
+    Info,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub message: String,
+}
+
+/// Messages shown to the user as toasts.
+/// The channel can be cloned into async tasks such as HTTP callbacks.
+#[derive(Resource, Clone)]
+pub struct Notifications {
+    pub tx: Sender<Notification>,
+    pub rx: Receiver<Notification>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        let (tx, rx) = async_channel::unbounded();
+        Self { tx, rx }
+    }
+}
+
+impl Notifications {
+    pub fn info(&self, message: impl Into<String>) {
+        self.send(NotificationKind::Info, message.into());
+    }
+
+    /// Report every problem fixed in a document, one per line.
+    pub fn repaired(&self, name: &str, issues: &[Issue]) {
+        if issues.is_empty() {
+            return;
+        }
+        let mut message = format!("Repaired {} problems in \"{}\":", issues.len(), name);
+        for issue in issues.iter() {
+            warn!("Repaired {}: {}", name, issue);
+            message.push_str(&format!("\n{}", issue));
+        }
+        self.info(message);
+    }
+
+    pub fn error(&self, message: impl Into<String>) {
+        let message = message.into();
+        error!("{}", message);
+        self.send(NotificationKind::Error, message);
+    }
+
+    fn send(&self, kind: NotificationKind, message: String) {
+        // the receiver lives as long as the app, so this only fails during shutdown
+        let _ = self.tx.try_send(Notification { kind, message });
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Reflect, Default, Debug)]
 pub enum NodeType {
     #[default]
@@ -131,20 +242,20 @@ pub enum NodeType {
     Circle,
 }
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub enum TextPos {
     #[default]
     Center,
     TopLeft,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct JsonNodeText {
     pub text: String,
     pub pos: TextPos,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct JsonNode<T> {
     pub id: Uuid,
     pub node_type: NodeType,
@@ -156,9 +267,11 @@ pub struct JsonNode<T> {
     pub text: JsonNodeText,
     pub bg_color: T,
     pub visible: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DrawingJsonNode<T> {
     pub x: f32,
     pub y: f32,
@@ -195,18 +308,21 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<UiState>();
         app.init_resource::<AppState>();
+        app.init_resource::<Notifications>();
+        app.init_resource::<CanvasHistory>();
 
         app.add_event::<AddRect<(String, Color)>>();
         app.add_event::<CreateArrow>();
         app.add_event::<RedrawArrow>();
         app.add_event::<SaveStore>();
+        app.add_event::<CanvasEdit>();
         app.add_event::<UpdateDeleteDocBtn>();
         app.add_event::<NodeInteraction>();
 
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems(
             Startup,
-            (read_native_config, init_search_index).before(init_layout),
+            (read_native_config, init_search_index, init_journal).before(init_layout),
         );
         #[cfg(target_arch = "wasm32")]
         app.add_systems(Startup, load_from_url.before(init_layout));
@@ -221,10 +337,10 @@ impl Plugin for UiPlugin {
                 resize_entity_start,
                 resize_entity_run,
                 resize_entity_end,
-                cancel_modal,
-                confirm_modal,
-            ),
+            )
+                .distributive_run_if(not_previewing),
         );
+        app.add_systems(Update, (cancel_modal, confirm_modal));
 
         app.add_systems(
             Update,
@@ -251,15 +367,16 @@ impl Plugin for UiPlugin {
             Update,
             (load_tab, remove_load_tab_request)
                 .chain()
-                .distributive_run_if(should_load_tab),
+                .distributive_run_if(should_load_tab)
+                .after(save_tab),
         );
 
         app.add_systems(
             Update,
             (
-                change_color_pallete,
+                change_color_pallete.run_if(not_previewing),
                 change_arrow_type,
-                change_text_pos,
+                change_text_pos.run_if(not_previewing),
                 add_tab_handler,
                 delete_tab_handler,
                 rename_tab_handler,
@@ -269,7 +386,9 @@ impl Plugin for UiPlugin {
                 rename_doc_handler,
                 delete_doc_handler,
                 save_doc_handler,
-                keyboard_input_system.before(bevy_cosmic_edit::cosmic_edit_bevy_events),
+                keyboard_input_system
+                    .before(bevy_cosmic_edit::cosmic_edit_bevy_events)
+                    .run_if(not_previewing),
             ),
         );
         app.add_systems(
@@ -279,6 +398,25 @@ impl Plugin for UiPlugin {
 
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems(Update, (search_box_click, search_box_text_changed));
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(
+            Update,
+            copy_image_to_clipboard
+                .run_if(should_copy_image)
+                .after(keyboard_input_system),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        app.init_resource::<OpenFilesRequest>()
+            .add_systems(Update, (file_drop_handler, open_files).chain());
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(
+            Update,
+            (
+                sync_folder.after(autosave),
+                write_synced_docs.after(save_to_store),
+            )
+                .distributive_run_if(resource_exists::<FolderSyncState>()),
+        );
 
         app.add_systems(
             Update,
@@ -299,22 +437,104 @@ impl Plugin for UiPlugin {
                 save_to_store.after(save_tab),
                 canvas_click,
                 active_editor_changed,
-                interactive_node.before(canvas_click),
+                interactive_node.before(canvas_click).run_if(not_previewing),
                 change_theme,
                 enable_drawing_mode,
-                drawing,
-                update_drawing_position,
+                drawing.run_if(not_previewing),
+                update_drawing_position.run_if(not_previewing),
+            ),
+        );
+        app.add_systems(
+            Update,
+            (
+                drawing_two_points.run_if(not_previewing),
+                enable_two_points_draw_mode,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            (
+                set_focus_drawing.run_if(not_previewing),
+                entity_to_draw_selected_changed,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            (set_focused_entity.run_if(not_previewing), clickable_links).chain(),
+        );
+        app.add_systems(
+            Update,
+            (autosave, update_unsaved_markers, window_close_requested).chain(),
+        );
+        app.add_systems(Update, (show_notifications, expire_toasts));
+        app.add_systems(
+            Update,
+            (
+                edit_tags_handler,
+                filter_by_tag_handler,
+                set_node_tags.run_if(should_set_node_tags),
+                filter_doc_list.before(doc_list_ui_changed),
+                dim_untagged_nodes,
+            ),
+        );
+        app.add_systems(
+            Update,
+            (
+                offer_journal_replay.run_if(resource_exists::<PendingJournal>()),
+                replay_pending_journal.run_if(
+                    resource_exists::<PendingJournal>()
+                        .and_then(resource_exists::<JournalReplayRequest>()),
+                ),
+            ),
+        );
+        app.add_systems(
+            Update,
+            (
+                reset_canvas_history
+                    .before(load_tab)
+                    .before(load_doc)
+                    .before(undo_canvas_edit),
+                (undo_canvas_edit, remove_undo_request)
+                    .chain()
+                    .distributive_run_if(should_undo),
             ),
         );
         app.add_systems(
             Update,
-            (drawing_two_points, enable_two_points_draw_mode).chain(),
+            (
+                save_version_handler,
+                show_versions_handler,
+                version_button_handler,
+                (save_version, remove_save_version_request)
+                    .chain()
+                    .distributive_run_if(should_save_version),
+                update_version_banner,
+                highlight_version_diff,
+            ),
         );
         app.add_systems(
             Update,
-            (set_focus_drawing, entity_to_draw_selected_changed).chain(),
+            (
+                (merge_doc, remove_merge_doc_request)
+                    .chain()
+                    .distributive_run_if(should_merge_doc)
+                    .after(save_tab),
+                merge_conflict_button_handler,
+                (resolve_merge_conflict, remove_resolve_conflict_request)
+                    .chain()
+                    .distributive_run_if(should_resolve_conflict)
+                    .after(save_tab),
+                update_merge_conflicts_panel,
+            ),
+        );
+        app.add_systems(
+            Update,
+            save_and_exit
+                .after(remove_save_tab_request)
+                .run_if(should_exit),
         );
-        app.add_systems(Update, (set_focused_entity, clickable_links).chain());
 
         app.add_systems(
             Update,
@@ -327,12 +547,12 @@ impl Plugin for UiPlugin {
                 .before(create_new_node),
         );
 
-        app.add_systems(PostUpdate, resize_notificator);
+        app.add_systems(PostUpdate, (resize_notificator, record_canvas_history));
     }
 }
 
 #[cfg(target_arch = "wasm32")]
-fn load_from_url(mut commands: Commands) {
+fn load_from_url(mut commands: Commands, notifications: Res<Notifications>) {
     let (tx, rx) = async_channel::bounded(1);
     commands.insert_resource(CommChannels { tx: tx.clone(), rx });
     let href = web_sys::window().unwrap().location().href().unwrap();
@@ -346,11 +566,16 @@ fn load_from_url(mut commands: Commands) {
         if links.len() == 1 {
             let url = links.first().unwrap().as_str().to_owned();
             let cc = tx.clone();
+            let notifications = notifications.clone();
             let task = pool.spawn(async move {
-                let request = ehttp::Request::get(url);
+                let request = ehttp::Request::get(&url);
                 ehttp::fetch(request, move |result| {
-                    let json_string = result.unwrap().text().unwrap();
-                    cc.try_send(json_string).unwrap();
+                    match crate::utils::response_text(result) {
+                        Ok(json_string) => {
+                            let _ = cc.try_send(json_string);
+                        }
+                        Err(e) => notifications.error(format!("Failed to load {}: {}", url, e)),
+                    }
                 });
             });
             task.detach();
@@ -359,11 +584,27 @@ fn load_from_url(mut commands: Commands) {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn read_native_config(mut app_state: ResMut<AppState>) {
+fn read_native_config(
+    mut commands: Commands,
+    mut app_state: ResMut<AppState>,
+    mut history: ResMut<CanvasHistory>,
+) {
+    use crate::document::FolderSync;
     use crate::utils::read_config_file;
 
     let config = read_config_file().unwrap_or_default();
     if let Some(github_token) = &config.github_access_token {
         app_state.github_token = Some(github_token.clone());
     }
+    history.limit = config.undo_limit;
+    if let Some(dir) = config.sync_folder {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            error!("Failed to create sync folder {:?}: {}", dir, e);
+        }
+        commands.insert_resource(FolderSyncState {
+            sync: FolderSync::new(dir),
+            timer: Timer::new(FOLDER_SYNC_INTERVAL, TimerMode::Repeating),
+            failing: false,
+        });
+    }
 }
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine};
+use bevy::{app::AppExit, prelude::*, window::PrimaryWindow, window::WindowCloseRequested};
+use bevy_cosmic_edit::CosmicFont;
+use bevy_pkv::PkvStore;
+
+use super::ui_helpers::{spawn_modal, ModalTop, UnsavedMarker};
+use super::{discard_journal, image_to_png, CanvasQuery, MainPanel, ModalAction};
+use crate::document::{
+    append_journal, compact_journal, image_hash, store_doc, JournalEntry, TabSnapshot,
+};
+use crate::resources::{
+    AppState, ExitRequest, FontSystemState, Journal, LoadDocRequest, LoadTabRequest,
+    SaveDocRequest, SaveTabRequest, VersionPreview,
+};
+use crate::themes::Theme;
+use crate::utils::ReflectableUuid;
+use crate::UiState;
+
+/// How often the canvas is compared with the active tab's last checkpoint.
+const UNSAVED_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Journal entries after which older entries of the same tab are dropped from the journal.
+const JOURNAL_COMPACT_ENTRIES: usize = 64;
+/// Idle time after the last edit before unsaved changes are written to the store.
+pub const AUTOSAVE_DELAY: Duration = Duration::from_secs(3);
+
+/// Hashes of node images on the canvas, so each image is only encoded once.
+#[derive(Default)]
+pub struct ImageHashes {
+    hashes: HashMap<Handle<Image>, String>,
+    encoded: HashMap<String, String>,
+}
+
+pub struct AutosaveState {
+    timer: Timer,
+    last_edit: Duration,
+    canvas: Option<TabSnapshot>,
+}
+
+impl Default for AutosaveState {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(UNSAVED_CHECK_INTERVAL, TimerMode::Repeating),
+            last_edit: Duration::ZERO,
+            canvas: None,
+        }
+    }
+}
+
+pub fn autosave(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut state: Local<AutosaveState>,
+    mut app_state: ResMut<AppState>,
+    ui_state: Res<UiState>,
+    canvas: CanvasQuery,
+    images: Res<Assets<Image>>,
+    mut image_hashes: Local<ImageHashes>,
+    mut journal: Option<ResMut<Journal>>,
+    requests: (
+        Option<Res<SaveDocRequest>>,
+        Option<Res<SaveTabRequest>>,
+        Option<Res<LoadDocRequest>>,
+        Option<Res<LoadTabRequest>>,
+    ),
+    preview: Option<Res<VersionPreview>>,
+) {
+    if !state.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let (save_doc, save_tab, load_doc, load_tab) = requests;
+    if save_doc.is_some() || save_tab.is_some() || load_doc.is_some() || load_tab.is_some() {
+        return;
+    }
+    if preview.is_some() {
+        // edits of a previewed version are thrown away with the preview
+        state.canvas = None;
+        return;
+    }
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    let Some(tab) = app_state
+        .docs
+        .get(&doc_id)
+        .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active))
+    else {
+        return;
+    };
+    let tab_id = tab.id;
+    let ImageHashes { hashes, encoded } = &mut *image_hashes;
+    let snapshot = canvas.snapshot(|handle| {
+        if let Some(hash) = hashes.get(handle) {
+            return Some(hash.clone());
+        }
+        let png = image_to_png(images.get(handle)?)?;
+        let hash = image_hash(&png);
+        encoded.insert(hash.clone(), general_purpose::STANDARD.encode(&png));
+        hashes.insert(handle.clone(), hash.clone());
+        Some(hash)
+    });
+    // images of nodes that are gone from the canvas are encoded again if they come back
+    hashes.retain(|_, hash| snapshot.images.values().any(|key| key == hash));
+    encoded.retain(|hash, _| snapshot.images.values().any(|key| key == hash));
+    let unsaved = !snapshot.same_canvas(&tab.checkpoints.back().cloned().unwrap_or_default());
+    if state.canvas.as_ref() != Some(&snapshot) {
+        state.last_edit = time.elapsed();
+        if let Some(journal) = journal.as_mut() {
+            // an edit that is undone by hand still goes in, it supersedes the earlier entries
+            if unsaved || journal.pending {
+                let doc_images = &app_state.docs[&doc_id].images;
+                let entry = JournalEntry {
+                    doc_id,
+                    tab_id,
+                    images: snapshot
+                        .images
+                        .values()
+                        .filter(|hash| {
+                            !doc_images.contains_key(*hash) && !journal.images.contains(*hash)
+                        })
+                        .filter_map(|hash| Some((hash.clone(), encoded.get(hash)?.clone())))
+                        .collect(),
+                    snapshot: snapshot.clone(),
+                };
+                match append_journal(&journal.path, &entry) {
+                    Ok(()) => {
+                        journal.pending = true;
+                        journal.images.extend(entry.images.into_keys());
+                        journal.entries += 1;
+                    }
+                    Err(e) => error!("Failed to write journal {:?}: {}", journal.path, e),
+                }
+                if journal.entries >= JOURNAL_COMPACT_ENTRIES {
+                    match compact_journal(&journal.path) {
+                        Ok(entries) => {
+                            // images of dropped entries are written again if they come back
+                            journal.images = entries
+                                .iter()
+                                .flat_map(|entry| entry.images.keys().cloned())
+                                .collect();
+                            journal.entries = entries.len();
+                        }
+                        Err(e) => error!("Failed to compact journal {:?}: {}", journal.path, e),
+                    }
+                }
+            }
+        }
+        state.canvas = Some(snapshot);
+    }
+    if unsaved {
+        app_state.unsaved_tabs.insert(tab_id);
+    } else {
+        app_state.unsaved_tabs.remove(&tab_id);
+    }
+    if let Some(journal) = journal.as_mut() {
+        let unsaved_doc = app_state
+            .docs
+            .keys()
+            .any(|doc_id| app_state.has_unsaved_changes(*doc_id));
+        if journal.pending && !unsaved_doc {
+            discard_journal(journal);
+        }
+    }
+
+    let editing = ui_state.entity_to_edit.is_some()
+        || ui_state.hold_entity.is_some()
+        || ui_state.entity_to_resize.is_some()
+        || ui_state.entity_to_draw_hold.is_some();
+    if !editing
+        && app_state.has_unsaved_changes(doc_id)
+        && time.elapsed() - state.last_edit >= AUTOSAVE_DELAY
+    {
+        commands.insert_resource(SaveDocRequest { doc_id, path: None });
+    }
+}
+
+pub fn update_unsaved_markers(
+    app_state: Res<AppState>,
+    mut markers: Query<(&mut Visibility, &UnsavedMarker)>,
+) {
+    if !app_state.is_changed() {
+        return;
+    }
+    for (mut visibility, marker) in markers.iter_mut() {
+        let unsaved =
+            app_state.unsaved_tabs.contains(&marker.id) || app_state.has_unsaved_changes(marker.id);
+        let expected = if unsaved {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != expected {
+            *visibility = expected;
+        }
+    }
+}
+
+/// Closing the window exits right away when nothing is unsaved, and asks to save otherwise.
+/// A modal that is open at that point is closed first.
+pub fn window_close_requested(
+    mut commands: Commands,
+    mut events: EventReader<WindowCloseRequested>,
+    mut exit: EventWriter<AppExit>,
+    app_state: Res<AppState>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: ResMut<FontSystemState>,
+    theme: Res<Theme>,
+    journal: Option<ResMut<Journal>>,
+    modals: Query<(Entity, &ModalTop)>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+    for (entity, modal) in modals.iter() {
+        match modal.action {
+            // already asking
+            ModalAction::SaveAndExit => return,
+            // the journal is offered again on next start
+            ModalAction::RestoreJournal => {
+                exit.send(AppExit);
+                return;
+            }
+            _ => commands.entity(entity).despawn_recursive(),
+        }
+    }
+    let unsaved = app_state
+        .docs
+        .keys()
+        .any(|doc_id| app_state.has_unsaved_changes(*doc_id));
+    if !unsaved {
+        if let Some(mut journal) = journal {
+            discard_journal(&mut journal);
+        }
+        exit.send(AppExit);
+        return;
+    }
+    let id = ReflectableUuid::generate();
+    *ui_state = UiState::default();
+    commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+    ui_state.modal_id = Some(id);
+    let entity = spawn_modal(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        font_system_state.0.clone().unwrap(),
+        windows.single(),
+        id,
+        ModalAction::SaveAndExit,
+    );
+    commands.entity(main_panel_query.single()).add_child(entity);
+}
+
+pub fn should_exit(request: Option<Res<ExitRequest>>) -> bool {
+    request.is_some()
+}
+
+/// Saves the active tab through the regular pipeline, then writes every unsaved document
+/// to the store and closes the app.
+pub fn save_and_exit(
+    mut commands: Commands,
+    mut tab_saved: Local<bool>,
+    save_tab_request: Option<Res<SaveTabRequest>>,
+    mut app_state: ResMut<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    mut exit: EventWriter<AppExit>,
+    journal: Option<ResMut<Journal>>,
+) {
+    if !*tab_saved {
+        *tab_saved = true;
+        if let Some(doc_id) = app_state.current_document {
+            let active_tab = app_state
+                .docs
+                .get(&doc_id)
+                .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active));
+            if let Some(tab) = active_tab {
+                commands.insert_resource(SaveTabRequest {
+                    doc_id,
+                    tab_id: tab.id,
+                });
+                return;
+            }
+        }
+    }
+    if save_tab_request.is_some() {
+        return;
+    }
+    let unsaved_docs: Vec<_> = app_state.unsaved_docs.drain().collect();
+    let mut stored = true;
+    for doc_id in unsaved_docs {
+        if let Some(doc) = app_state.docs.get(&doc_id) {
+            if let Err(e) = store_doc(&mut pkv, doc) {
+                error!("Failed to store \"{}\": {}", doc.name, e);
+                stored = false;
+            }
+        }
+    }
+    // the journal brings back what couldn't be stored on next start
+    if let (true, Some(mut journal)) = (stored, journal) {
+        discard_journal(&mut journal);
+    }
+    exit.send(AppExit);
+}
@@ -10,7 +10,7 @@ use bevy_pkv::PkvStore;
 use bevy_prototype_lyon::prelude::{Fill, Stroke};
 use cosmic_text::{Cursor, Edit};
 use serde::Serialize;
-use serde_json::{json, Value};
+use serde_json::Value;
 use uuid::Uuid;
 
 use crate::themes::Theme;
@@ -21,13 +21,16 @@ use super::ui_helpers::{
     Drawing, GenericButton, NewDoc, RawText, SaveDoc, TextPosMode, Tooltip, TwoPointsDraw,
     VeloNode, VeloShape,
 };
-use super::{ExportToFile, ImportFromFile, ImportFromUrl, MainPanel, ShareDoc};
+use super::{
+    CanvasEdit, ExportToFile, ImportFromFile, ImportFromUrl, MainPanel, Notifications, ShareDoc,
+};
 use crate::canvas::arrow::components::{ArrowMeta, ArrowMode};
 use crate::components::{Doc, MainCamera, Tab};
+use crate::document::{load_doc_index, load_stored_doc, TabSnapshot, DOC_SCHEMA_VERSION};
 use crate::resources::{AppState, FontSystemState, LoadDocRequest, SaveDocRequest};
 use crate::utils::{
-    bevy_color_to_cosmic, get_timestamp, load_doc_to_memory, ReflectableUuid, UserPreferences,
-    DARK_THEME_ICON_CODE, LIGHT_THEME_ICON_CODE,
+    bevy_color_to_cosmic, get_timestamp, load_doc_to_memory, response_text, ReflectableUuid,
+    UserPreferences, DARK_THEME_ICON_CODE, LIGHT_THEME_ICON_CODE,
 };
 
 #[path = "../../macros.rs"]
@@ -60,6 +63,7 @@ pub fn rec_button_handlers(
         ),
     >,
     theme: Res<Theme>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     let mut camera_transform = camera_proj_query.single_mut();
     let x = camera_transform.translation.x;
@@ -125,8 +129,10 @@ pub fn rec_button_handlers(
                     });
                 }
                 super::ui_helpers::ButtonTypes::Del => {
+                    let mut deleted = vec![];
                     if let Some(id) = ui_state.entity_to_draw_selected {
                         ui_state.entity_to_draw_selected = None;
+                        deleted.push(id);
                         for (entity, drawing) in &mut drawings.iter_mut() {
                             if drawing.id == id {
                                 commands.entity(entity).despawn_recursive();
@@ -134,6 +140,7 @@ pub fn rec_button_handlers(
                         }
                     }
                     if let Some(id) = ui_state.entity_to_edit {
+                        deleted.push(id);
                         commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
                         *ui_state = UiState::default();
                         for (entity, node, _, _) in velo_node_query.iter() {
@@ -147,6 +154,9 @@ pub fn rec_button_handlers(
                             }
                         }
                     }
+                    if !deleted.is_empty() {
+                        canvas_edits.send(CanvasEdit { ids: deleted });
+                    }
                 }
                 super::ui_helpers::ButtonTypes::Front => {
                     let current_document = app_state.current_document.unwrap();
@@ -204,6 +214,7 @@ pub fn rec_button_handlers(
                                 break;
                             }
                         }
+                        canvas_edits.send(CanvasEdit { ids: vec![id] });
                     }
                 }
                 super::ui_helpers::ButtonTypes::Back => {
@@ -250,6 +261,7 @@ pub fn rec_button_handlers(
                                 break;
                             }
                         }
+                        canvas_edits.send(CanvasEdit { ids: vec![id] });
                     }
                 }
                 super::ui_helpers::ButtonTypes::AddText => {
@@ -348,6 +360,7 @@ pub fn change_color_pallete(
     >,
     mut velo_border: Query<(&mut Fill, &mut Stroke, &mut VeloShape), With<VeloShape>>,
     mut ui_state: ResMut<UiState>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     for (interaction, change_color) in &mut interaction_query {
         match *interaction {
@@ -360,6 +373,9 @@ pub fn change_color_pallete(
                             stroke.color = Color::NONE;
                         }
                         velo_border.pair_color = pair_color;
+                        canvas_edits.send(CanvasEdit {
+                            ids: vec![velo_border.id],
+                        });
                         return;
                     }
                 }
@@ -427,21 +443,14 @@ pub fn new_doc_handler(
                 let name = "Untitled".to_string();
                 let tab_id = ReflectableUuid::generate();
                 let mut checkpoints = VecDeque::new();
-                checkpoints.push_back(
-                    json!({
-                        "nodes": [],
-                        "arrows": [],
-                        "images": {},
-                        "drawings": []
-                    })
-                    .to_string(),
-                );
+                checkpoints.push_back(TabSnapshot::default());
                 let tabs = vec![Tab {
                     id: tab_id,
                     name: "Tab 1".to_string(),
                     checkpoints,
                     is_active: true,
                     z_index: 1.,
+                    versions: vec![],
                 }];
                 app_state.docs.insert(
                     doc_id,
@@ -450,6 +459,8 @@ pub fn new_doc_handler(
                         name: name.clone(),
                         tabs,
                         tags: vec![],
+                        version: DOC_SCHEMA_VERSION,
+                        images: HashMap::new(),
                     },
                 );
                 commands.insert_resource(SaveDocRequest {
@@ -533,17 +544,24 @@ pub fn delete_doc_handler(
         match *interaction {
             Interaction::Pressed => {
                 if app_state.docs.len() == 1 {
-                    if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-                        if docs.len() > 1 {
-                            for (id, doc) in docs.iter() {
-                                if app_state.docs.len() != 1 {
-                                    break;
+                    let index = load_doc_index(&pkv);
+                    if index.names.len() > 1 {
+                        for id in index.names.keys() {
+                            if app_state.docs.len() != 1 {
+                                break;
+                            }
+                            if app_state.docs.contains_key(id) {
+                                continue;
+                            }
+                            match load_stored_doc(&pkv, *id) {
+                                Some(Ok(doc)) => {
+                                    app_state.docs.insert(*id, doc);
                                 }
-                                app_state.docs.insert(*id, doc.clone());
+                                Some(Err(e)) => {
+                                    error!("Failed to upgrade document {:?}: {}", id, e)
+                                }
+                                None => error!("Document {:?} not found in pkv", id),
                             }
-                        } else {
-                            // do not allow deletion if there is less than two docs
-                            return;
                         }
                     } else {
                         // do not allow deletion if there is less than two docs
@@ -626,9 +644,13 @@ pub fn export_to_file(
 }
 
 #[cfg(target_arch = "wasm32")]
-pub fn set_window_property(mut app_state: ResMut<AppState>, mut pkv: ResMut<PkvStore>) {
+pub fn set_window_property(
+    mut app_state: ResMut<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    notifications: Res<Notifications>,
+) {
     if let Some(doc_id) = app_state.current_document {
-        load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
+        load_doc_to_memory(doc_id, &mut app_state, &mut pkv, &notifications);
         let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
         let value = serde_json::to_string_pretty(&current_doc).unwrap();
         let window = wasm_bindgen::JsValue::from(web_sys::window().unwrap());
@@ -654,12 +676,18 @@ pub fn shared_doc_handler(
     mut app_state: ResMut<AppState>,
     mut query: Query<&Interaction, (Changed<Interaction>, With<ShareDoc>)>,
     mut pkv: ResMut<PkvStore>,
+    notifications: Res<Notifications>,
 ) {
     for interaction in &mut query.iter_mut() {
         match *interaction {
             Interaction::Pressed => {
                 if let Some(doc_id) = app_state.current_document {
-                    load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
+                    let Some(github_token) = app_state.github_token.clone() else {
+                        notifications
+                            .error("Sharing needs a github_access_token in the config file");
+                        continue;
+                    };
+                    load_doc_to_memory(doc_id, &mut app_state, &mut pkv, &notifications);
                     let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
                     let contents = serde_json::to_string_pretty(&current_doc).unwrap();
                     let mut files = std::collections::HashMap::new();
@@ -685,27 +713,37 @@ pub fn shared_doc_handler(
                     );
                     request.headers.insert(
                         "Authorization".to_string(),
-                        format!("token {}", app_state.github_token.as_ref().unwrap()),
+                        format!("token {}", github_token),
                     );
-                    #[cfg(not(target_arch = "wasm32"))]
-                    let mut clipboard = arboard::Clipboard::new().unwrap();
+                    let notifications = notifications.clone();
                     ehttp::fetch(request, move |result| {
-                        let response = result.unwrap();
-                        if response.ok {
-                            let res_json: Value =
-                                serde_json::from_str(response.text().unwrap().as_str()).unwrap();
-                            let files: Value = res_json["files"].clone();
-                            let velo = files["velo.json"].clone();
-                            #[cfg(not(target_arch = "wasm32"))]
-                            clipboard
-                                .set_text(format!(
-                                    "https://staffengineer.github.io/velo?document={}",
-                                    velo["raw_url"].to_string().replace('\"', "")
-                                ))
-                                .unwrap();
-                        } else {
-                            error!("Error sharing document: {}", response.status_text);
+                        let res_json: Value = match response_text(result)
+                            .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+                        {
+                            Ok(res_json) => res_json,
+                            Err(e) => {
+                                notifications.error(format!("Error sharing document: {}", e));
+                                return;
+                            }
+                        };
+                        let files: Value = res_json["files"].clone();
+                        let velo = files["velo.json"].clone();
+                        let link = format!(
+                            "https://staffengineer.github.io/velo?document={}",
+                            velo["raw_url"].to_string().replace('\"', "")
+                        );
+                        #[cfg(not(target_arch = "wasm32"))]
+                        match arboard::Clipboard::new()
+                            .and_then(|mut clipboard| clipboard.set_text(&link))
+                        {
+                            Ok(()) => notifications.info("Share link copied to clipboard"),
+                            Err(e) => notifications.error(format!(
+                                "Document shared as {}, but copying the link failed: {}",
+                                link, e
+                            )),
                         }
+                        #[cfg(target_arch = "wasm32")]
+                        notifications.info(format!("Document shared as {}", link));
                     });
                 }
             }
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose, Engine};
+use bevy::prelude::*;
+
+use super::{image_to_png, CanvasQuery, Notifications};
+use crate::document::image_hash;
+use crate::export::{select, snapshot_to_image, RasterFonts};
+use crate::resources::CopyImageRequest;
+use crate::themes::Theme;
+use crate::UiState;
+
+pub fn should_copy_image(request: Option<Res<CopyImageRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn copy_image_to_clipboard(
+    mut commands: Commands,
+    request: Res<CopyImageRequest>,
+    ui_state: Res<UiState>,
+    canvas: CanvasQuery,
+    images: Res<Assets<Image>>,
+    theme: Res<Theme>,
+    notifications: Res<Notifications>,
+    mut raster_fonts: Local<RasterFonts>,
+) {
+    commands.remove_resource::<CopyImageRequest>();
+    let mut encoded = HashMap::new();
+    let snapshot = canvas.snapshot(|handle| {
+        let png = image_to_png(images.get(handle)?)?;
+        let hash = image_hash(&png);
+        encoded.insert(hash.clone(), general_purpose::STANDARD.encode(&png));
+        Some(hash)
+    });
+    let selection: Vec<_> = [ui_state.entity_to_edit, ui_state.entity_to_draw_selected]
+        .into_iter()
+        .flatten()
+        .collect();
+    let snapshot = if selection.is_empty() {
+        snapshot
+    } else {
+        select(&snapshot, &selection)
+    };
+    let image = snapshot_to_image(
+        &snapshot,
+        &encoded,
+        &theme,
+        &mut raster_fonts,
+        request.scale,
+    );
+    let image_data = arboard::ImageData {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        bytes: image.into_raw().into(),
+    };
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_image(image_data)) {
+        Ok(()) => notifications.info("Image copied to clipboard"),
+        Err(e) => notifications.error(format!("Failed to copy image to clipboard: {}", e)),
+    }
+}
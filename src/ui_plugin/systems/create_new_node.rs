@@ -8,7 +8,7 @@ use crate::{
     utils::ReflectableUuid,
 };
 
-use super::{ui_helpers::spawn_sprite_node, AddRect, NodeMeta, UiState};
+use super::{ui_helpers::spawn_sprite_node, AddRect, CanvasEdit, NodeMeta, UiState};
 
 pub fn create_new_node(
     mut commands: Commands,
@@ -22,6 +22,7 @@ pub fn create_new_node(
     mut z_index_local: Local<f32>,
     mut materials: ResMut<Assets<CustomShadowMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     let window = windows.single_mut();
     for event in events.iter() {
@@ -57,7 +58,11 @@ pub fn create_new_node(
                 text_pos: event.node.text.pos.clone(),
                 is_active: true,
                 visible: true,
+                tags: event.node.tags.clone(),
             },
         );
+        canvas_edits.send(CanvasEdit {
+            ids: vec![ReflectableUuid(event.node.id)],
+        });
     }
 }
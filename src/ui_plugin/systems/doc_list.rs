@@ -8,9 +8,10 @@ use bevy_cosmic_edit::CosmicFont;
 use super::ui_helpers::ScrollingList;
 use crate::{resources::FontSystemState, themes::Theme, ui_plugin::ui_helpers::DocListItemButton};
 
+use crate::document::load_doc_index;
 use crate::resources::{AppState, LoadDocRequest, SaveDocRequest};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use bevy_pkv::PkvStore;
 
@@ -33,6 +34,7 @@ pub fn list_item_click(
         match *interaction {
             Interaction::Pressed => {
                 if Some(doc_list_item.id) != state.current_document {
+                    // the canvas may have edits that aren't in the document yet
                     commands.insert_resource(SaveDocRequest {
                         doc_id: state.current_document.unwrap(),
                         path: None,
@@ -147,10 +149,8 @@ pub fn get_doc_name(
     if let Some(doc) = app_state.docs.get(&doc_id) {
         return doc.name.clone();
     }
-    if let Ok(names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-        if let Some(name) = names.get(&doc_id) {
-            return name.clone();
-        }
+    if let Some(name) = load_doc_index(pkv).names.get(&doc_id) {
+        return name.clone();
     }
 
     "Unknown".to_string()
@@ -12,7 +12,7 @@ use crate::{
 
 use super::{
     ui_helpers::{Drawing, InteractiveNode, MainPanel, TwoPointsDrawType},
-    NodeInteraction, NodeInteractionType, UiState,
+    CanvasEdit, NodeInteraction, NodeInteractionType, UiState,
 };
 
 #[path = "../../macros.rs"]
@@ -50,6 +50,7 @@ pub fn set_focus_drawing(
     mut node_interaction_events: EventReader<NodeInteraction>,
     mut ui_state: ResMut<UiState>,
     drawing_container_q: Query<&Drawing<(String, Color)>, With<Drawing<(String, Color)>>>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     for event in node_interaction_events.iter() {
         if let Ok(drawing) = drawing_container_q.get(event.entity) {
@@ -69,7 +70,9 @@ pub fn set_focus_drawing(
             }
         }
         if event.node_interaction_type == NodeInteractionType::LeftMouseRelease {
-            ui_state.entity_to_draw_hold = None;
+            if let Some(id) = ui_state.entity_to_draw_hold.take() {
+                canvas_edits.send(CanvasEdit { ids: vec![id] });
+            }
         }
     }
 }
@@ -91,6 +94,7 @@ pub fn drawing_two_points(
         With<Drawing<(String, Color)>>,
     >,
     interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainPanel>)>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     if *previous_draw_mode != ui_state.drawing_two_points_mode.clone()
         || ui_state.entity_to_draw_selected.is_some()
@@ -256,6 +260,11 @@ pub fn drawing_two_points(
             {
                 *drawing_path = path;
                 drawing_comp.points = points;
+                if end.is_some() {
+                    canvas_edits.send(CanvasEdit {
+                        ids: vec![drawing_comp.id],
+                    });
+                }
             }
 
             if end.is_some() {
@@ -282,6 +291,7 @@ pub fn drawing(
     >,
     mut app_state: ResMut<AppState>,
     mut z_index_local: Local<f32>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     if ui_state.entity_to_draw_hold.is_some() || ui_state.entity_to_draw_selected.is_some() {
         *holding_state = None;
@@ -316,7 +326,9 @@ pub fn drawing(
         if buttons.just_released(MouseButton::Left) {
             *holding_state = None;
             primary_window.cursor.icon = CursorIcon::Default;
-            ui_state.entity_to_draw = None;
+            if let Some(id) = ui_state.entity_to_draw.take() {
+                canvas_edits.send(CanvasEdit { ids: vec![id] });
+            }
         }
 
         if let Some(holding_time) = *holding_state {
@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use super::{Notifications, SaveStore};
+use crate::components::Doc;
+use crate::document::{load_stored_doc, repair_doc, round_doc, store_doc, FolderChange};
+use crate::export::{write_doc, RasterFonts};
+use crate::resources::{AppState, CanvasHistory, FolderSyncState, MergeDocRequest, SaveTabRequest};
+use crate::themes::Theme;
+
+/// How often the synced folder is checked for changed files.
+pub const FOLDER_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Picks up document files added, changed or removed in the synced folder.
+/// Documents with changes of their own, or on the canvas, are merged with the file instead
+/// of being replaced by it.
+pub fn sync_folder(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut folder: ResMut<FolderSyncState>,
+    mut app_state: ResMut<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    mut history: ResMut<CanvasHistory>,
+    merge_request: Option<Res<MergeDocRequest>>,
+    notifications: Res<Notifications>,
+) {
+    if !folder.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let changes = match folder.sync.scan() {
+        Ok(changes) => changes,
+        Err(e) => {
+            // reported once, until the folder can be read again
+            if !folder.failing {
+                notifications.error(format!(
+                    "Failed to read {}: {}",
+                    folder.sync.dir.display(),
+                    e
+                ));
+            }
+            folder.failing = true;
+            return;
+        }
+    };
+    folder.failing = false;
+    let mut merging = merge_request.is_some();
+    let mut added = 0;
+    for change in changes {
+        match change {
+            FolderChange::Changed { path, mut doc } => {
+                let doc_id = doc.id;
+                let local = match app_state.docs.get(&doc_id) {
+                    Some(local) => Some(local.clone()),
+                    None => load_stored_doc(&pkv, doc_id).and_then(Result::ok),
+                };
+                if local.as_ref().is_some_and(|local| same_doc(local, &doc)) {
+                    continue;
+                }
+                let known = local.is_some();
+                let open = app_state.current_document == Some(doc_id);
+                if known && (open || app_state.has_unsaved_changes(doc_id)) {
+                    if merging {
+                        // one merge at a time, the file is picked up again by the next scan
+                        folder.sync.forget(&path);
+                        continue;
+                    }
+                    merging = true;
+                    let active_tab = app_state
+                        .docs
+                        .get(&doc_id)
+                        .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active));
+                    if let (true, Some(tab)) = (open, active_tab) {
+                        // the canvas may have edits that aren't in the document yet
+                        commands.insert_resource(SaveTabRequest {
+                            doc_id,
+                            tab_id: tab.id,
+                        });
+                    }
+                    commands.insert_resource(MergeDocRequest { doc });
+                    continue;
+                }
+                let issues = repair_doc(&mut doc);
+                notifications.repaired(&path.display().to_string(), &issues);
+                if let Err(e) = store_doc(&mut pkv, &doc) {
+                    notifications.error(format!("Failed to store {}: {}", path.display(), e));
+                    continue;
+                }
+                for tab in doc.tabs.iter() {
+                    history.remove_tab(&tab.id);
+                }
+                if app_state.docs.contains_key(&doc_id) {
+                    app_state.docs.insert(doc_id, doc);
+                }
+                if known {
+                    info!("Reloaded {}", path.display());
+                } else {
+                    added += 1;
+                    app_state.doc_list_ui.insert(doc_id);
+                }
+            }
+            FolderChange::Removed { path, doc_id } => {
+                if doc_id.is_some() {
+                    notifications.info(format!(
+                        "{} was removed, the document stays in velo",
+                        path.display()
+                    ));
+                }
+            }
+            FolderChange::Invalid { path, error } => {
+                notifications.error(format!("Failed to read {}: {}", path.display(), error));
+            }
+        }
+    }
+    if added > 0 {
+        notifications.info(format!(
+            "Added {} documents from {}",
+            added,
+            folder.sync.dir.display()
+        ));
+    }
+}
+
+/// Writes documents saved in velo back to their file in the synced folder.
+/// The file rounds coordinates, the document in velo and in the store is rounded the same
+/// way so that it still compares equal to the file when the file is read back.
+pub fn write_synced_docs(
+    mut events: EventReader<SaveStore>,
+    mut folder: ResMut<FolderSyncState>,
+    mut app_state: ResMut<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    notifications: Res<Notifications>,
+    theme: Res<Theme>,
+    mut raster_fonts: Local<RasterFonts>,
+) {
+    for event in events.iter() {
+        let Some(path) = folder.sync.path_of(event.doc_id).cloned() else {
+            continue;
+        };
+        let Some(doc) = app_state.docs.get(&event.doc_id) else {
+            continue;
+        };
+        let doc = match round_doc(doc) {
+            Ok(doc) => doc,
+            Err(e) => {
+                notifications.error(format!("Failed to save to {}: {}", path.display(), e));
+                continue;
+            }
+        };
+        match write_doc(&path, &doc, &theme, &mut raster_fonts) {
+            Ok(()) => {
+                folder.sync.mark_written(&path, doc.id);
+                if let Err(e) = store_doc(&mut pkv, &doc) {
+                    notifications.error(format!("Failed to store \"{}\": {}", doc.name, e));
+                }
+                app_state.docs.insert(doc.id, doc);
+            }
+            Err(e) => notifications.error(format!("Failed to save to {}: {}", path.display(), e)),
+        }
+    }
+}
+
+fn same_doc(a: &Doc, b: &Doc) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{
+        doc_from_json, doc_to_canonical_json, merge_docs, FolderSync, TabSnapshot,
+    };
+    use crate::resources::SaveDocRequest;
+    use crate::themes::velo_light;
+    use crate::ui_plugin::{autosave, Drawing};
+    use crate::utils::ReflectableUuid;
+    use crate::{DrawingJsonNode, UiState};
+    use bevy_prototype_lyon::prelude::Stroke;
+
+    #[test]
+    fn test_write_synced_doc_fractional() {
+        let mut doc = doc_from_json(include_str!("../../export/fixtures/board.json")).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("board.json");
+        std::fs::write(&path, doc_to_canonical_json(&doc).unwrap()).unwrap();
+        let mut sync = FolderSync::new(dir.path().to_path_buf());
+        assert_eq!(sync.scan().unwrap().len(), 1);
+
+        // saved in velo with coordinates the file doesn't keep
+        let canvas = doc.tabs[0].checkpoints.back_mut().unwrap();
+        canvas.nodes[0].x = 20.987654;
+        canvas.nodes[1].y = -3.333333;
+        let doc_id = doc.id;
+        let mut app = App::new();
+        app.add_systems(Update, write_synced_docs);
+        app.add_event::<SaveStore>();
+        app.init_resource::<Notifications>();
+        app.insert_resource(velo_light());
+        PkvStore::new("test", "folder_sync").clear().unwrap();
+        app.insert_resource(PkvStore::new("test", "folder_sync"));
+        let mut app_state = AppState::default();
+        app_state.docs.insert(doc_id, doc);
+        app.insert_resource(app_state);
+        app.insert_resource(FolderSyncState {
+            sync,
+            timer: Timer::new(FOLDER_SYNC_INTERVAL, TimerMode::Repeating),
+            failing: false,
+        });
+        app.world.send_event(SaveStore { doc_id, path: None });
+        app.update();
+
+        let file = doc_from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let local = load_stored_doc(app.world.resource::<PkvStore>(), doc_id)
+            .unwrap()
+            .unwrap();
+        assert!(same_doc(&local, &file));
+        assert!(same_doc(
+            &app.world.resource::<AppState>().docs[&doc_id],
+            &file
+        ));
+
+        // another tool moves a node and adds the canvas as a new checkpoint
+        let mut remote = file.clone();
+        let mut canvas = remote.tabs[0].checkpoints.back().unwrap().clone();
+        canvas.nodes[2].x += 50.;
+        remote.tabs[0].checkpoints.push_back(canvas);
+        std::fs::write(&path, doc_to_canonical_json(&remote).unwrap()).unwrap();
+        let mut folder = app.world.resource_mut::<FolderSyncState>();
+        let changes = folder.sync.scan().unwrap();
+        let [FolderChange::Changed { doc: remote, .. }] = &changes[..] else {
+            panic!("expected the changed document, got {:?}", changes);
+        };
+
+        let (merged, conflicts) = merge_docs(&local, remote);
+        assert!(conflicts.is_empty(), "{:?}", conflicts);
+        let merged = merged.tabs[0].checkpoints.back().unwrap();
+        let remote = remote.tabs[0].checkpoints.back().unwrap();
+        assert!(merged.same_canvas(remote));
+        assert!(merged.nodes.iter().any(|node| node.x == 20.988));
+    }
+
+    #[test]
+    fn test_autosave_after_synced_save() {
+        let mut doc = doc_from_json(include_str!("../../export/fixtures/board.json")).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("board.json");
+        std::fs::write(&path, doc_to_canonical_json(&doc).unwrap()).unwrap();
+        let mut sync = FolderSync::new(dir.path().to_path_buf());
+        assert_eq!(sync.scan().unwrap().len(), 1);
+
+        // a drawing on the canvas, saved with coordinates the file doesn't keep
+        let drawing = DrawingJsonNode {
+            x: 10.123456,
+            y: -3.333333,
+            z: 1.,
+            id: ReflectableUuid::generate(),
+            points: vec![Vec2::new(0.5, 0.25), Vec2::new(7.777777, 1.111111)],
+            drawing_color: ("color_change_1".to_string(), Color::BLACK),
+            width: 2.,
+        };
+        doc.tabs[0].checkpoints = [TabSnapshot {
+            drawings: vec![DrawingJsonNode {
+                x: drawing.x,
+                y: drawing.y,
+                z: drawing.z,
+                id: drawing.id,
+                points: drawing.points.clone(),
+                drawing_color: drawing.drawing_color.0.clone(),
+                width: drawing.width,
+            }],
+            ..default()
+        }]
+        .into();
+        let (doc_id, tab_id) = (doc.id, doc.tabs[0].id);
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<Image>();
+        app.add_systems(Update, (write_synced_docs, autosave).chain());
+        app.add_event::<SaveStore>();
+        app.init_resource::<Notifications>();
+        app.init_resource::<UiState>();
+        app.insert_resource(velo_light());
+        app.init_resource::<Time>();
+        PkvStore::new("test", "folder_sync_autosave")
+            .clear()
+            .unwrap();
+        app.insert_resource(PkvStore::new("test", "folder_sync_autosave"));
+        let mut app_state = AppState {
+            current_document: Some(doc_id),
+            ..default()
+        };
+        app_state.docs.insert(doc_id, doc);
+        app.insert_resource(app_state);
+        app.insert_resource(FolderSyncState {
+            sync,
+            timer: Timer::new(FOLDER_SYNC_INTERVAL, TimerMode::Repeating),
+            failing: false,
+        });
+        app.world.spawn((
+            Transform::from_xyz(drawing.x, drawing.y, drawing.z),
+            Stroke::new(drawing.drawing_color.1, drawing.width),
+            Drawing {
+                id: drawing.id,
+                points: drawing.points,
+                drawing_color: drawing.drawing_color,
+            },
+        ));
+        app.world.send_event(SaveStore { doc_id, path: None });
+        app.update();
+        assert_ne!(
+            app.world.resource::<AppState>().docs[&doc_id].tabs[0].checkpoints[0].drawings[0].x,
+            10.123456
+        );
+
+        // the canvas still matches the rounded document, well past the autosave delay
+        let start = app.world.resource::<Time>().startup();
+        for seconds in [1, 5, 10] {
+            app.world
+                .resource_mut::<Time>()
+                .update_with_instant(start + Duration::from_secs(seconds));
+            app.update();
+            let app_state = app.world.resource::<AppState>();
+            assert!(!app_state.unsaved_tabs.contains(&tab_id));
+            assert!(!app_state.has_unsaved_changes(doc_id));
+            assert!(app.world.get_resource::<SaveDocRequest>().is_none());
+        }
+    }
+}
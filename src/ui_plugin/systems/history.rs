@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicFont;
+use uuid::Uuid;
+
+use super::ui_helpers::{spawn_drawing, spawn_sprite_node, Drawing, NodeMeta, VeloNode};
+use super::{CanvasEdit, CanvasQuery, DrawingJsonNode, JsonNode};
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::canvas::arrow::events::{CreateArrow, RedrawArrow};
+use crate::canvas::shadows::CustomShadowMaterial;
+use crate::document::{diff_canvas, CanvasChange, CommandHistory};
+use crate::export::ThemeColors;
+use crate::resources::{
+    AppState, CanvasHistory, FontSystemState, LoadDocRequest, LoadTabRequest, UndoRequest,
+    VersionPreview,
+};
+use crate::themes::Theme;
+use crate::utils::ReflectableUuid;
+use crate::UiState;
+
+pub fn should_undo(request: Option<Res<UndoRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn remove_undo_request(world: &mut World) {
+    world.remove_resource::<UndoRequest>().unwrap();
+}
+
+fn active_tab(app_state: &AppState) -> Option<ReflectableUuid> {
+    let doc = app_state.docs.get(&app_state.current_document?)?;
+    doc.tabs.iter().find(|tab| tab.is_active).map(|tab| tab.id)
+}
+
+/// Forget the canvas the next edit is compared with when it is about to be rebuilt
+/// or shows a version, it is taken again once the canvas shows the tab.
+pub fn reset_canvas_history(
+    mut history: ResMut<CanvasHistory>,
+    requests: (
+        Option<Res<LoadDocRequest>>,
+        Option<Res<LoadTabRequest>>,
+        Option<Res<VersionPreview>>,
+    ),
+) {
+    let (load_doc, load_tab, preview) = requests;
+    if load_doc.is_some() || load_tab.is_some() || preview.is_some() {
+        history.canvas = None;
+        history.pending.clear();
+    }
+}
+
+/// Record each edit sent as [`CanvasEdit`] as one step, by comparing the entities it
+/// touched with the canvas as of the last recorded edit.
+pub fn record_canvas_history(
+    mut edits: EventReader<CanvasEdit>,
+    mut created_arrows: EventReader<CreateArrow>,
+    mut history: ResMut<CanvasHistory>,
+    app_state: Res<AppState>,
+    canvas: CanvasQuery,
+    preview: Option<Res<VersionPreview>>,
+) {
+    let arrows_pending = !created_arrows.is_empty();
+    created_arrows.clear();
+    for edit in edits.iter() {
+        history
+            .pending
+            .push(edit.ids.iter().map(|id| id.0).collect());
+    }
+    if preview.is_some() {
+        history.pending.clear();
+        return;
+    }
+    if arrows_pending {
+        // arrows are spawned in the next frame, the edit is recorded once they are there
+        return;
+    }
+    let Some(tab_id) = active_tab(&app_state) else {
+        return;
+    };
+    let up_to_date = matches!(&history.canvas, Some((canvas_tab, _)) if *canvas_tab == tab_id);
+    if up_to_date && history.pending.is_empty() {
+        return;
+    }
+    let snapshot = canvas.snapshot(|_| None);
+    for (id, image) in canvas.node_images() {
+        history.images.entry(id).or_insert_with(|| image.clone());
+    }
+    let limit = history.limit;
+    let pending = std::mem::take(&mut history.pending);
+    if let Some((_, before)) = history.canvas.take().filter(|_| up_to_date) {
+        let mut recorded = HashSet::new();
+        for ids in pending {
+            let ids: HashSet<Uuid> = ids.difference(&recorded).copied().collect();
+            if let Some(command) = diff_canvas(&before, &snapshot, &ids) {
+                history
+                    .tabs
+                    .entry(tab_id)
+                    .or_insert_with(|| CommandHistory::new(limit))
+                    .record(command);
+            }
+            recorded.extend(ids);
+        }
+    }
+    history.canvas = Some((tab_id, snapshot));
+    history.prune_images();
+}
+
+/// Apply the last edit of the active tab backwards, or the last undone one again.
+/// Only the nodes, arrows and drawings the edit changed are respawned.
+pub fn undo_canvas_edit(
+    mut commands: Commands,
+    request: Res<UndoRequest>,
+    mut history: ResMut<CanvasHistory>,
+    app_state: Res<AppState>,
+    mut ui_state: ResMut<UiState>,
+    canvas: CanvasQuery,
+    velo_nodes: Query<(Entity, &VeloNode)>,
+    arrows: Query<(Entity, &ArrowMeta)>,
+    drawings: Query<(Entity, &Drawing<(String, Color)>)>,
+    mut events: (EventWriter<CreateArrow>, EventWriter<RedrawArrow>),
+    mut assets: (
+        ResMut<Assets<CustomShadowMaterial>>,
+        ResMut<Assets<Mesh>>,
+        ResMut<Assets<CosmicFont>>,
+    ),
+    font_system_state: Res<FontSystemState>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    theme: Res<Theme>,
+    preview: Option<Res<VersionPreview>>,
+) {
+    if preview.is_some() {
+        return;
+    }
+    let Some(tab_id) = active_tab(&app_state) else {
+        return;
+    };
+    let Some(tab_history) = history.tabs.get_mut(&tab_id) else {
+        return;
+    };
+    let command = match *request {
+        UndoRequest::Undo => tab_history.undo(),
+        UndoRequest::Redo => tab_history.redo(),
+    };
+    let Some(command) = command else {
+        return;
+    };
+    info!("{:?} {}", request, command.describe());
+    *ui_state = UiState::default();
+    commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+
+    let current = canvas.snapshot(|_| None);
+    let colors = ThemeColors::new(&theme);
+    let scale_factor = windows.single().scale_factor() as f32;
+    let mut despawned = HashSet::new();
+    for change in command.changes.iter() {
+        match change.clone() {
+            CanvasChange::Node { before, after } => {
+                if let Some(before) = before {
+                    for (entity, node) in velo_nodes.iter() {
+                        if node.id.0 == before.id {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    }
+                }
+                let Some(after) = after else {
+                    continue;
+                };
+                // text, tags and visibility aren't undone, they stay as they are on the canvas
+                let node = match current.nodes.iter().find(|node| node.id == after.id) {
+                    Some(node) => JsonNode {
+                        text: node.text.clone(),
+                        tags: node.tags.clone(),
+                        visible: node.visible,
+                        ..after
+                    },
+                    None => after,
+                };
+                let id = ReflectableUuid(node.id);
+                let bg_color = colors.get(&node.bg_color).unwrap_or(theme.node_bg);
+                spawn_sprite_node(
+                    &mut commands,
+                    &mut assets.0,
+                    &mut assets.1,
+                    &theme,
+                    &mut assets.2,
+                    font_system_state.0.clone().unwrap(),
+                    scale_factor,
+                    NodeMeta {
+                        id,
+                        node_type: node.node_type,
+                        size: (node.width, node.height),
+                        position: (node.x, node.y, node.z),
+                        text: node.text.text,
+                        pair_bg_color: (node.bg_color, bg_color),
+                        image: history.images.get(&id).cloned(),
+                        text_pos: node.text.pos,
+                        is_active: false,
+                        visible: node.visible,
+                        tags: node.tags,
+                    },
+                );
+                events.1.send(RedrawArrow { id });
+            }
+            CanvasChange::Arrow { before, after } => {
+                if let Some(before) = before {
+                    let arrow = arrows.iter().find(|(entity, arrow)| {
+                        !despawned.contains(entity)
+                            && (arrow.start.id, arrow.end.id, arrow.arrow_type)
+                                == (before.start.id, before.end.id, before.arrow_type)
+                    });
+                    if let Some((entity, _)) = arrow {
+                        despawned.insert(entity);
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                if let Some(after) = after {
+                    events.0.send(CreateArrow {
+                        visible: after.visible,
+                        arrow_type: after.arrow_type,
+                        start: after.start,
+                        end: after.end,
+                    });
+                }
+            }
+            CanvasChange::Drawing { before, after } => {
+                if let Some(before) = before {
+                    for (entity, drawing) in drawings.iter() {
+                        if drawing.id == before.id {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    }
+                }
+                if let Some(after) = after {
+                    let color = colors
+                        .get(&after.drawing_color)
+                        .unwrap_or(theme.drawing_pencil_btn);
+                    spawn_drawing(
+                        &mut commands,
+                        DrawingJsonNode {
+                            x: after.x,
+                            y: after.y,
+                            z: after.z,
+                            id: after.id,
+                            points: after.points,
+                            drawing_color: (after.drawing_color, color),
+                            width: after.width,
+                        },
+                    );
+                }
+            }
+        }
+    }
+    // the canvas is compared again once the respawned entities are in place
+    history.canvas = None;
+}
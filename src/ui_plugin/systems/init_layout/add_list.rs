@@ -10,6 +10,7 @@ use bevy::{
 use bevy_pkv::PkvStore;
 
 use super::ui_helpers::ScrollingList;
+use crate::document::{load_doc_index, migrate_storage, DOC_SCHEMA_VERSION};
 use crate::resources::{AppState, LoadDocRequest};
 use crate::ui_plugin::ui_helpers::DocList;
 use crate::utils::ReflectableUuid;
@@ -24,6 +25,7 @@ pub fn add_list(
     app_state: &mut ResMut<AppState>,
     pkv: &mut ResMut<PkvStore>,
 ) -> Entity {
+    migrate_storage(pkv);
     if let Ok(last_saved) = pkv.get::<ReflectableUuid>("last_saved") {
         app_state.current_document = Some(last_saved);
         commands.insert_resource(LoadDocRequest { doc_id: last_saved });
@@ -58,8 +60,9 @@ pub fn add_list(
         ))
         .id();
 
-    if let Ok(names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-        let keys: Vec<_> = names.keys().collect();
+    let index = load_doc_index(pkv);
+    if !index.names.is_empty() {
+        let keys: Vec<_> = index.names.keys().collect();
         app_state.doc_list_ui.extend(keys);
     } else {
         let tab_id = ReflectableUuid::generate();
@@ -69,6 +72,7 @@ pub fn add_list(
             name: tab_name,
             checkpoints: VecDeque::new(),
             z_index: 1.,
+            versions: vec![],
             is_active: true,
         }];
         let doc_id = ReflectableUuid::generate();
@@ -79,6 +83,8 @@ pub fn add_list(
                 name: "Untitled".to_string(),
                 tabs,
                 tags: vec![],
+                version: DOC_SCHEMA_VERSION,
+                images: HashMap::new(),
             },
         );
         app_state.current_document = Some(doc_id);
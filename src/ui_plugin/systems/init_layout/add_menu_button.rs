@@ -22,6 +22,10 @@ pub fn add_menu_button(
         "Import From URL" => "\u{e902}",
         "Save Document to window.velo object" => "\u{e866}",
         "Share Document (copy URL to clipboard)" => "\u{e80d}",
+        "Edit Tags (selected node or document)" => "\u{e54e}",
+        "Filter By Tag" => "\u{e152}",
+        "Save Version" => "\u{e867}",
+        "Versions (preview, restore or fork)" => "\u{e889}",
         "Enable dark theme (restart is required for now)" => DARK_THEME_ICON_CODE,
         "Enable light theme (restart is required for now)" => LIGHT_THEME_ICON_CODE,
         _ => panic!("Unknown menu button tooltip label: {}", label),
@@ -7,15 +7,15 @@ use bevy_cosmic_edit::{create_cosmic_font_system, CosmicFont, CosmicFontConfig};
 use bevy_pkv::PkvStore;
 
 use super::ui_helpers::{
-    self, AddTab, BottomPanel, ButtonAction, ChangeTheme, DrawPencil, LeftPanel, LeftPanelControls,
-    LeftPanelExplorer, MainPanel, Menu, NewDoc, ParticlesEffect, Root, SaveDoc, TextPosMode,
-    TwoPointsDraw,
+    self, AddTab, BottomPanel, ButtonAction, ChangeTheme, DrawPencil, EditTags, FilterByTag,
+    LeftPanel, LeftPanelControls, LeftPanelExplorer, MainPanel, Menu, NewDoc, ParticlesEffect,
+    Root, SaveDoc, SaveVersion, ShowVersions, TextPosMode, TwoPointsDraw,
 };
 use super::{CommChannels, ExportToFile, ImportFromFile, ImportFromUrl, ShareDoc};
 use crate::canvas::arrow::components::{ArrowMode, ArrowType};
 use crate::resources::{AppState, FontSystemState};
 use crate::themes::Theme;
-use crate::utils::get_theme_key;
+use crate::utils::{get_theme_key, VELO_FONTS};
 use crate::TextPos;
 
 #[path = "../../../macros.rs"]
@@ -86,14 +86,7 @@ pub fn init_layout(
     theme: Res<Theme>,
 ) {
     // font setup
-    let font_bytes_regular = include_bytes!("../../../../assets/fonts/VictorMono-Regular.ttf");
-    let font_bytes_bold = include_bytes!("../../../../assets/fonts/VictorMono-Bold.ttf");
-    let font_bytes_italic = include_bytes!("../../../../assets/fonts/VictorMono-Italic.ttf");
-    let font_bytes_bold_italic =
-        include_bytes!("../../../../assets/fonts/VictorMono-BoldItalic.ttf");
-    let font_bytes_medium = include_bytes!("../../../../assets/fonts/VictorMono-Medium.ttf");
-    let font_bytes_semibold = include_bytes!("../../../../assets/fonts/VictorMono-SemiBold.ttf");
-    let font = Font::try_from_bytes(font_bytes_regular.to_vec()).unwrap();
+    let font = Font::try_from_bytes(VELO_FONTS[0].to_vec()).unwrap();
     let text_style = TextStyle {
         font: TextStyle::default().font,
         font_size: 14.0,
@@ -103,14 +96,7 @@ pub fn init_layout(
     let cosmic_font_config = CosmicFontConfig {
         fonts_dir_path: None,
         load_system_fonts: true,
-        font_bytes: Some(vec![
-            font_bytes_regular,
-            font_bytes_italic,
-            font_bytes_bold,
-            font_bytes_bold_italic,
-            font_bytes_medium,
-            font_bytes_semibold,
-        ]),
+        font_bytes: Some(VELO_FONTS.to_vec()),
     };
     let font_system = create_cosmic_font_system(cosmic_font_config);
     let cosmic_font_handle = cosmic_fonts.add(CosmicFont(font_system));
@@ -245,8 +231,40 @@ pub fn init_layout(
         &icon_font,
         super::SetWindowProperty,
     );
+    let edit_tags = add_menu_button(
+        &mut commands,
+        &theme,
+        "Edit Tags (selected node or document)".to_string(),
+        &icon_font,
+        EditTags,
+    );
+    let filter_by_tag = add_menu_button(
+        &mut commands,
+        &theme,
+        "Filter By Tag".to_string(),
+        &icon_font,
+        FilterByTag,
+    );
+    let save_version = add_menu_button(
+        &mut commands,
+        &theme,
+        "Save Version".to_string(),
+        &icon_font,
+        SaveVersion,
+    );
+    let show_versions = add_menu_button(
+        &mut commands,
+        &theme,
+        "Versions (preview, restore or fork)".to_string(),
+        &icon_font,
+        ShowVersions,
+    );
     commands.entity(menu).add_child(new_doc);
     commands.entity(menu).add_child(save_doc);
+    commands.entity(menu).add_child(edit_tags);
+    commands.entity(menu).add_child(filter_by_tag);
+    commands.entity(menu).add_child(save_version);
+    commands.entity(menu).add_child(show_versions);
     #[cfg(not(target_arch = "wasm32"))]
     commands.entity(menu).add_child(export_file);
     #[cfg(not(target_arch = "wasm32"))]
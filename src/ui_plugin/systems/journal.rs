@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicFont;
+use bevy_pkv::PkvStore;
+
+use super::ui_helpers::spawn_modal;
+use super::{MainPanel, ModalAction, Notifications};
+use crate::document::{clear_journal, replay_journal, store_doc};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::document::{read_journal, JOURNAL_FILE};
+use crate::resources::{
+    AppState, FontSystemState, Journal, JournalReplayRequest, LoadDocRequest, PendingJournal,
+};
+use crate::themes::Theme;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utils::data_dir;
+use crate::utils::{load_doc_to_memory, ReflectableUuid};
+use crate::UiState;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn journal_path() -> PathBuf {
+    let dir = data_dir();
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("Failed to create data directory {:?}: {}", dir, e);
+    }
+    dir.join(JOURNAL_FILE)
+}
+
+/// A journal that is still around on startup means the last session ended
+/// before its edits were stored.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn init_journal(mut commands: Commands) {
+    let path = journal_path();
+    let entries = read_journal(&path).unwrap_or_else(|e| {
+        error!("Failed to read journal {:?}: {}", path, e);
+        vec![]
+    });
+    if entries.is_empty() {
+        commands.insert_resource(Journal {
+            path,
+            pending: false,
+            images: HashSet::new(),
+            entries: 0,
+        });
+    } else {
+        commands.insert_resource(PendingJournal { path, entries });
+    }
+}
+
+pub fn discard_journal(journal: &mut Journal) {
+    if let Err(e) = clear_journal(&journal.path) {
+        error!("Failed to clear journal {:?}: {}", journal.path, e);
+    }
+    journal.pending = false;
+    journal.images.clear();
+    journal.entries = 0;
+}
+
+pub fn offer_journal_replay(
+    mut commands: Commands,
+    mut offered: Local<bool>,
+    mut ui_state: ResMut<UiState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: Res<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    if *offered || ui_state.modal_id.is_some() {
+        return;
+    }
+    let (Ok(main_panel), Some(font)) = (main_panel_query.get_single(), &font_system_state.0) else {
+        return;
+    };
+    *offered = true;
+    let id = ReflectableUuid::generate();
+    ui_state.modal_id = Some(id);
+    let entity = spawn_modal(
+        &mut commands,
+        &theme,
+        &mut cosmic_fonts,
+        font.clone(),
+        windows.single(),
+        id,
+        ModalAction::RestoreJournal,
+    );
+    commands.entity(main_panel).add_child(entity);
+}
+
+pub fn replay_pending_journal(
+    mut commands: Commands,
+    request: Res<JournalReplayRequest>,
+    pending: Res<PendingJournal>,
+    mut app_state: ResMut<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    notifications: Res<Notifications>,
+) {
+    if request.replay {
+        let doc_ids: HashSet<_> = pending.entries.iter().map(|entry| entry.doc_id).collect();
+        for doc_id in doc_ids {
+            load_doc_to_memory(doc_id, &mut app_state, &mut pkv, &notifications);
+            let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+                warn!("Journal refers to unknown document {:?}", doc_id);
+                continue;
+            };
+            if replay_journal(doc, &pending.entries) {
+                if let Err(e) = store_doc(&mut pkv, doc) {
+                    // kept as unsaved, autosave stores it again
+                    notifications.error(format!("Failed to store \"{}\": {}", doc.name, e));
+                    app_state.unsaved_docs.insert(doc_id);
+                }
+                if app_state.current_document == Some(doc_id) {
+                    commands.insert_resource(LoadDocRequest { doc_id });
+                }
+            }
+        }
+    }
+    let mut journal = Journal {
+        path: pending.path.clone(),
+        pending: false,
+        images: HashSet::new(),
+        entries: 0,
+    };
+    discard_journal(&mut journal);
+    commands.insert_resource(journal);
+    commands.remove_resource::<PendingJournal>();
+    commands.remove_resource::<JournalReplayRequest>();
+}
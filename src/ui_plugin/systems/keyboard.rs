@@ -8,7 +8,6 @@ use bevy::{
 use bevy_cosmic_edit::{
     get_cosmic_text, get_text_spans, ActiveEditor, CosmicEdit, CosmicEditHistory, EditHistoryItem,
 };
-use bevy_prototype_lyon::prelude::{PathBuilder, ShapeBundle, Stroke};
 use cosmic_text::Edit;
 #[cfg(not(target_arch = "wasm32"))]
 use image::*;
@@ -17,14 +16,18 @@ use std::{collections::VecDeque, convert::TryInto};
 use uuid::Uuid;
 
 use crate::{
+    canvas::arrow::components::ArrowMeta,
     components::MainCamera,
-    resources::{LoadTabRequest, SaveTabRequest},
+    resources::{SaveTabRequest, UndoRequest},
     themes::Theme,
     utils::{bevy_color_to_cosmic, ReflectableUuid},
     AddRect, JsonNode, JsonNodeText, NodeType, UiState,
 };
 
-use super::ui_helpers::{Drawing, EditableText, InteractiveNode, VeloNode};
+use super::ui_helpers::{spawn_drawing, Drawing, EditableText, VeloNode};
+use super::{CanvasEdit, DrawingJsonNode};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resources::CopyImageRequest;
 use crate::resources::{AppState, SaveDocRequest};
 
 #[path = "../../macros.rs"]
@@ -51,6 +54,8 @@ pub fn keyboard_input_system(
         With<Drawing<(String, Color)>>,
     >,
     velo_node_query: Query<(Entity, &VeloNode)>,
+    arrows: Query<(Entity, &ArrowMeta)>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     let camera_transform = camera_proj_query.single_mut();
     let x = camera_transform.translation.x;
@@ -62,7 +67,12 @@ pub fn keyboard_input_system(
     #[cfg(not(target_os = "macos"))]
     let command = input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
     let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
-    if command && input.just_pressed(KeyCode::C) {
+    if command && shift && input.just_pressed(KeyCode::C) {
+        #[cfg(not(target_arch = "wasm32"))]
+        commands.insert_resource(CopyImageRequest {
+            scale: scale_factor as f32,
+        });
+    } else if command && input.just_pressed(KeyCode::C) {
         if ui_state.entity_to_draw_selected.is_some() {
             for (drawing, gt) in &mut drawing_q.iter_mut() {
                 if drawing.id == ui_state.entity_to_draw_selected.unwrap() {
@@ -77,29 +87,20 @@ pub fn keyboard_input_system(
         insert_from_clipboard(&mut images, &mut events, x, y, scale_factor, &theme);
 
         if let Some((copied_drawing, z_index)) = copied_drawing.clone() {
-            let mut path_builder = PathBuilder::new();
-            let mut points_iter = copied_drawing.points.iter();
-            let start = points_iter.next().unwrap();
-            path_builder.move_to(*start);
-            path_builder.line_to(*start);
-            for point in points_iter {
-                path_builder.line_to(*point);
-            }
-            let path = path_builder.build();
-            commands.spawn((
-                ShapeBundle {
-                    path,
-                    transform: Transform::from_xyz(x, y, z_index + 0.01),
-                    ..Default::default()
-                },
-                Stroke::new(copied_drawing.drawing_color.1, 2.),
-                Drawing {
-                    id: ReflectableUuid::generate(),
-                    points: copied_drawing.points.clone(),
+            let id = ReflectableUuid::generate();
+            spawn_drawing(
+                &mut commands,
+                DrawingJsonNode {
+                    x,
+                    y,
+                    z: z_index + 0.01,
+                    id,
+                    points: copied_drawing.points,
                     drawing_color: copied_drawing.drawing_color,
+                    width: 2.,
                 },
-                InteractiveNode,
-            ));
+            );
+            canvas_edits.send(CanvasEdit { ids: vec![id] });
         }
     } else if command && shift && input.just_pressed(KeyCode::S) {
         commands.insert_resource(SaveDocRequest {
@@ -115,15 +116,19 @@ pub fn keyboard_input_system(
                 });
             }
         }
-    } else if command && input.just_pressed(KeyCode::L) {
-        if let Some(current_doc) = app_state.docs.get(&app_state.current_document.unwrap()) {
-            if let Some(active_tab) = current_doc.tabs.iter().find(|t| t.is_active) {
-                commands.insert_resource(LoadTabRequest {
-                    doc_id: app_state.current_document.unwrap(),
-                    tab_id: active_tab.id,
-                    drop_last_checkpoint: true,
-                });
-            }
+    } else if command && (input.just_pressed(KeyCode::Z) || input.just_pressed(KeyCode::Y)) {
+        // text being edited has its own undo history
+        let editing_text = ui_state.entity_to_edit.is_some()
+            || ui_state.tab_to_edit.is_some()
+            || ui_state.doc_to_edit.is_some()
+            || ui_state.search_box_to_edit.is_some()
+            || ui_state.modal_id.is_some();
+        if !editing_text {
+            commands.insert_resource(if shift || input.just_pressed(KeyCode::Y) {
+                UndoRequest::Redo
+            } else {
+                UndoRequest::Undo
+            });
         }
     } else if command && input.just_pressed(KeyCode::P) {
         events.send(AddRect {
@@ -186,6 +191,12 @@ pub fn keyboard_input_system(
                     commands.entity(entity).despawn_recursive();
                 }
             }
+            for (entity, arrow) in arrows.iter() {
+                if arrow.start.id == id || arrow.end.id == id {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            canvas_edits.send(CanvasEdit { ids: vec![id] });
         }
     } else {
         for (editable_text, mut cosmic_edit, mut cosmit_edit_history) in
@@ -221,13 +232,21 @@ pub fn keyboard_input_system(
                 }
                 if let Some(doc_id) = ui_state.doc_to_edit {
                     let doc = app_state.docs.get_mut(&doc_id).unwrap();
-                    doc.name = get_cosmic_text(cosmic_edit.editor.buffer())
+                    let name = get_cosmic_text(cosmic_edit.editor.buffer());
+                    if doc.name != name {
+                        doc.name = name;
+                        app_state.unsaved_docs.insert(doc_id);
+                    }
                 }
                 if let Some(tab_id) = ui_state.tab_to_edit {
                     if let Some(doc_id) = app_state.current_document {
                         let doc = app_state.docs.get_mut(&doc_id).unwrap();
                         if let Some(tab) = doc.tabs.iter_mut().find(|x| x.id == tab_id) {
-                            tab.name = get_cosmic_text(cosmic_edit.editor.buffer())
+                            let name = get_cosmic_text(cosmic_edit.editor.buffer());
+                            if tab.name != name {
+                                tab.name = name;
+                                app_state.unsaved_docs.insert(doc_id);
+                            }
                         }
                     }
                 }
@@ -253,38 +272,53 @@ pub fn insert_from_clipboard(
                 image.bytes.into_owned(),
             )
             .unwrap();
-            let width = image.width();
-            let height = image.height();
-            let size: Extent3d = Extent3d {
-                width,
-                height,
-                ..Default::default()
-            };
-            let image = Image::new(
-                size,
-                TextureDimension::D2,
-                image.to_vec(),
-                TextureFormat::Rgba8UnormSrgb,
-            );
-            let image = images.add(image);
-            events.send(AddRect {
-                node: JsonNode {
-                    visible: true,
-                    id: Uuid::new_v4(),
-                    node_type: crate::NodeType::Rect,
-                    x,
-                    y,
-                    width: width as f32 / scale_factor as f32,
-                    height: height as f32 / scale_factor as f32,
-                    text: crate::JsonNodeText {
-                        text: "".to_string(),
-                        pos: crate::TextPos::Center,
-                    },
-                    bg_color: pair_struct!(theme.clipboard_image_bg),
-                    z: 0.,
-                },
-                image: Some(image),
-            });
+            insert_image(images, events, image, x, y, scale_factor, theme);
         }
     }
 }
+
+/// Add a node showing `image` at its actual size on screen, centered on `x`, `y`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn insert_image(
+    images: &mut ResMut<Assets<Image>>,
+    events: &mut EventWriter<AddRect<(String, Color)>>,
+    image: RgbaImage,
+    x: f32,
+    y: f32,
+    scale_factor: f64,
+    theme: &Res<Theme>,
+) {
+    let width = image.width();
+    let height = image.height();
+    let size: Extent3d = Extent3d {
+        width,
+        height,
+        ..Default::default()
+    };
+    let image = Image::new(
+        size,
+        TextureDimension::D2,
+        image.to_vec(),
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let image = images.add(image);
+    events.send(AddRect {
+        node: JsonNode {
+            visible: true,
+            id: Uuid::new_v4(),
+            node_type: crate::NodeType::Rect,
+            x,
+            y,
+            width: width as f32 / scale_factor as f32,
+            height: height as f32 / scale_factor as f32,
+            text: crate::JsonNodeText {
+                text: "".to_string(),
+                pos: crate::TextPos::Center,
+            },
+            bg_color: pair_struct!(theme.clipboard_image_bg),
+            z: 0.,
+            tags: vec![],
+        },
+        image: Some(image),
+    });
+}
@@ -5,25 +5,25 @@ use bevy::{
     window::PrimaryWindow,
 };
 use bevy_cosmic_edit::CosmicFont;
-use bevy_prototype_lyon::prelude::{PathBuilder, ShapeBundle, Stroke};
 
 use super::{
     ui_helpers::{
-        add_tab, spawn_sprite_node, BottomPanel, Drawing, InteractiveNode, NodeMeta, TabContainer,
+        add_tab, spawn_drawing, spawn_sprite_node, BottomPanel, Drawing, NodeMeta, TabContainer,
         VeloNode,
     },
-    DeleteDoc, DeleteTab, DrawingJsonNode,
+    DeleteDoc, DeleteTab, DrawingJsonNode, Notifications,
 };
 use crate::{canvas::arrow::events::CreateArrow, utils::load_doc_to_memory};
 use crate::{
     canvas::{arrow::components::ArrowMeta, shadows::CustomShadowMaterial},
-    resources::{FontSystemState, LoadTabRequest},
+    document::preview_version,
+    resources::{FontSystemState, LoadTabRequest, VersionPreview},
     themes::Theme,
 };
 
 use crate::resources::{AppState, LoadDocRequest};
 use crate::utils::ReflectableUuid;
-use crate::{JsonNode, UiState};
+use crate::UiState;
 use bevy_pkv::PkvStore;
 use image::{load_from_memory_with_format, ImageFormat};
 use serde_json::{Map, Value};
@@ -51,17 +51,29 @@ pub fn load_doc(
     mut bottom_panel: Query<Entity, With<BottomPanel>>,
     mut pkv: ResMut<PkvStore>,
     asset_server: Res<AssetServer>,
-    mut tabs_query: Query<Entity, With<TabContainer>>,
+    mut tabs_query: Query<(Entity, &TabContainer)>,
     mut delete_doc: Query<(&mut Visibility, &DeleteDoc), With<DeleteDoc>>,
     theme: Res<Theme>,
     mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
     font_system_state: ResMut<FontSystemState>,
     windows: Query<&Window, With<PrimaryWindow>>,
+    notifications: Res<Notifications>,
 ) {
     let primary_window = windows.single();
     let scale_factor = primary_window.scale_factor() as f32;
     let bottom_panel = bottom_panel.single_mut();
     let doc_id = request.doc_id;
+    load_doc_to_memory(doc_id, &mut app_state, &mut pkv, &notifications);
+    let Some(doc) = app_state.docs.get(&doc_id) else {
+        // keep showing the document whose tabs are still on screen
+        let shown_tab = tabs_query.iter().next().map(|(_, tab)| tab.id);
+        app_state.current_document = app_state
+            .docs
+            .iter()
+            .find(|(_, doc)| doc.tabs.iter().any(|tab| Some(tab.id) == shown_tab))
+            .map(|(id, _)| *id);
+        return;
+    };
     for (mut visibility, doc) in delete_doc.iter_mut() {
         if doc.id == doc_id {
             *visibility = Visibility::Visible;
@@ -69,13 +81,12 @@ pub fn load_doc(
             *visibility = Visibility::Hidden;
         }
     }
-    load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
 
     let mut tabs = vec![];
-    for entity in tabs_query.iter_mut() {
+    for (entity, _) in tabs_query.iter_mut() {
         commands.entity(entity).despawn_recursive();
     }
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter() {
+    for tab in doc.tabs.iter() {
         let tab_view: Entity = add_tab(
             &mut commands,
             &mut cosmic_fonts,
@@ -92,7 +103,7 @@ pub fn load_doc(
             commands.insert_resource(LoadTabRequest {
                 doc_id,
                 tab_id: tab.id,
-                drop_last_checkpoint: false,
+                version: None,
             });
         }
     }
@@ -104,7 +115,7 @@ pub fn load_tab(
     mut old_arrows: Query<Entity, With<ArrowMeta>>,
     mut old_drawings: Query<Entity, With<Drawing<(String, Color)>>>,
     request: Res<LoadTabRequest>,
-    mut app_state: ResMut<AppState>,
+    app_state: Res<AppState>,
     mut ui_state: ResMut<UiState>,
     mut commands: Commands,
     mut res_images: ResMut<Assets<Image>>,
@@ -145,27 +156,43 @@ pub fn load_tab(
             *visibility = Visibility::Hidden;
         }
     }
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter_mut() {
+    if request.version.is_none() {
+        commands.remove_resource::<VersionPreview>();
+    }
+    let doc = app_state.docs.get(&doc_id).unwrap();
+    for tab in doc.tabs.iter() {
         if tab.id == request.tab_id {
-            if tab.checkpoints.is_empty() {
-                break;
-            }
-
-            let json = if request.drop_last_checkpoint && tab.checkpoints.len() > 1 {
-                tab.checkpoints.pop_back().unwrap()
-            } else {
-                tab.checkpoints.back().unwrap().clone()
+            let snapshot = match request.version {
+                Some(version_id) => {
+                    let Some(version) = tab.versions.iter().find(|v| v.id == version_id) else {
+                        break;
+                    };
+                    let current = tab.checkpoints.back().cloned().unwrap_or_default();
+                    let (preview, diff) = preview_version(&version.snapshot, &current);
+                    commands.insert_resource(VersionPreview {
+                        tab_id: tab.id,
+                        version_id,
+                        name: version.name.clone(),
+                        diff,
+                    });
+                    preview
+                }
+                None => {
+                    let Some(snapshot) = tab.checkpoints.back().cloned() else {
+                        break;
+                    };
+                    snapshot
+                }
             };
-            let mut json: Value = serde_json::from_str(&json).unwrap();
-            let images = json["images"].as_object().unwrap();
-            let nodes = json["nodes"].as_array().unwrap();
-            for node in nodes.iter() {
-                let json_node: JsonNode<String> = serde_json::from_value(node.clone()).unwrap();
-                let image: Option<Handle<Image>> = match images.get(&json_node.id.to_string()) {
+            for json_node in snapshot.nodes {
+                let image: Option<Handle<Image>> = match snapshot
+                    .images
+                    .get(&json_node.id.to_string())
+                    .and_then(|hash| doc.images.get(hash))
+                {
                     Some(image) => {
-                        let image_bytes = general_purpose::STANDARD
-                            .decode(image.as_str().unwrap().as_bytes())
-                            .unwrap();
+                        let image_bytes =
+                            general_purpose::STANDARD.decode(image.as_bytes()).unwrap();
                         let img =
                             load_from_memory_with_format(&image_bytes, ImageFormat::Png).unwrap();
                         let size: Extent3d = Extent3d {
@@ -212,13 +239,12 @@ pub fn load_tab(
                         text_pos: json_node.text.pos,
                         is_active: false,
                         visible: json_node.visible,
+                        tags: json_node.tags,
                     },
                 );
             }
 
-            let arrows = json["arrows"].as_array_mut().unwrap();
-            for arrow in arrows.iter() {
-                let arrow_meta: ArrowMeta = serde_json::from_value(arrow.clone()).unwrap();
+            for arrow_meta in snapshot.arrows {
                 create_arrow.send(CreateArrow {
                     visible: arrow_meta.visible,
                     start: arrow_meta.start,
@@ -226,19 +252,7 @@ pub fn load_tab(
                     arrow_type: arrow_meta.arrow_type,
                 });
             }
-            let drawings = json["drawings"].as_array_mut().unwrap();
-            for drawing in drawings.iter() {
-                let drawing_json_node: DrawingJsonNode<String> =
-                    serde_json::from_value(drawing.clone()).unwrap();
-                let mut path_builder = PathBuilder::new();
-                let mut points_iter = drawing_json_node.points.iter();
-                let start = points_iter.next().unwrap();
-                path_builder.move_to(*start);
-                path_builder.line_to(*start);
-                for point in points_iter {
-                    path_builder.line_to(*point);
-                }
-                let path = path_builder.build();
+            for drawing_json_node in snapshot.drawings {
                 let theme_color = local_theme
                     .as_ref()
                     .unwrap()
@@ -248,24 +262,18 @@ pub fn load_tab(
                     drawing_json_node.drawing_color,
                     serde_json::from_value(theme_color.clone()).unwrap(),
                 );
-                commands.spawn((
-                    ShapeBundle {
-                        path,
-                        transform: Transform::from_xyz(
-                            drawing_json_node.x,
-                            drawing_json_node.y,
-                            drawing_json_node.z,
-                        ),
-                        ..Default::default()
-                    },
-                    Stroke::new(pair_color.1, 2.),
-                    Drawing {
+                spawn_drawing(
+                    &mut commands,
+                    DrawingJsonNode {
+                        x: drawing_json_node.x,
+                        y: drawing_json_node.y,
+                        z: drawing_json_node.z,
                         id: drawing_json_node.id,
-                        points: drawing_json_node.points.clone(),
+                        points: drawing_json_node.points,
                         drawing_color: pair_color,
+                        width: drawing_json_node.width,
                     },
-                    InteractiveNode,
-                ));
+                );
             }
             break;
         }
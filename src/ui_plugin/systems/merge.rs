@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use bevy_pkv::PkvStore;
+
+use super::ui_helpers::{spawn_merge_conflicts, MergeConflictButton, MergeConflictsPanel};
+use super::{MainPanel, Notifications};
+use crate::document::{merge_docs, resolve_conflict};
+use crate::resources::{
+    AppState, CanvasHistory, LoadDocRequest, LoadTabRequest, MergeConflicts, MergeDocRequest,
+    ResolveConflictRequest,
+};
+use crate::themes::Theme;
+use crate::utils::load_doc_to_memory;
+
+pub fn should_merge_doc(request: Option<Res<MergeDocRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn remove_merge_doc_request(world: &mut World) {
+    world.remove_resource::<MergeDocRequest>().unwrap();
+}
+
+pub fn should_resolve_conflict(request: Option<Res<ResolveConflictRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn remove_resolve_conflict_request(world: &mut World) {
+    world.remove_resource::<ResolveConflictRequest>().unwrap();
+}
+
+/// Merge an imported copy into the document with the same id and open the result.
+pub fn merge_doc(
+    mut commands: Commands,
+    request: Res<MergeDocRequest>,
+    mut app_state: ResMut<AppState>,
+    mut pkv: ResMut<PkvStore>,
+    mut history: ResMut<CanvasHistory>,
+    notifications: Res<Notifications>,
+) {
+    let doc_id = request.doc.id;
+    load_doc_to_memory(doc_id, &mut app_state, &mut pkv, &notifications);
+    let Some(local) = app_state.docs.get(&doc_id) else {
+        notifications.error(format!("Failed to merge \"{}\"", request.doc.name));
+        return;
+    };
+    let (merged, conflicts) = merge_docs(local, &request.doc);
+    for tab in merged.tabs.iter() {
+        // recorded edits don't apply to the merged canvas
+        history.remove_tab(&tab.id);
+    }
+    notifications.info(match conflicts.len() {
+        0 => format!("Merged \"{}\"", merged.name),
+        count => format!("Merged \"{}\" with {} conflicts", merged.name, count),
+    });
+    app_state.docs.insert(doc_id, merged);
+    app_state.current_document = Some(doc_id);
+    app_state.unsaved_docs.insert(doc_id);
+    app_state.doc_list_ui.insert(doc_id);
+    match conflicts.is_empty() {
+        true => commands.remove_resource::<MergeConflicts>(),
+        false => commands.insert_resource(MergeConflicts { doc_id, conflicts }),
+    }
+    commands.insert_resource(LoadDocRequest { doc_id });
+}
+
+pub fn merge_conflict_button_handler(
+    mut commands: Commands,
+    query: Query<(&Interaction, &MergeConflictButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Pressed {
+            commands.insert_resource(ResolveConflictRequest {
+                index: button.index,
+                side: button.side,
+            });
+        }
+    }
+}
+
+/// Apply the picked side of a conflict and show it if its tab is on the canvas.
+pub fn resolve_merge_conflict(
+    mut commands: Commands,
+    request: Res<ResolveConflictRequest>,
+    mut app_state: ResMut<AppState>,
+    mut history: ResMut<CanvasHistory>,
+    mut merge_conflicts: ResMut<MergeConflicts>,
+) {
+    if request.index >= merge_conflicts.conflicts.len() {
+        return;
+    }
+    let doc_id = merge_conflicts.doc_id;
+    let conflict = merge_conflicts.conflicts.remove(request.index);
+    if merge_conflicts.conflicts.is_empty() {
+        commands.remove_resource::<MergeConflicts>();
+    }
+    let current_document = app_state.current_document;
+    let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+        return;
+    };
+    resolve_conflict(doc, &conflict, request.side);
+    let active = doc
+        .tabs
+        .iter()
+        .any(|tab| tab.id == conflict.tab_id && tab.is_active);
+    app_state.unsaved_docs.insert(doc_id);
+    history.remove_tab(&conflict.tab_id);
+    if active && current_document == Some(doc_id) {
+        commands.insert_resource(LoadTabRequest {
+            doc_id,
+            tab_id: conflict.tab_id,
+            version: None,
+        });
+    }
+}
+
+/// Shows the conflicts left to resolve after a merge.
+pub fn update_merge_conflicts_panel(
+    mut commands: Commands,
+    merge_conflicts: Option<Res<MergeConflicts>>,
+    panels: Query<Entity, With<MergeConflictsPanel>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    theme: Res<Theme>,
+) {
+    let changed = match &merge_conflicts {
+        Some(merge_conflicts) => merge_conflicts.is_changed(),
+        None => !panels.is_empty(),
+    };
+    if !changed {
+        return;
+    }
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(merge_conflicts) = merge_conflicts {
+        let panel = spawn_merge_conflicts(&mut commands, &theme, &merge_conflicts.conflicts);
+        commands.entity(main_panel_query.single()).add_child(panel);
+    }
+}
@@ -1,7 +1,7 @@
-use std::collections::HashMap;
-use std::fs::canonicalize;
+use std::collections::VecDeque;
 use std::path::PathBuf;
 
+use async_channel::Sender;
 use bevy::prelude::*;
 use bevy::tasks::IoTaskPool;
 use bevy_cosmic_edit::{get_cosmic_text, ActiveEditor, CosmicEdit};
@@ -10,10 +10,19 @@ use cosmic_text::Edit;
 use linkify::{LinkFinder, LinkKind};
 
 use super::ui_helpers::{ModalCancel, ModalConfirm, ModalTop};
-use super::{CommChannels, EditableText, ModalAction, TabContainer};
-use crate::components::Doc;
-use crate::resources::{AppState, LoadDocRequest, LoadTabRequest, SaveDocRequest};
-use crate::utils::ReflectableUuid;
+use super::{CommChannels, EditableText, ModalAction, Notifications, TabContainer};
+use crate::components::Tab;
+use crate::document::{
+    doc_from_bundle, doc_from_json, gc_images, is_bundle, load_doc_index, parse_tags,
+    remove_stored_doc, repair_doc, TabSnapshot,
+};
+use crate::import::diagram_to_snapshot;
+use crate::resources::{
+    AppState, CanvasHistory, ExitRequest, JournalReplayRequest, LoadDocRequest, LoadTabRequest,
+    MergeDocRequest, SaveDocRequest, SaveTabRequest, SaveVersionRequest, SetNodeTagsRequest,
+};
+use crate::themes::Theme;
+use crate::utils::{response_bytes, ReflectableUuid};
 use crate::UiState;
 
 pub fn cancel_modal(
@@ -29,6 +38,9 @@ pub fn cancel_modal(
         if let Interaction::Pressed = interaction {
             for (entity, path_modal_top) in query.iter() {
                 if path_modal_cancel.id == path_modal_top.id {
+                    if path_modal_top.action == ModalAction::RestoreJournal {
+                        commands.insert_resource(JournalReplayRequest { replay: false });
+                    }
                     commands.entity(entity).despawn_recursive();
                     state.modal_id = None;
                 }
@@ -41,10 +53,16 @@ fn delete_doc(
     app_state: &mut ResMut<AppState>,
     commands: &mut Commands,
     pkv: &mut ResMut<PkvStore>,
+    history: &mut ResMut<CanvasHistory>,
 ) {
     let current_document = app_state.current_document.unwrap();
     let id_to_remove = current_document;
-    app_state.docs.remove(&current_document);
+    if let Some(doc) = app_state.docs.remove(&current_document) {
+        for tab in doc.tabs.iter() {
+            history.remove_tab(&tab.id);
+        }
+    }
+    app_state.unsaved_docs.remove(&current_document);
     remove_from_storage(pkv, id_to_remove, app_state.current_document.unwrap());
     app_state.current_document = app_state.docs.keys().next().cloned();
     app_state.doc_list_ui.remove(&id_to_remove);
@@ -58,7 +76,7 @@ fn delete_doc(
             let pool = IoTaskPool::get();
             let id_to_remove = std::sync::Arc::new(id_to_remove);
             pool.spawn(async move {
-                let _ = super::clear_doc_index(&index, &id_to_remove.0);
+                let _ = crate::document::clear_doc_index(&index, &id_to_remove.0);
             })
             .detach();
         }
@@ -69,6 +87,7 @@ fn delete_tab(
     app_state: &mut ResMut<AppState>,
     commands: &mut Commands,
     query_container: &mut Query<(Entity, &TabContainer), With<TabContainer>>,
+    history: &mut ResMut<CanvasHistory>,
 ) {
     let current_document = app_state.current_document.unwrap();
     let tab_id = app_state
@@ -100,12 +119,12 @@ fn delete_tab(
         .iter()
         .position(|x| x.is_active)
         .unwrap();
-    app_state
-        .docs
-        .get_mut(&current_document)
-        .unwrap()
-        .tabs
-        .remove(index);
+    let doc = app_state.docs.get_mut(&current_document).unwrap();
+    let tab = doc.tabs.remove(index);
+    gc_images(doc);
+    app_state.unsaved_tabs.remove(&tab.id);
+    history.remove_tab(&tab.id);
+    app_state.unsaved_docs.insert(current_document);
     let last_tab = app_state
         .docs
         .get_mut(&current_document)
@@ -117,7 +136,7 @@ fn delete_tab(
     commands.insert_resource(LoadTabRequest {
         doc_id: current_document,
         tab_id: last_tab.id,
-        drop_last_checkpoint: false,
+        version: None,
     });
 }
 
@@ -126,21 +145,48 @@ pub fn load_doc_handler(
     mut app_state: ResMut<AppState>,
     comm_channels: Res<CommChannels>,
     pkv: Res<PkvStore>,
+    notifications: Res<Notifications>,
+    theme: Res<Theme>,
 ) {
-    if comm_channels.rx.is_empty() {
+    let Ok(r) = comm_channels.rx.try_recv() else {
+        return;
+    };
+    if let Some(snapshot) = diagram_to_snapshot(&r, &theme) {
+        match snapshot {
+            Ok(snapshot) => import_tab(snapshot, &mut app_state, &mut commands),
+            Err(e) => notifications.error(format!("Failed to import diagram: {}", e)),
+        }
         return;
     }
-    let r = comm_channels
-        .rx
-        .try_recv()
-        .expect("Failed to receive document string");
-    let import_document: Doc = serde_json::from_str(&r).expect("Failed to deserialize document");
-    if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-        if docs.contains_key(&import_document.id) {
+    let mut import_document = match doc_from_json(&r) {
+        Ok(doc) => doc,
+        Err(e) => {
+            notifications.error(format!("Failed to import document: {}", e));
             return;
         }
+    };
+    let issues = repair_doc(&mut import_document);
+    notifications.repaired(&import_document.name, &issues);
+    if app_state.docs.contains_key(&import_document.id)
+        || load_doc_index(&pkv).names.contains_key(&import_document.id)
+    {
+        // another copy of a document in the list, merged once the canvas is saved
+        if let Some(doc_id) = app_state.current_document {
+            let active_tab = app_state
+                .docs
+                .get(&doc_id)
+                .and_then(|doc| doc.tabs.iter().find(|tab| tab.is_active).map(|tab| tab.id));
+            if let Some(tab_id) = active_tab {
+                commands.insert_resource(SaveTabRequest { doc_id, tab_id });
+            }
+        }
+        commands.insert_resource(MergeDocRequest {
+            doc: import_document,
+        });
+        return;
     }
     app_state.current_document = Some(import_document.id);
+    app_state.unsaved_docs.insert(import_document.id);
     app_state.doc_list_ui.insert(import_document.id);
     app_state
         .docs
@@ -150,6 +196,39 @@ pub fn load_doc_handler(
     });
 }
 
+/// Add an imported diagram to the current document as a new active tab.
+fn import_tab(snapshot: TabSnapshot, app_state: &mut ResMut<AppState>, commands: &mut Commands) {
+    let Some(current_document) = app_state.current_document else {
+        return;
+    };
+    let Some(doc) = app_state.docs.get_mut(&current_document) else {
+        return;
+    };
+    for tab in doc.tabs.iter_mut() {
+        if tab.is_active {
+            commands.insert_resource(SaveTabRequest {
+                tab_id: tab.id,
+                doc_id: current_document,
+            });
+        }
+        tab.is_active = false;
+    }
+    let z_index = snapshot.nodes.iter().map(|node| node.z).fold(1., f32::max);
+    let name = "Tab ".to_string() + &(doc.tabs.len() + 1).to_string();
+    doc.tabs.push(Tab {
+        id: ReflectableUuid::generate(),
+        name,
+        checkpoints: VecDeque::from([snapshot]),
+        is_active: true,
+        z_index,
+        versions: vec![],
+    });
+    app_state.unsaved_docs.insert(current_document);
+    commands.insert_resource(LoadDocRequest {
+        doc_id: current_document,
+    });
+}
+
 pub fn confirm_modal(
     mut commands: Commands,
     mut interaction_query: Query<
@@ -164,6 +243,8 @@ pub fn confirm_modal(
     input: Res<Input<KeyCode>>,
     mut query_path: Query<(&CosmicEdit, &EditableText), With<EditableText>>,
     comm_channels: Res<CommChannels>,
+    notifications: Res<Notifications>,
+    mut history: ResMut<CanvasHistory>,
 ) {
     for (interaction, path_modal_confirm) in interaction_query.iter_mut() {
         if let Interaction::Pressed = interaction {
@@ -181,34 +262,31 @@ pub fn confirm_modal(
                                     break;
                                 }
                                 ModalAction::LoadFromFile => {
-                                    if let Ok(path) = canonicalize(PathBuf::from(text.trim())) {
-                                        let json = std::fs::read_to_string(path)
-                                            .expect("Error reading document from file");
-                                        let cc = comm_channels.tx.clone();
-                                        cc.try_send(json).unwrap()
-                                    }
+                                    load_from_file(&text, &comm_channels, &notifications);
                                 }
                                 ModalAction::LoadFromUrl => {
-                                    let pool = IoTaskPool::get();
-                                    let url = text.trim();
-                                    let mut finder = LinkFinder::new();
-                                    finder.kinds(&[LinkKind::Url]);
-                                    let links: Vec<_> = finder.links(url).collect();
-                                    if links.len() == 1 {
-                                        let url = links.first().unwrap().as_str().to_owned();
-                                        let cc = comm_channels.tx.clone();
-                                        let task = pool.spawn(async move {
-                                            let request = ehttp::Request::get(url);
-                                            ehttp::fetch(request, move |result| {
-                                                let json_string = result.unwrap().text().unwrap();
-                                                cc.try_send(json_string).unwrap();
-                                            });
-                                        });
-                                        task.detach();
-                                    }
+                                    load_from_url(&text, &comm_channels, &notifications);
                                 }
                                 ModalAction::DeleteDocument => {}
                                 ModalAction::DeleteTab => {}
+                                ModalAction::SaveAndExit => {}
+                                ModalAction::RestoreJournal => {}
+                                ModalAction::EditDocTags(_)
+                                | ModalAction::EditNodeTags(_)
+                                | ModalAction::FilterByTag => {
+                                    confirm_tags(
+                                        &path_modal_confirm.action,
+                                        &text,
+                                        &mut app_state,
+                                        &mut commands,
+                                    );
+                                }
+                                ModalAction::SaveVersion => {
+                                    commands.insert_resource(SaveVersionRequest {
+                                        name: text.trim().to_string(),
+                                    });
+                                }
+                                ModalAction::Versions => {}
                             }
                         }
                     }
@@ -217,11 +295,27 @@ pub fn confirm_modal(
                         ModalAction::LoadFromFile => {}
                         ModalAction::LoadFromUrl => {}
                         ModalAction::DeleteDocument => {
-                            delete_doc(&mut app_state, &mut commands, &mut pkv);
+                            delete_doc(&mut app_state, &mut commands, &mut pkv, &mut history);
                         }
                         ModalAction::DeleteTab => {
-                            delete_tab(&mut app_state, &mut commands, &mut tab_query_container);
+                            delete_tab(
+                                &mut app_state,
+                                &mut commands,
+                                &mut tab_query_container,
+                                &mut history,
+                            );
+                        }
+                        ModalAction::SaveAndExit => {
+                            commands.insert_resource(ExitRequest);
                         }
+                        ModalAction::RestoreJournal => {
+                            commands.insert_resource(JournalReplayRequest { replay: true });
+                        }
+                        ModalAction::EditDocTags(_) => {}
+                        ModalAction::EditNodeTags(_) => {}
+                        ModalAction::FilterByTag => {}
+                        ModalAction::SaveVersion => {}
+                        ModalAction::Versions => {}
                     }
                 }
                 commands.entity(entity).despawn_recursive();
@@ -245,34 +339,31 @@ pub fn confirm_modal(
                                 break;
                             }
                             ModalAction::LoadFromFile => {
-                                if let Ok(path) = canonicalize(PathBuf::from(text.trim())) {
-                                    let json = std::fs::read_to_string(path)
-                                        .expect("Error reading document from file");
-                                    let cc = comm_channels.tx.clone();
-                                    cc.try_send(json).unwrap()
-                                }
+                                load_from_file(&text, &comm_channels, &notifications);
                             }
                             ModalAction::LoadFromUrl => {
-                                let pool = IoTaskPool::get();
-                                let url = text.trim();
-                                let mut finder = LinkFinder::new();
-                                finder.kinds(&[LinkKind::Url]);
-                                let links: Vec<_> = finder.links(url).collect();
-                                if links.len() == 1 {
-                                    let url = links.first().unwrap().as_str().to_owned();
-                                    let cc = comm_channels.tx.clone();
-                                    let task = pool.spawn(async move {
-                                        let request = ehttp::Request::get(url);
-                                        ehttp::fetch(request, move |result| {
-                                            let json_string = result.unwrap().text().unwrap();
-                                            cc.try_send(json_string).unwrap();
-                                        });
-                                    });
-                                    task.detach();
-                                }
+                                load_from_url(&text, &comm_channels, &notifications);
                             }
                             ModalAction::DeleteDocument => {}
                             ModalAction::DeleteTab => {}
+                            ModalAction::SaveAndExit => {}
+                            ModalAction::RestoreJournal => {}
+                            ModalAction::EditDocTags(_)
+                            | ModalAction::EditNodeTags(_)
+                            | ModalAction::FilterByTag => {
+                                confirm_tags(
+                                    &path_modal_top.action,
+                                    &text,
+                                    &mut app_state,
+                                    &mut commands,
+                                );
+                            }
+                            ModalAction::SaveVersion => {
+                                commands.insert_resource(SaveVersionRequest {
+                                    name: text.trim().to_string(),
+                                });
+                            }
+                            ModalAction::Versions => {}
                         }
                     }
                 }
@@ -281,11 +372,25 @@ pub fn confirm_modal(
                     ModalAction::LoadFromFile => {}
                     ModalAction::LoadFromUrl => {}
                     ModalAction::DeleteDocument => {
-                        delete_doc(&mut app_state, &mut commands, &mut pkv);
+                        delete_doc(&mut app_state, &mut commands, &mut pkv, &mut history);
+                    }
+                    ModalAction::DeleteTab => delete_tab(
+                        &mut app_state,
+                        &mut commands,
+                        &mut tab_query_container,
+                        &mut history,
+                    ),
+                    ModalAction::SaveAndExit => {
+                        commands.insert_resource(ExitRequest);
                     }
-                    ModalAction::DeleteTab => {
-                        delete_tab(&mut app_state, &mut commands, &mut tab_query_container)
+                    ModalAction::RestoreJournal => {
+                        commands.insert_resource(JournalReplayRequest { replay: true });
                     }
+                    ModalAction::EditDocTags(_) => {}
+                    ModalAction::EditNodeTags(_) => {}
+                    ModalAction::FilterByTag => {}
+                    ModalAction::SaveVersion => {}
+                    ModalAction::Versions => {}
                 }
             }
             commands.entity(entity).despawn_recursive();
@@ -295,29 +400,103 @@ pub fn confirm_modal(
     }
 }
 
+/// Tags are typed in the modal text input, separated by commas or spaces.
+fn confirm_tags(
+    action: &ModalAction,
+    text: &str,
+    app_state: &mut ResMut<AppState>,
+    commands: &mut Commands,
+) {
+    let tags = parse_tags(text);
+    match action {
+        ModalAction::EditDocTags(doc_id) => {
+            if let Some(doc) = app_state.docs.get_mut(doc_id) {
+                if doc.tags != tags {
+                    doc.tags = tags;
+                    app_state.unsaved_docs.insert(*doc_id);
+                }
+            }
+        }
+        ModalAction::EditNodeTags(node_id) => {
+            commands.insert_resource(SetNodeTagsRequest {
+                node_id: *node_id,
+                tags,
+            });
+        }
+        ModalAction::FilterByTag => {
+            app_state.tag_filter = tags.into_iter().next();
+        }
+        _ => {}
+    }
+}
+
+fn load_from_file(text: &str, comm_channels: &CommChannels, notifications: &Notifications) {
+    let path = PathBuf::from(text.trim());
+    let text = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(document_text);
+    match text {
+        Ok(json) => send_document(json, &comm_channels.tx, notifications),
+        Err(e) => notifications.error(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn load_from_url(text: &str, comm_channels: &CommChannels, notifications: &Notifications) {
+    let url = text.trim();
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+    let links: Vec<_> = finder.links(url).collect();
+    if links.len() != 1 {
+        notifications.error(format!("Not a valid URL: {}", url));
+        return;
+    }
+    let url = links[0].as_str().to_owned();
+    let cc = comm_channels.tx.clone();
+    let notifications = notifications.clone();
+    let pool = IoTaskPool::get();
+    let task = pool.spawn(async move {
+        let request = ehttp::Request::get(&url);
+        ehttp::fetch(request, move |result| {
+            match response_bytes(result).and_then(document_text) {
+                Ok(json) => send_document(json, &cc, &notifications),
+                Err(e) => notifications.error(format!("Failed to load {}: {}", url, e)),
+            }
+        });
+    });
+    task.detach();
+}
+
+/// Text the import channel takes: the file itself, or the document of a `.velo` bundle as JSON.
+pub fn document_text(data: Vec<u8>) -> Result<String, String> {
+    if is_bundle(&data) {
+        let doc = doc_from_bundle(&data).map_err(|e| e.to_string())?;
+        return serde_json::to_string(&doc).map_err(|e| e.to_string());
+    }
+    String::from_utf8(data).map_err(|_| "not a text file or .velo bundle".to_string())
+}
+
+fn send_document(json: String, tx: &Sender<String>, notifications: &Notifications) {
+    if tx.try_send(json).is_err() {
+        notifications.error("Another document is still being imported");
+    }
+}
+
 fn remove_from_storage(
     pkv: &mut ResMut<PkvStore>,
     id_to_remove: ReflectableUuid,
     new_id: ReflectableUuid,
 ) {
-    if let Ok(mut docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-        if docs.remove(&id_to_remove).is_some() {
-            pkv.set("docs", &docs).unwrap();
-        }
-    }
-    if let Ok(mut tags) = pkv.get::<HashMap<ReflectableUuid, Vec<String>>>("tags") {
-        if tags.remove(&id_to_remove).is_some() {
-            pkv.set("tags", &tags).unwrap();
-        }
-    }
-    if let Ok(mut names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-        if names.remove(&id_to_remove).is_some() {
-            pkv.set("names", &names).unwrap();
-        }
+    if let Err(e) = remove_stored_doc(pkv, id_to_remove) {
+        error!(
+            "Failed to remove document {:?} from storage: {}",
+            id_to_remove, e
+        );
     }
     if let Ok(last_saved) = pkv.get::<ReflectableUuid>("last_saved") {
         if last_saved == id_to_remove {
-            pkv.set("last_saved", &new_id).unwrap();
+            if let Err(e) = pkv.set("last_saved", &new_id) {
+                error!("Failed to store the last saved document: {}", e);
+            }
         }
     }
 }
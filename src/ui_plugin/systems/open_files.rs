@@ -0,0 +1,136 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use super::{document_text, insert_image, CommChannels, Notifications};
+use crate::components::MainCamera;
+use crate::resources::OpenFilesRequest;
+use crate::themes::Theme;
+use crate::AddRect;
+
+/// Space between image nodes dropped together, so they don't cover each other.
+const DROPPED_IMAGE_OFFSET: f32 = 20.;
+
+/// Dropped PNG and JPEG files become image nodes where they are dropped, other files are
+/// opened as documents.
+pub fn file_drop_handler(
+    mut drops: EventReader<FileDragAndDrop>,
+    mut request: ResMut<OpenFilesRequest>,
+    mut images: ResMut<Assets<Image>>,
+    mut events: EventWriter<AddRect<(String, Color)>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    notifications: Res<Notifications>,
+    theme: Res<Theme>,
+) {
+    if drops.is_empty() {
+        return;
+    }
+    let window = windows.single();
+    let (camera, camera_transform) = camera.single();
+    let drop_point = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .unwrap_or_else(|| camera_transform.translation().truncate());
+    let mut dropped_images = 0;
+    for drop in drops.iter() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else {
+            continue;
+        };
+        let extension = path_buf
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        if !matches!(extension.as_deref(), Some("png" | "jpg" | "jpeg")) {
+            request.paths.push_back(path_buf.clone());
+            continue;
+        }
+        let image = std::fs::read(path_buf)
+            .map_err(|e| e.to_string())
+            .and_then(|data| image::load_from_memory(&data).map_err(|e| e.to_string()));
+        match image {
+            Ok(image) => {
+                let offset = DROPPED_IMAGE_OFFSET * dropped_images as f32;
+                dropped_images += 1;
+                insert_image(
+                    &mut images,
+                    &mut events,
+                    image.to_rgba8(),
+                    drop_point.x + offset,
+                    drop_point.y - offset,
+                    window.scale_factor(),
+                    &theme,
+                );
+            }
+            Err(e) => notifications.error(format!("Failed to read {}: {}", path_buf.display(), e)),
+        }
+    }
+}
+
+/// Sends the next file to open through the import channel once it is free.
+pub fn open_files(
+    mut request: ResMut<OpenFilesRequest>,
+    comm_channels: Option<Res<CommChannels>>,
+    notifications: Res<Notifications>,
+) {
+    let Some(comm_channels) = comm_channels else {
+        return;
+    };
+    if request.paths.is_empty() || comm_channels.tx.is_full() {
+        return;
+    }
+    let path = request.paths.pop_front().unwrap();
+    let text = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(document_text);
+    match text {
+        Ok(text) => {
+            let _ = comm_channels.tx.try_send(text);
+        }
+        Err(e) => notifications.error(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{doc_from_json, doc_to_bundle};
+    use crate::ui_plugin::NotificationKind;
+
+    #[test]
+    fn test_open_files() {
+        let doc = doc_from_json(include_str!("../../export/fixtures/board.json")).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let json = serde_json::to_string(&doc).unwrap();
+        let json_path = dir.path().join("board.json");
+        std::fs::write(&json_path, &json).unwrap();
+        let bundle_path = dir.path().join("board.velo");
+        std::fs::write(&bundle_path, doc_to_bundle(&doc).unwrap()).unwrap();
+        let missing_path = dir.path().join("missing.json");
+
+        let mut app = App::new();
+        app.add_systems(Update, open_files);
+        let (tx, rx) = async_channel::bounded(1);
+        app.insert_resource(CommChannels { tx, rx: rx.clone() });
+        app.init_resource::<Notifications>();
+        app.insert_resource(OpenFilesRequest {
+            paths: [json_path, bundle_path, missing_path.clone()].into(),
+        });
+
+        app.update();
+        assert_eq!(rx.try_recv().unwrap(), json);
+        app.update();
+        // the next file waits until the channel is free
+        app.update();
+        assert_eq!(app.world.resource::<OpenFilesRequest>().paths.len(), 1);
+        let bundled = doc_from_json(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(bundled.id, doc.id);
+
+        app.update();
+        assert!(rx.is_empty());
+        assert!(app.world.resource::<OpenFilesRequest>().paths.is_empty());
+        let notification = app.world.resource::<Notifications>().rx.try_recv().unwrap();
+        assert_eq!(notification.kind, NotificationKind::Error);
+        assert!(notification
+            .message
+            .starts_with(&format!("Failed to read {}", missing_path.display())));
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
-    ui_helpers::{ResizeMarker, VeloShape},
-    NodeInteraction, NodeType, RawText, RedrawArrow, VeloNode,
+    ui_helpers::{node_path, ResizeMarker, VeloShape},
+    CanvasEdit, NodeInteraction, NodeType, RawText, RedrawArrow, VeloNode,
 };
 use crate::{
     canvas::{arrow::components::ArrowConnect, shadows::systems::Shadow},
@@ -54,12 +54,13 @@ pub fn resize_entity_start(
 pub fn resize_entity_end(
     mut ui_state: ResMut<UiState>,
     mut node_interaction_events: EventReader<NodeInteraction>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     for event in node_interaction_events.iter() {
-        if event.node_interaction_type == super::NodeInteractionType::LeftMouseRelease
-            && ui_state.entity_to_resize.is_some()
-        {
-            ui_state.entity_to_resize = None;
+        if event.node_interaction_type == super::NodeInteractionType::LeftMouseRelease {
+            if let Some(id) = ui_state.entity_to_resize.take() {
+                canvas_edits.send(CanvasEdit { ids: vec![id] });
+            }
         }
     }
 }
@@ -176,35 +177,7 @@ pub fn resize_entity_run(
                 }
 
                 // update size of bevy_lyon node
-                let points = [
-                    Vec2::new(-width / 2., -height / 2.),
-                    Vec2::new(-width / 2., height / 2.),
-                    Vec2::new(width / 2., height / 2.),
-                    Vec2::new(width / 2., -height / 2.),
-                ];
-
-                let new_path = match velo_border.node_type {
-                    NodeType::Rect => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-                        &bevy_prototype_lyon::shapes::RoundedPolygon {
-                            points: points.into_iter().collect(),
-                            closed: true,
-                            radius: 10.,
-                        },
-                    ),
-                    NodeType::Paper => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-                        &bevy_prototype_lyon::shapes::Polygon {
-                            points: points.into_iter().collect(),
-                            closed: true,
-                        },
-                    ),
-                    NodeType::Circle => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-                        &bevy_prototype_lyon::shapes::Circle {
-                            radius: width / 2.,
-                            center: Vec2::new(0., 0.),
-                        },
-                    ),
-                };
-                *path = new_path;
+                *path = node_path(&velo_border.node_type, width, height);
                 events.send(RedrawArrow { id: raw_text.id });
             }
         }
@@ -4,7 +4,7 @@ use cosmic_text::Edit;
 
 use crate::resources::AppState;
 
-use crate::resources::LoadTabRequest;
+use crate::resources::{LoadTabRequest, VersionPreview};
 
 use super::ui_helpers::DocListItemButton;
 use super::ui_helpers::TabButton;
@@ -13,6 +13,7 @@ pub fn resize_notificator(
     mut commands: Commands,
     resize_event: Res<Events<WindowResized>>,
     app_state: Res<AppState>,
+    preview: Option<Res<VersionPreview>>,
     mut tabs: Query<&mut CosmicEdit, (With<TabButton>, Without<DocListItemButton>)>,
     mut docs: Query<&mut CosmicEdit, (With<DocListItemButton>, Without<TabButton>)>,
 ) {
@@ -24,7 +25,7 @@ pub fn resize_notificator(
                 commands.insert_resource(LoadTabRequest {
                     doc_id: current_doc.id,
                     tab_id: active_tab.id,
-                    drop_last_checkpoint: false,
+                    version: preview.map(|preview| preview.version_id),
                 });
             }
         }
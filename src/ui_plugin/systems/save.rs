@@ -1,20 +1,20 @@
-use base64::{engine::general_purpose, Engine};
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
 use bevy_cosmic_edit::CosmicEdit;
 use bevy_pkv::PkvStore;
 use bevy_prototype_lyon::prelude::Stroke;
 use image::*;
 
-use serde_json::json;
-use std::{collections::HashMap, io::Cursor};
+use std::io::Cursor;
 
 use super::ui_helpers::{Drawing, VeloNode, VeloShape};
-use super::{DrawingJsonNode, RawText, SaveStore};
+use super::{DrawingJsonNode, Notifications, RawText, SaveStore};
 use crate::canvas::arrow::components::ArrowMeta;
-use crate::components::Doc;
+use crate::document::{gc_images, store_doc, store_image, TabSnapshot};
+use crate::export::{write_doc, RasterFonts};
 use crate::resources::SaveDocRequest;
-use crate::resources::{AppState, SaveTabRequest};
+use crate::resources::{AppState, SaveTabRequest, VersionPreview};
+use crate::themes::Theme;
 use crate::utils::{load_doc_to_memory, ReflectableUuid};
 use crate::{JsonNode, JsonNodeText, MAX_CHECKPOINTS};
 
@@ -40,12 +40,16 @@ pub fn save_doc(
     mut pkv: ResMut<PkvStore>,
     mut commands: Commands,
     mut events: EventWriter<SaveStore>,
+    notifications: Res<Notifications>,
 ) {
     let doc_id = request.doc_id;
 
-    load_doc_to_memory(doc_id, &mut app_state, &mut pkv);
+    load_doc_to_memory(doc_id, &mut app_state, &mut pkv, &notifications);
+    let Some(doc) = app_state.docs.get(&doc_id) else {
+        return;
+    };
 
-    for tab in app_state.docs.get_mut(&doc_id).unwrap().tabs.iter() {
+    for tab in doc.tabs.iter() {
         if tab.is_active {
             commands.insert_resource(SaveTabRequest {
                 doc_id,
@@ -64,47 +68,33 @@ pub fn save_to_store(
     mut pkv: ResMut<PkvStore>,
     mut app_state: ResMut<AppState>,
     mut events: EventReader<SaveStore>,
+    notifications: Res<Notifications>,
+    theme: Res<Theme>,
+    mut raster_fonts: Local<RasterFonts>,
 ) {
     for event in events.iter() {
         let doc_id = event.doc_id;
-        if let Ok(mut docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-            docs.insert(doc_id, app_state.docs.get(&doc_id).unwrap().clone());
-            pkv.set("docs", &docs).unwrap();
-        } else {
-            let mut docs = HashMap::new();
-            docs.insert(doc_id, app_state.docs.get(&doc_id).unwrap().clone());
-            pkv.set("docs", &docs).unwrap();
-        }
-        if let Ok(mut tags) = pkv.get::<HashMap<ReflectableUuid, Vec<String>>>("tags") {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            if let Some(tags) = tags.get_mut(&doc_id) {
-                tags.append(&mut doc.tags.clone());
-            } else {
-                tags.insert(doc.id, doc.tags.clone());
+        let Some(doc) = app_state.docs.get(&doc_id) else {
+            continue;
+        };
+        match store_doc(&mut pkv, doc) {
+            Ok(()) => {
+                app_state.unsaved_docs.remove(&doc_id);
             }
-            pkv.set("tags", &tags).unwrap();
-        } else {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            let mut tags = HashMap::new();
-            tags.insert(doc.id, doc.tags.clone());
-            pkv.set("tags", &tags).unwrap();
+            Err(e) => notifications.error(format!("Failed to store \"{}\": {}", doc.name, e)),
         }
-        if let Ok(mut names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            names.insert(doc.id, doc.name.clone());
-            pkv.set("names", &names).unwrap();
-        } else {
-            let doc = app_state.docs.get(&doc_id).unwrap();
-            let mut names = HashMap::new();
-            names.insert(doc.id, doc.name.clone());
-            pkv.set("names", &names).unwrap();
+        if let Err(e) = pkv.set("last_saved", &doc_id) {
+            error!("Failed to store the last saved document: {}", e);
         }
-        pkv.set("last_saved", &doc_id).unwrap();
 
-        if let Some(path) = event.path.clone() {
-            let current_doc = app_state.docs.get(&doc_id).unwrap().clone();
-            std::fs::write(path, serde_json::to_string_pretty(&current_doc).unwrap())
-                .expect("Error saving current document to file")
+        if let Some(path) = event.path.as_ref() {
+            let current_doc = &app_state.docs[&doc_id];
+            match write_doc(path, current_doc, &theme, &mut raster_fonts) {
+                Ok(()) => notifications.info(format!("Saved to {}", path.display())),
+                Err(e) => {
+                    notifications.error(format!("Failed to save to {}: {}", path.display(), e))
+                }
+            }
         }
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -116,8 +106,8 @@ pub fn save_to_store(
                 index.node_updates.clear();
                 let index = std::sync::Arc::new(index.index.clone());
                 pool.spawn(async move {
-                    let _ = super::clear_tabs_index(&index, &tabs_to_delete);
-                    let _ = super::update_search_index(&index, &node_updates);
+                    let _ = crate::document::clear_tabs_index(&index, &tabs_to_delete);
+                    let _ = crate::document::update_search_index(&index, &node_updates);
                 })
                 .detach();
             }
@@ -125,121 +115,174 @@ pub fn save_to_store(
     }
 }
 
+pub fn image_to_png(image: &Image) -> Option<Vec<u8>> {
+    let img = image.clone().try_into_dynamic().ok()?;
+    let mut image_data: Vec<u8> = Vec::new();
+    img.write_to(&mut Cursor::new(&mut image_data), ImageOutputFormat::Png)
+        .unwrap();
+    Some(image_data)
+}
+
+/// Canvas entities that make up the active tab.
+#[derive(SystemParam)]
+pub struct CanvasQuery<'w, 's> {
+    arrows: Query<'w, 's, (&'static ArrowMeta, &'static Visibility), With<ArrowMeta>>,
+    raw_text_query:
+        Query<'w, 's, (&'static RawText, &'static CosmicEdit, &'static Parent), With<RawText>>,
+    border_query: Query<'w, 's, (&'static Parent, &'static VeloShape), With<VeloShape>>,
+    velo_node_query:
+        Query<'w, 's, (&'static Transform, &'static Visibility, &'static VeloNode), With<VeloNode>>,
+    drawing_query: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Drawing<(String, Color)>,
+            &'static Stroke,
+        ),
+        With<Drawing<(String, Color)>>,
+    >,
+}
+
+impl<'w, 's> CanvasQuery<'w, 's> {
+    /// `image_key` maps a node background image to the value kept in `TabSnapshot.images`.
+    pub fn snapshot(
+        &self,
+        mut image_key: impl FnMut(&Handle<Image>) -> Option<String>,
+    ) -> TabSnapshot {
+        let mut snapshot = TabSnapshot::default();
+        for (raw_text, cosmic_edit, parent) in self.raw_text_query.iter() {
+            if let Some(key) = cosmic_edit.bg_image.as_ref().and_then(&mut image_key) {
+                snapshot.images.insert(raw_text.id.0.to_string(), key);
+            }
+            let (border_parent, border) = self.border_query.get(parent.get()).unwrap();
+            let (top_transform, top_visibility, velo_node) =
+                self.velo_node_query.get(border_parent.get()).unwrap();
+            let x = top_transform.translation.x;
+            let y = top_transform.translation.y;
+            let z = top_transform.translation.z;
+            let (width, height) = (cosmic_edit.width, cosmic_edit.height);
+            let visible = top_visibility == Visibility::Visible;
+            snapshot.nodes.push(JsonNode {
+                visible,
+                node_type: border.node_type.clone(),
+                id: raw_text.id.0,
+                x,
+                y,
+                z,
+                width,
+                height,
+                bg_color: border.pair_color.0.clone(),
+                text: JsonNodeText {
+                    text: raw_text.last_text.clone(),
+                    pos: cosmic_edit.text_pos.clone().into(),
+                },
+                tags: velo_node.tags.clone(),
+            });
+        }
+
+        for (arrow_meta, visibility) in self.arrows.iter() {
+            let mut meta = *arrow_meta;
+            meta.visible = visibility == Visibility::Visible;
+            snapshot.arrows.push(meta);
+        }
+
+        for (transform, drawing, stroke) in self.drawing_query.iter() {
+            snapshot.drawings.push(DrawingJsonNode {
+                x: transform.translation.x,
+                y: transform.translation.y,
+                z: transform.translation.z,
+                width: stroke.options.line_width,
+                id: drawing.id,
+                points: drawing.points.clone(),
+                drawing_color: drawing.drawing_color.0.clone(),
+            });
+        }
+        snapshot.normalize();
+        snapshot
+    }
+
+    /// Background images of the nodes that have one.
+    pub fn node_images(&self) -> impl Iterator<Item = (ReflectableUuid, &Handle<Image>)> + '_ {
+        self.raw_text_query
+            .iter()
+            .filter_map(|(raw_text, cosmic_edit, _)| {
+                Some((raw_text.id, cosmic_edit.bg_image.as_ref()?))
+            })
+    }
+}
+
 pub fn save_tab(
     images: Res<Assets<Image>>,
-    arrows: Query<(&ArrowMeta, &Visibility), With<ArrowMeta>>,
     request: Res<SaveTabRequest>,
     mut app_state: ResMut<AppState>,
-    raw_text_query: Query<(&RawText, &CosmicEdit, &Parent), With<RawText>>,
-    border_query: Query<(&Parent, &VeloShape), With<VeloShape>>,
-    velo_node_query: Query<(&Transform, &Visibility), With<VeloNode>>,
-    drawing_query: Query<
-        (&Transform, &Drawing<(String, Color)>, &Stroke),
-        With<Drawing<(String, Color)>>,
-    >,
+    canvas: CanvasQuery,
+    preview: Option<Res<VersionPreview>>,
 ) {
+    if preview.is_some() {
+        // the canvas shows a version for review, not the tab
+        return;
+    }
     #[cfg(not(target_arch = "wasm32"))]
     if let Some(index) = &mut app_state.search_index {
         index.tabs_to_delete.insert(request.tab_id.0);
     }
-    let mut json = json!({
-        "images": {},
-        "nodes": [],
-        "arrows": [],
-        "drawings": []
+    let doc = app_state.docs.get_mut(&request.doc_id).unwrap();
+    let snapshot = canvas.snapshot(|handle| {
+        let image_data = image_to_png(images.get(handle).unwrap())?;
+        Some(store_image(doc, &image_data))
     });
-    let json_images = json["images"].as_object_mut().unwrap();
-    for (raw_text, cosmic_edit, _) in raw_text_query.iter() {
-        if let Some(handle) = cosmic_edit.bg_image.clone() {
-            let image = images.get(&handle).unwrap();
-            if let Ok(img) = image.clone().try_into_dynamic() {
-                let mut image_data: Vec<u8> = Vec::new();
-                img.write_to(&mut Cursor::new(&mut image_data), ImageOutputFormat::Png)
-                    .unwrap();
-                let res_base64 = general_purpose::STANDARD.encode(image_data);
-                json_images.insert(raw_text.id.0.to_string(), json!(res_base64));
-            }
-        }
-    }
 
-    let json_nodes = json["nodes"].as_array_mut().unwrap();
-    for (raw_text, cosmic_edit, parent) in raw_text_query.iter() {
-        let (border_parent, border) = border_query.get(parent.get()).unwrap();
-        let (top_transform, top_visibility) = velo_node_query.get(border_parent.get()).unwrap();
-        let x = top_transform.translation.x;
-        let y = top_transform.translation.y;
-        let z = top_transform.translation.z;
-        let (width, height) = (cosmic_edit.width, cosmic_edit.height);
-        let visible = top_visibility == Visibility::Visible;
-        json_nodes.push(json!(JsonNode {
-            visible,
-            node_type: border.node_type.clone(),
-            id: raw_text.id.0,
-            x,
-            y,
-            z,
-            width,
-            height,
-            bg_color: border.pair_color.0.clone(),
-            text: JsonNodeText {
-                text: raw_text.last_text.clone(),
-                pos: cosmic_edit.text_pos.clone().into()
-            },
-        }));
-        #[cfg(not(target_arch = "wasm32"))]
-        if let Some(index) = &mut app_state.search_index {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(index) = &mut app_state.search_index {
+        for node in snapshot.nodes.iter() {
             index.node_updates.insert(
-                super::NodeSearchLocation {
+                crate::document::NodeSearchLocation {
                     doc_id: request.doc_id.0,
                     tab_id: request.tab_id.0,
-                    node_id: raw_text.id.0,
+                    node_id: node.id,
                 },
-                raw_text.last_text.clone(),
+                node.text.text.clone(),
             );
         }
     }
 
-    let json_arrows = json["arrows"].as_array_mut().unwrap();
-    for (arrow_meta, visibility) in arrows.iter() {
-        let mut meta = *arrow_meta;
-        meta.visible = visibility == Visibility::Visible;
-        json_arrows.push(json!(meta));
-    }
-
-    let json_drawing = json["drawings"].as_array_mut().unwrap();
-    for (transform, drawing, stroke) in drawing_query.iter() {
-        json_drawing.push(json!(DrawingJsonNode {
-            x: transform.translation.x,
-            y: transform.translation.y,
-            z: transform.translation.z,
-            width: stroke.options.line_width,
-            id: drawing.id,
-            points: drawing.points.clone(),
-            drawing_color: drawing.drawing_color.0.clone()
-        }));
-    }
-
-    let doc_id = request.doc_id;
-
-    for tab in &mut app_state.docs.get_mut(&doc_id).unwrap().tabs {
+    let doc = app_state.docs.get_mut(&request.doc_id).unwrap();
+    let mut changed = false;
+    for tab in &mut doc.tabs {
         if request.tab_id == tab.id {
             if (tab.checkpoints.len() as i32) > MAX_CHECKPOINTS {
                 tab.checkpoints.pop_front();
             }
-            if let Some(last) = tab.checkpoints.back() {
-                if last == &json.to_string() {
-                    break;
-                }
+            let unchanged = tab
+                .checkpoints
+                .back()
+                .is_some_and(|last| last.same_canvas(&snapshot) && last.images == snapshot.images);
+            if unchanged {
+                break;
             }
-            tab.checkpoints.push_back(json.to_string());
+            tab.checkpoints.push_back(snapshot);
+            changed = true;
             break;
         }
     }
+    gc_images(doc);
+    if changed {
+        app_state.unsaved_docs.insert(request.doc_id);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::components::Doc;
+    use crate::document::{
+        load_doc_index, load_stored_doc, store_doc, DocIndex, DOC_INDEX_KEY, DOC_SCHEMA_VERSION,
+    };
+    use crate::themes::velo_light;
+    use crate::ui_plugin::NotificationKind;
+    use crate::utils::ReflectableUuid;
+    use std::collections::HashMap;
     use tempfile::tempdir;
 
     #[test]
@@ -265,7 +308,10 @@ mod tests {
                     name: "Test tab".to_string(),
                     checkpoints: std::collections::VecDeque::new(),
                     z_index: 1.,
+                    versions: vec![],
                 }],
+                version: DOC_SCHEMA_VERSION,
+                images: HashMap::new(),
             },
         );
         let request = SaveDocRequest {
@@ -274,6 +320,8 @@ mod tests {
         };
         app.insert_resource(request);
         app.add_event::<SaveStore>();
+        app.init_resource::<Notifications>();
+        app.insert_resource(velo_light());
         PkvStore::new("test", "test").clear().unwrap();
         app.insert_resource(PkvStore::new("test", "test"));
         app.insert_resource(app_state);
@@ -283,16 +331,15 @@ mod tests {
 
         // Assertions
         let pkv = app.world.resource::<PkvStore>();
-        let saved_docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
-        assert_eq!(saved_docs.get(&doc_id).unwrap().name, "test_doc");
-        assert!(saved_docs.get(&doc_id).unwrap().tabs[0].is_active);
-        let saved_tags: HashMap<ReflectableUuid, Vec<String>> = pkv.get("tags").unwrap();
+        let saved_doc = load_stored_doc(pkv, doc_id).unwrap().unwrap();
+        assert_eq!(saved_doc.name, "test_doc");
+        assert!(saved_doc.tabs[0].is_active);
+        let index = load_doc_index(pkv);
         assert_eq!(
-            saved_tags.get(&doc_id).unwrap(),
+            index.tags.get(&doc_id).unwrap(),
             &vec!["test_tag".to_string()]
         );
-        let saved_names: HashMap<ReflectableUuid, String> = pkv.get("names").unwrap();
-        assert_eq!(saved_names.get(&doc_id).unwrap(), "test_doc");
+        assert_eq!(index.names.get(&doc_id).unwrap(), "test_doc");
         assert_eq!(pkv.get::<ReflectableUuid>("last_saved").unwrap(), doc_id);
         let file_contents = std::fs::read_to_string(temp_file_path).unwrap();
         let saved_doc: Doc = serde_json::from_str(&file_contents).unwrap();
@@ -301,7 +348,7 @@ mod tests {
     }
 
     #[test]
-    ///the PKV store has tags, but not for the document being saved:
+    ///the index has tags, but not for the document being saved:
     fn test_save_doc2() {
         // Setup
         let mut app = App::new();
@@ -321,9 +368,12 @@ mod tests {
                     id: tab_id,
                     is_active: true,
                     z_index: 1.,
+                    versions: vec![],
                     name: "Test tab".to_string(),
                     checkpoints: std::collections::VecDeque::new(),
                 }],
+                version: DOC_SCHEMA_VERSION,
+                images: HashMap::new(),
             },
         );
         let request = SaveDocRequest {
@@ -333,10 +383,14 @@ mod tests {
         app.insert_resource(request);
         PkvStore::new("test", "test1").clear().unwrap();
         let mut pkv = PkvStore::new("test", "test1");
-        let mut tags = HashMap::new();
-        tags.insert(ReflectableUuid::generate(), vec!["test_tag_2".to_string()]);
-        pkv.set("tags", &tags).unwrap();
+        let mut index = DocIndex::default();
+        index
+            .tags
+            .insert(ReflectableUuid::generate(), vec!["test_tag_2".to_string()]);
+        pkv.set(DOC_INDEX_KEY, &index).unwrap();
         app.add_event::<SaveStore>();
+        app.init_resource::<Notifications>();
+        app.insert_resource(velo_light());
         app.insert_resource(pkv);
         app.insert_resource(app_state);
 
@@ -345,16 +399,15 @@ mod tests {
 
         // Assertions
         let pkv = app.world.resource::<PkvStore>();
-        let saved_docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
-        assert_eq!(saved_docs.get(&doc_id).unwrap().name, "test_doc");
-        assert!(saved_docs.get(&doc_id).unwrap().tabs[0].is_active);
-        let saved_tags: HashMap<ReflectableUuid, Vec<String>> = pkv.get("tags").unwrap();
+        let saved_doc = load_stored_doc(pkv, doc_id).unwrap().unwrap();
+        assert_eq!(saved_doc.name, "test_doc");
+        assert!(saved_doc.tabs[0].is_active);
+        let index = load_doc_index(pkv);
         assert_eq!(
-            saved_tags.get(&doc_id).unwrap(),
+            index.tags.get(&doc_id).unwrap(),
             &vec!["test_tag_1".to_string()]
         );
-        let saved_names: HashMap<ReflectableUuid, String> = pkv.get("names").unwrap();
-        assert_eq!(saved_names.get(&doc_id).unwrap(), "test_doc");
+        assert_eq!(index.names.get(&doc_id).unwrap(), "test_doc");
         assert_eq!(pkv.get::<ReflectableUuid>("last_saved").unwrap(), doc_id);
         let file_contents = std::fs::read_to_string(temp_file_path).unwrap();
         let saved_doc: Doc = serde_json::from_str(&file_contents).unwrap();
@@ -363,7 +416,8 @@ mod tests {
     }
 
     #[test]
-    /// the PKV store already has tags for the document being saved.
+    /// the index already has tags for the document being saved;
+    /// they are replaced by the document's tags without duplicates.
     fn test_save_doc3() {
         // Setup
         let mut app = App::new();
@@ -373,20 +427,26 @@ mod tests {
         let doc_id = ReflectableUuid::generate();
         let tab_id = ReflectableUuid::generate();
         let mut app_state = AppState::default();
-        let existing_tags = vec!["test_tag_2".to_string(), "test_tag_1".to_string()];
         app_state.docs.insert(
             doc_id,
             Doc {
                 id: doc_id,
                 name: "test_doc".to_string(),
-                tags: vec!["test_tag_1".to_string()],
+                tags: vec![
+                    "test_tag_1".to_string(),
+                    "test_tag_3".to_string(),
+                    "Test_Tag_1".to_string(),
+                ],
                 tabs: vec![crate::components::Tab {
                     id: tab_id,
                     is_active: true,
                     z_index: 1.,
+                    versions: vec![],
                     name: "Test tab".to_string(),
                     checkpoints: std::collections::VecDeque::new(),
                 }],
+                version: DOC_SCHEMA_VERSION,
+                images: HashMap::new(),
             },
         );
         let request = SaveDocRequest {
@@ -396,10 +456,12 @@ mod tests {
         app.insert_resource(request);
         PkvStore::new("test", "test3").clear().unwrap();
         let mut pkv = PkvStore::new("test", "test3");
-        let mut tags = HashMap::new();
-        tags.insert(doc_id, vec!["test_tag_2".to_string()]);
-        pkv.set("tags", &tags).unwrap();
+        let mut index = DocIndex::default();
+        index.tags.insert(doc_id, vec!["test_tag_2".to_string()]);
+        pkv.set(DOC_INDEX_KEY, &index).unwrap();
         app.add_event::<SaveStore>();
+        app.init_resource::<Notifications>();
+        app.insert_resource(velo_light());
         app.insert_resource(pkv);
         app.insert_resource(app_state);
 
@@ -409,16 +471,15 @@ mod tests {
         // Assertions
         // Check that the document was saved to the PKV store
         let pkv = app.world.resource::<PkvStore>();
-        let saved_docs: HashMap<ReflectableUuid, Doc> = pkv.get("docs").unwrap();
-        assert_eq!(saved_docs.get(&doc_id).unwrap().name, "test_doc");
-        assert!(saved_docs.get(&doc_id).unwrap().tabs[0].is_active);
-        // Check that the tags were saved to the PKV store
-        let saved_tags: HashMap<ReflectableUuid, Vec<String>> = pkv.get("tags").unwrap();
-        let expected_tags = existing_tags;
-        assert_eq!(saved_tags.get(&doc_id).unwrap(), &expected_tags);
-        // Check that the name was saved to the PKV store
-        let saved_names: HashMap<ReflectableUuid, String> = pkv.get("names").unwrap();
-        assert_eq!(saved_names.get(&doc_id).unwrap(), "test_doc");
+        let saved_doc = load_stored_doc(pkv, doc_id).unwrap().unwrap();
+        assert_eq!(saved_doc.name, "test_doc");
+        assert!(saved_doc.tabs[0].is_active);
+        let index = load_doc_index(pkv);
+        // Check that the tags were saved to the index
+        let expected_tags = vec!["test_tag_1".to_string(), "test_tag_3".to_string()];
+        assert_eq!(index.tags.get(&doc_id).unwrap(), &expected_tags);
+        // Check that the name was saved to the index
+        assert_eq!(index.names.get(&doc_id).unwrap(), "test_doc");
         // Check that the last_saved field was updated in the PKV store
         assert_eq!(pkv.get::<ReflectableUuid>("last_saved").unwrap(), doc_id);
         // Check that the file was saved to the correct path
@@ -426,5 +487,57 @@ mod tests {
         let saved_doc: Doc = serde_json::from_str(&file_contents).unwrap();
         assert_eq!(saved_doc.name, "test_doc");
         assert!(saved_doc.tabs[0].is_active);
+        // Check that the user was told where the file went
+        let notification = app.world.resource::<Notifications>().rx.try_recv().unwrap();
+        assert_eq!(notification.kind, NotificationKind::Info);
+        assert!(notification.message.starts_with("Saved to"));
+    }
+
+    #[test]
+    /// Stored document that needs repairs
+    fn test_save_doc_repaired() {
+        let mut app = App::new();
+        app.add_systems(Update, (save_doc, save_to_store.after(save_doc)));
+        let doc_id = ReflectableUuid::generate();
+        let tab = crate::components::Tab {
+            id: ReflectableUuid::generate(),
+            is_active: true,
+            name: "Test tab".to_string(),
+            checkpoints: std::collections::VecDeque::new(),
+            z_index: 1.,
+            versions: vec![],
+        };
+        PkvStore::new("test", "test4").clear().unwrap();
+        let mut pkv = PkvStore::new("test", "test4");
+        store_doc(
+            &mut pkv,
+            &Doc {
+                id: doc_id,
+                name: "test_doc".to_string(),
+                tags: vec![],
+                tabs: vec![tab.clone(), tab.clone(), tab],
+                version: DOC_SCHEMA_VERSION,
+                images: HashMap::new(),
+            },
+        )
+        .unwrap();
+        app.insert_resource(SaveDocRequest { doc_id, path: None });
+        app.add_event::<SaveStore>();
+        app.init_resource::<Notifications>();
+        app.insert_resource(velo_light());
+        app.insert_resource(pkv);
+        app.insert_resource(AppState::default());
+
+        app.update();
+
+        // every repair is reported, not just the first
+        let notification = app.world.resource::<Notifications>().rx.try_recv().unwrap();
+        assert_eq!(notification.kind, NotificationKind::Info);
+        let lines: Vec<_> = notification.message.lines().collect();
+        assert_eq!(lines[0], "Repaired 2 problems in \"test_doc\":");
+        assert_eq!(lines.len(), 3);
+        let pkv = app.world.resource::<PkvStore>();
+        let saved_doc = load_stored_doc(pkv, doc_id).unwrap().unwrap();
+        assert!(saved_doc.tabs[1].id != saved_doc.tabs[2].id);
     }
 }
@@ -9,23 +9,13 @@ use bevy_prototype_lyon::prelude::Stroke;
 use cosmic_text::Edit;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::path::Path;
-use std::path::PathBuf;
-use tantivy::collector::TopDocs;
-use tantivy::query::BooleanQuery;
-use tantivy::query::FuzzyTermQuery;
-use tantivy::query::Occur;
-use tantivy::ReloadPolicy;
-
-use tantivy::schema::*;
 use tantivy::Index;
 use uuid::Uuid;
 
+use crate::document::{fuzzy_search, initialize_search_index, load_doc_index, NodeSearchLocation};
 use crate::resources::AppState;
 use crate::themes::Theme;
-use crate::utils::ReflectableUuid;
-use crate::APP_NAME;
-use crate::ORG_NAME;
+use crate::utils::{data_dir, ReflectableUuid};
 
 use super::ui_helpers::SearchButton;
 use super::ui_helpers::SearchText;
@@ -39,13 +29,6 @@ pub struct SearchIndexState {
     pub node_updates: HashMap<NodeSearchLocation, String>,
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
-pub struct NodeSearchLocation {
-    pub doc_id: Uuid,
-    pub tab_id: Uuid,
-    pub node_id: Uuid,
-}
-
 pub fn search_box_click(
     mut commands: Commands,
     mut interaction_query: Query<
@@ -116,9 +99,10 @@ pub fn search_box_text_changed(
                     Err(e) => info!("Error searching index {:?}", e),
                 }
             }
-        } else if let Ok(names) = pkv.get::<HashMap<ReflectableUuid, String>>("names") {
+        } else {
+            let index = load_doc_index(&pkv);
             highlight_search_match_nodes(&HashSet::new(), &mut velo_border, &theme);
-            let keys_in_storage: Vec<_> = names.keys().collect();
+            let keys_in_storage: Vec<_> = index.names.keys().collect();
             let keys_in_memory: Vec<_> = app_state.docs.keys().cloned().collect();
             let mut combined_keys = keys_in_memory;
             combined_keys.extend(keys_in_storage);
@@ -129,146 +113,13 @@ pub fn search_box_text_changed(
 }
 
 pub fn init_search_index(mut app_state: ResMut<AppState>) {
-    let dirs = directories::ProjectDirs::from("", ORG_NAME, APP_NAME);
-    let path = match dirs.as_ref() {
-        Some(dirs) => dirs.data_dir(),
-        None => Path::new("."),
-    }
-    .to_path_buf();
     app_state.search_index = Some(SearchIndexState {
-        index: initialize_search_index(path),
+        index: initialize_search_index(data_dir()),
         node_updates: HashMap::new(),
         tabs_to_delete: HashSet::new(),
     });
 }
 
-pub fn initialize_search_index(dir: PathBuf) -> tantivy::Index {
-    Index::open_in_dir(dir.clone()).unwrap_or_else(|_| {
-        let mut schema_builder = Schema::builder();
-        schema_builder.add_text_field("text", TEXT);
-        schema_builder.add_text_field("full_text", STRING);
-        schema_builder.add_text_field("doc_id", STRING | STORED);
-        schema_builder.add_text_field("tab_id", STRING | STORED);
-        schema_builder.add_text_field("node_id", STRING | STORED);
-        let schema = schema_builder.build();
-        Index::create_in_dir(dir, schema).unwrap()
-    })
-}
-
-pub fn update_search_index(
-    index: &Index,
-    node_search_locations: &HashMap<NodeSearchLocation, String>,
-) -> tantivy::Result<()> {
-    let mut index_writer = index.writer(50_000_000)?;
-
-    for (node_search_location, str) in node_search_locations.iter() {
-        let term = tantivy::Term::from_field_text(
-            index.schema().get_field("node_id").unwrap(),
-            &node_search_location.node_id.to_string(),
-        );
-        index_writer.delete_term(term);
-
-        let mut document = tantivy::Document::new();
-        document.add_text(index.schema().get_field("text").unwrap(), str);
-        document.add_text(index.schema().get_field("full_text").unwrap(), str);
-        document.add_text(
-            index.schema().get_field("doc_id").unwrap(),
-            &node_search_location.doc_id.to_string(),
-        );
-        document.add_text(
-            index.schema().get_field("tab_id").unwrap(),
-            &node_search_location.tab_id.to_string(),
-        );
-        document.add_text(
-            index.schema().get_field("node_id").unwrap(),
-            &node_search_location.node_id.to_string(),
-        );
-
-        index_writer.add_document(document)?;
-    }
-
-    index_writer.commit()?;
-
-    Ok(())
-}
-
-const MAX_SEARCH_RESULTS: usize = 1000;
-
-pub fn clear_tabs_index(index: &Index, tab_ids: &HashSet<Uuid>) -> tantivy::Result<()> {
-    let mut index_writer = index.writer(50_000_000)?;
-
-    for tab_id in tab_ids {
-        let term = tantivy::Term::from_field_text(
-            index.schema().get_field("tab_id").unwrap(),
-            &tab_id.to_string(),
-        );
-        index_writer.delete_term(term);
-    }
-
-    index_writer.commit()?;
-
-    Ok(())
-}
-
-pub fn clear_doc_index(index: &Index, doc_id: &Uuid) -> tantivy::Result<()> {
-    let mut index_writer = index.writer(50_000_000)?;
-
-    let term = tantivy::Term::from_field_text(
-        index.schema().get_field("doc_id").unwrap(),
-        &doc_id.to_string(),
-    );
-    index_writer.delete_term(term);
-
-    index_writer.commit()?;
-
-    Ok(())
-}
-
-pub fn fuzzy_search(index: &Index, query: &str) -> tantivy::Result<Vec<NodeSearchLocation>> {
-    let reader = index
-        .reader_builder()
-        .reload_policy(ReloadPolicy::OnCommit)
-        .try_into()?;
-    let searcher = reader.searcher();
-    let normalized_query = query.to_lowercase();
-
-    let schema = index.schema();
-    let text_field = schema.get_field("text").unwrap();
-    let full_text_field = schema.get_field("full_text").unwrap();
-    let doc_id_field = schema.get_field("doc_id").unwrap();
-    let tab_id_field = schema.get_field("tab_id").unwrap();
-    let node_id_field = schema.get_field("node_id").unwrap();
-
-    let text_term = Term::from_field_text(text_field, normalized_query.as_str());
-    let query1 = FuzzyTermQuery::new(text_term, 2, true);
-
-    let full_text_term = Term::from_field_text(full_text_field, normalized_query.as_str());
-    let query2 = FuzzyTermQuery::new(full_text_term, 2, true);
-    let query = BooleanQuery::new(vec![
-        (Occur::Should, Box::new(query1)),
-        (Occur::Should, Box::new(query2)),
-    ]);
-
-    let top_docs = searcher
-        .search(&query, &(TopDocs::with_limit(MAX_SEARCH_RESULTS)))
-        .unwrap();
-    let ids: Vec<NodeSearchLocation> = top_docs
-        .iter()
-        .map(|(_, doc_address)| {
-            let doc = searcher.doc(*doc_address).unwrap();
-            let doc_id_value = doc.get_first(doc_id_field).unwrap();
-            let tab_id_value = doc.get_first(tab_id_field).unwrap();
-            let node_id_value = doc.get_first(node_id_field).unwrap();
-            NodeSearchLocation {
-                doc_id: Uuid::parse_str(doc_id_value.as_text().unwrap()).unwrap(),
-                tab_id: Uuid::parse_str(tab_id_value.as_text().unwrap()).unwrap(),
-                node_id: Uuid::parse_str(node_id_value.as_text().unwrap()).unwrap(),
-            }
-        })
-        .collect();
-    Ok(ids)
-}
-
 fn highlight_search_match_nodes(
     node_ids: &HashSet<ReflectableUuid>,
     velo_border: &mut Query<(&mut Stroke, &VeloShape), With<VeloShape>>,
@@ -292,143 +143,3 @@ fn highlight_search_match_nodes(
         }
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use tempfile::TempDir;
-    use uuid::Uuid;
-
-    use super::*;
-
-    #[test]
-    fn test_fuzzy_search() {
-        // Create a temporary directory for the index
-        let temp_dir = TempDir::new().expect("Failed to create temporary directory");
-
-        // Initialize the index using the temporary directory
-        let index = initialize_search_index(temp_dir.path().to_path_buf());
-        let id1 = Uuid::new_v4();
-        let text1 = "apple".to_string();
-        let id2 = Uuid::new_v4();
-        let text2 = "banana".to_string();
-        let mut node_search_locations = HashMap::new();
-        node_search_locations.insert(
-            NodeSearchLocation {
-                doc_id: id1,
-                tab_id: Uuid::new_v4(),
-                node_id: Uuid::new_v4(),
-            },
-            text1,
-        );
-        node_search_locations.insert(
-            NodeSearchLocation {
-                doc_id: id2,
-                tab_id: Uuid::new_v4(),
-                node_id: Uuid::new_v4(),
-            },
-            text2,
-        );
-        update_search_index(&index, &node_search_locations).unwrap();
-        // Perform fuzzy search and assert the results
-        let query = "appla";
-        let result = fuzzy_search(&index, query).unwrap();
-
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].doc_id, id1);
-        // Clean up the temporary directory
-        temp_dir
-            .close()
-            .expect("Failed to remove temporary directory");
-    }
-
-    #[test]
-    fn test_clear_tab() {
-        // Create a temporary directory for the index
-        let temp_dir = TempDir::new().expect("Failed to create temporary directory");
-
-        // Initialize the index using the temporary directory
-        let index = initialize_search_index(temp_dir.path().to_path_buf());
-        let doc_id = Uuid::new_v4();
-        let tab_id = Uuid::new_v4();
-        let text_1 = "example text 1".to_string();
-        let text_2 = "example text 2".to_string();
-        let mut node_search_locations = HashMap::new();
-        node_search_locations.insert(
-            NodeSearchLocation {
-                doc_id,
-                tab_id,
-                node_id: Uuid::new_v4(),
-            },
-            text_1,
-        );
-        node_search_locations.insert(
-            NodeSearchLocation {
-                doc_id,
-                tab_id,
-                node_id: Uuid::new_v4(),
-            },
-            text_2,
-        );
-        update_search_index(&index, &node_search_locations).unwrap();
-
-        let mut tab_ids = HashSet::new();
-        tab_ids.insert(tab_id);
-        // Clear the tab from the index
-        clear_tabs_index(&index, &tab_ids).unwrap();
-
-        // Perform a search and assert that the tab is not found
-        let query = "example";
-        let result = fuzzy_search(&index, query).unwrap();
-
-        assert_eq!(result.len(), 0);
-
-        // Clean up the temporary directory
-        temp_dir
-            .close()
-            .expect("Failed to remove temporary directory");
-    }
-
-    #[test]
-    fn test_clear_doc() {
-        // Create a temporary directory for the index
-        let temp_dir = TempDir::new().expect("Failed to create temporary directory");
-
-        // Initialize the index using the temporary directory
-        let index = initialize_search_index(temp_dir.path().to_path_buf());
-        let doc_id = Uuid::new_v4();
-        let text_1 = "example text 1".to_string();
-        let text_2 = "example text 2".to_string();
-        let mut node_search_locations = HashMap::new();
-        node_search_locations.insert(
-            NodeSearchLocation {
-                doc_id,
-                tab_id: Uuid::new_v4(),
-                node_id: Uuid::new_v4(),
-            },
-            text_1,
-        );
-        node_search_locations.insert(
-            NodeSearchLocation {
-                doc_id,
-                tab_id: Uuid::new_v4(),
-                node_id: Uuid::new_v4(),
-            },
-            text_2,
-        );
-        update_search_index(&index, &node_search_locations).unwrap();
-
-        // Clear the document from the index
-        clear_doc_index(&index, &doc_id).unwrap();
-
-        // Perform a search and assert that the document is not found
-        let query = "example";
-        let result = fuzzy_search(&index, query).unwrap();
-
-        assert_eq!(result.len(), 0);
-
-        // Clean up the temporary directory
-        temp_dir
-            .close()
-            .expect("Failed to remove temporary directory");
-    }
-}
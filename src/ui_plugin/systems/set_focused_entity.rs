@@ -1,12 +1,13 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
-use super::{ui_helpers::RawText, NodeInteraction, UiState};
+use super::{ui_helpers::RawText, CanvasEdit, NodeInteraction, UiState};
 
 pub fn set_focused_entity(
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
     mut node_interaction_events: EventReader<NodeInteraction>,
     mut ui_state: ResMut<UiState>,
     velo: Query<&RawText, With<RawText>>,
+    mut canvas_edits: EventWriter<CanvasEdit>,
 ) {
     let mut primary_window = windows.single_mut();
 
@@ -41,7 +42,9 @@ pub fn set_focused_entity(
             }
         }
         if event.node_interaction_type == crate::ui_plugin::NodeInteractionType::LeftMouseRelease {
-            ui_state.hold_entity = None;
+            if let Some(id) = ui_state.hold_entity.take() {
+                canvas_edits.send(CanvasEdit { ids: vec![id] });
+            }
         }
     }
 }
@@ -56,7 +56,7 @@ pub fn select_tab_handler(
                 commands.insert_resource(LoadTabRequest {
                     doc_id: current_document,
                     tab_id: selected_tab.id,
-                    drop_last_checkpoint: false,
+                    version: None,
                 });
             }
             Interaction::Hovered => {}
@@ -92,7 +92,9 @@ pub fn add_tab_handler(
                     checkpoints: VecDeque::new(),
                     is_active: true,
                     z_index: 1.,
+                    versions: vec![],
                 });
+                app_state.unsaved_docs.insert(current_document);
                 commands.insert_resource(LoadDocRequest {
                     doc_id: app_state.current_document.unwrap(),
                 });
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicFont;
+use bevy_pkv::PkvStore;
+use bevy_prototype_lyon::prelude::Fill;
+
+use super::ui_helpers::{spawn_modal_with_value, EditTags, FilterByTag, VeloNode, VeloShape};
+use super::{MainPanel, ModalAction};
+use crate::document::{has_tag, load_doc_index};
+use crate::resources::{AppState, FontSystemState, SetNodeTagsRequest};
+use crate::themes::Theme;
+use crate::utils::ReflectableUuid;
+use crate::UiState;
+
+/// Opacity of nodes that don't carry the filtered tag.
+const DIMMED_ALPHA: f32 = 0.2;
+
+/// Edits the tags of the selected node, or of the current document when no node is selected.
+pub fn edit_tags_handler(
+    mut commands: Commands,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<EditTags>)>,
+    mut ui_state: ResMut<UiState>,
+    app_state: Res<AppState>,
+    velo_nodes: Query<&VeloNode>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: ResMut<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    for interaction in &mut query.iter_mut() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let selected_node = ui_state
+            .entity_to_edit
+            .and_then(|id| velo_nodes.iter().find(|node| node.id == id));
+        let (action, tags) = match (selected_node, app_state.current_document) {
+            (Some(node), _) => (ModalAction::EditNodeTags(node.id), node.tags.clone()),
+            (None, Some(doc_id)) => match app_state.docs.get(&doc_id) {
+                Some(doc) => (ModalAction::EditDocTags(doc_id), doc.tags.clone()),
+                None => continue,
+            },
+            (None, None) => continue,
+        };
+        let id = ReflectableUuid::generate();
+        *ui_state = UiState::default();
+        commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+        ui_state.modal_id = Some(id);
+        let entity = spawn_modal_with_value(
+            &mut commands,
+            &theme,
+            &mut cosmic_fonts,
+            font_system_state.0.clone().unwrap(),
+            windows.single(),
+            id,
+            action,
+            tags.join(", "),
+        );
+        commands.entity(main_panel_query.single()).add_child(entity);
+    }
+}
+
+pub fn filter_by_tag_handler(
+    mut commands: Commands,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<FilterByTag>)>,
+    mut ui_state: ResMut<UiState>,
+    app_state: Res<AppState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: ResMut<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    for interaction in &mut query.iter_mut() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let id = ReflectableUuid::generate();
+        *ui_state = UiState::default();
+        commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+        ui_state.modal_id = Some(id);
+        let entity = spawn_modal_with_value(
+            &mut commands,
+            &theme,
+            &mut cosmic_fonts,
+            font_system_state.0.clone().unwrap(),
+            windows.single(),
+            id,
+            ModalAction::FilterByTag,
+            app_state.tag_filter.clone().unwrap_or_default(),
+        );
+        commands.entity(main_panel_query.single()).add_child(entity);
+    }
+}
+
+pub fn should_set_node_tags(request: Option<Res<SetNodeTagsRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn set_node_tags(
+    mut commands: Commands,
+    request: Res<SetNodeTagsRequest>,
+    mut velo_nodes: Query<&mut VeloNode>,
+) {
+    for mut node in velo_nodes.iter_mut() {
+        if node.id == request.node_id {
+            node.tags = request.tags.clone();
+        }
+    }
+    commands.remove_resource::<SetNodeTagsRequest>();
+}
+
+/// Narrows the document list down to documents with the filtered tag.
+pub fn filter_doc_list(
+    mut app_state: ResMut<AppState>,
+    mut previous_filter: Local<Option<String>>,
+    pkv: Res<PkvStore>,
+) {
+    if !app_state.is_changed() {
+        return;
+    }
+    if app_state.tag_filter.is_none() {
+        if previous_filter.take().is_some() {
+            let index = load_doc_index(&pkv);
+            let doc_ids: Vec<_> = index
+                .names
+                .keys()
+                .chain(app_state.docs.keys())
+                .copied()
+                .collect();
+            app_state.doc_list_ui.extend(doc_ids);
+        }
+        return;
+    }
+    *previous_filter = app_state.tag_filter.clone();
+    let tag = app_state.tag_filter.as_deref().unwrap();
+    let mut index = load_doc_index(&pkv);
+    // documents in memory may have tags that aren't stored yet
+    for (doc_id, doc) in app_state.docs.iter() {
+        index.tags.insert(*doc_id, doc.tags.clone());
+    }
+    let doc_ids: HashSet<ReflectableUuid> = index
+        .tags
+        .iter()
+        .filter(|(_, tags)| has_tag(tags, tag))
+        .map(|(doc_id, _)| *doc_id)
+        .collect();
+    if app_state.doc_list_ui != doc_ids {
+        app_state.doc_list_ui = doc_ids;
+    }
+}
+
+/// Dims nodes on the canvas that don't have the filtered tag.
+pub fn dim_untagged_nodes(
+    app_state: Res<AppState>,
+    velo_nodes: Query<&VeloNode>,
+    mut shapes: Query<(&Parent, &VeloShape, &mut Fill)>,
+) {
+    for (parent, shape, mut fill) in shapes.iter_mut() {
+        let Ok(node) = velo_nodes.get(parent.get()) else {
+            continue;
+        };
+        let matches = match &app_state.tag_filter {
+            Some(tag) => has_tag(&node.tags, tag),
+            None => true,
+        };
+        let color = shape.pair_color.1;
+        let expected = if matches {
+            color
+        } else {
+            color.with_a(color.a() * DIMMED_ALPHA)
+        };
+        if fill.color != expected {
+            fill.color = expected;
+        }
+    }
+}
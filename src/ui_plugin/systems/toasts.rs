@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+
+use super::ui_helpers::{spawn_toast, spawn_toast_container, Toast, ToastContainer};
+use super::{MainPanel, Notifications};
+use crate::themes::Theme;
+
+pub fn show_notifications(
+    mut commands: Commands,
+    notifications: Res<Notifications>,
+    container_query: Query<Entity, With<ToastContainer>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    theme: Res<Theme>,
+) {
+    if notifications.rx.is_empty() {
+        return;
+    }
+    let container = match container_query.get_single() {
+        Ok(container) => container,
+        Err(_) => {
+            // messages stay queued until the layout is there
+            let Ok(main_panel) = main_panel_query.get_single() else {
+                return;
+            };
+            let container = spawn_toast_container(&mut commands);
+            commands.entity(main_panel).add_child(container);
+            container
+        }
+    };
+    while let Ok(notification) = notifications.rx.try_recv() {
+        let toast = spawn_toast(&mut commands, &theme, &notification);
+        commands.entity(container).add_child(toast);
+    }
+}
+
+/// Toasts go away on their own, or earlier when clicked.
+pub fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast, &Interaction)>,
+) {
+    for (entity, mut toast, interaction) in toasts.iter_mut() {
+        if toast.timer.tick(time.delta()).finished() || *interaction == Interaction::Pressed {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_cosmic_edit::CosmicFont;
+use bevy_prototype_lyon::prelude::Stroke;
+
+use super::ui_helpers::{
+    spawn_modal_with_value, spawn_version_banner, spawn_versions_modal, ModalTop, SaveVersion,
+    ShowVersions, VeloShape, VersionAction, VersionButton, VersionPreviewBanner,
+};
+use super::{image_to_png, CanvasQuery, MainPanel, ModalAction, Notifications};
+use crate::canvas::arrow::components::ArrowMeta;
+use crate::components::Tab;
+use crate::document::{store_image, DiffStatus, TabVersion};
+use crate::resources::{
+    AppState, CanvasHistory, FontSystemState, LoadDocRequest, LoadTabRequest, SaveTabRequest,
+    SaveVersionRequest, VersionPreview,
+};
+use crate::themes::Theme;
+use crate::utils::{get_timestamp, ReflectableUuid};
+use crate::{UiState, MAX_CHECKPOINTS};
+
+/// Outline width of nodes and arrows that differ from the tab while a version is previewed.
+const DIFF_LINE_WIDTH: f32 = 3.;
+
+/// Whether the canvas can be edited, it is read-only while it shows a version.
+pub fn not_previewing(preview: Option<Res<VersionPreview>>) -> bool {
+    preview.is_none()
+}
+
+pub fn should_save_version(request: Option<Res<SaveVersionRequest>>) -> bool {
+    request.is_some()
+}
+
+pub fn remove_save_version_request(world: &mut World) {
+    world.remove_resource::<SaveVersionRequest>().unwrap();
+}
+
+fn active_tab(app_state: &mut AppState) -> Option<(ReflectableUuid, &mut Tab)> {
+    let doc_id = app_state.current_document?;
+    let doc = app_state.docs.get_mut(&doc_id)?;
+    let tab = doc.tabs.iter_mut().find(|tab| tab.is_active)?;
+    Some((doc_id, tab))
+}
+
+/// Asks for a name to keep the canvas of the active tab under.
+pub fn save_version_handler(
+    mut commands: Commands,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<SaveVersion>)>,
+    mut ui_state: ResMut<UiState>,
+    mut app_state: ResMut<AppState>,
+    preview: Option<Res<VersionPreview>>,
+    notifications: Res<Notifications>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cosmic_fonts: ResMut<Assets<CosmicFont>>,
+    font_system_state: ResMut<FontSystemState>,
+    theme: Res<Theme>,
+) {
+    for interaction in &mut query.iter_mut() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if preview.is_some() {
+            notifications.info("Close the preview to save a version");
+            continue;
+        }
+        let Some((_, tab)) = active_tab(&mut app_state) else {
+            continue;
+        };
+        let name = format!("Version {}", tab.versions.len() + 1);
+        let id = ReflectableUuid::generate();
+        *ui_state = UiState::default();
+        commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+        ui_state.modal_id = Some(id);
+        let entity = spawn_modal_with_value(
+            &mut commands,
+            &theme,
+            &mut cosmic_fonts,
+            font_system_state.0.clone().unwrap(),
+            windows.single(),
+            id,
+            ModalAction::SaveVersion,
+            name,
+        );
+        commands.entity(main_panel_query.single()).add_child(entity);
+    }
+}
+
+pub fn show_versions_handler(
+    mut commands: Commands,
+    mut query: Query<&Interaction, (Changed<Interaction>, With<ShowVersions>)>,
+    mut ui_state: ResMut<UiState>,
+    mut app_state: ResMut<AppState>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    theme: Res<Theme>,
+) {
+    for interaction in &mut query.iter_mut() {
+        if *interaction != Interaction::Pressed || ui_state.modal_id.is_some() {
+            continue;
+        }
+        let Some((_, tab)) = active_tab(&mut app_state) else {
+            continue;
+        };
+        let id = ReflectableUuid::generate();
+        *ui_state = UiState::default();
+        commands.insert_resource(bevy_cosmic_edit::ActiveEditor { entity: None });
+        ui_state.modal_id = Some(id);
+        let entity =
+            spawn_versions_modal(&mut commands, &theme, windows.single(), id, &tab.versions);
+        commands.entity(main_panel_query.single()).add_child(entity);
+    }
+}
+
+pub fn save_version(
+    request: Res<SaveVersionRequest>,
+    mut app_state: ResMut<AppState>,
+    images: Res<Assets<Image>>,
+    canvas: CanvasQuery,
+    notifications: Res<Notifications>,
+) {
+    let Some(doc_id) = app_state.current_document else {
+        return;
+    };
+    let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+        return;
+    };
+    let snapshot = canvas.snapshot(|handle| {
+        let image_data = image_to_png(images.get(handle)?)?;
+        Some(store_image(doc, &image_data))
+    });
+    let Some(tab) = doc.tabs.iter_mut().find(|tab| tab.is_active) else {
+        return;
+    };
+    let name = match request.name.as_str() {
+        "" => format!("Version {}", tab.versions.len() + 1),
+        name => name.to_string(),
+    };
+    tab.versions.push(TabVersion {
+        id: ReflectableUuid::generate(),
+        name: name.clone(),
+        created: get_timestamp() as u64,
+        snapshot,
+    });
+    app_state.unsaved_docs.insert(doc_id);
+    notifications.info(format!("Saved version \"{}\"", name));
+}
+
+pub fn version_button_handler(
+    mut commands: Commands,
+    query: Query<(&Interaction, &VersionButton), Changed<Interaction>>,
+    mut app_state: ResMut<AppState>,
+    mut ui_state: ResMut<UiState>,
+    mut history: ResMut<CanvasHistory>,
+    modals: Query<(Entity, &ModalTop)>,
+    preview: Option<Res<VersionPreview>>,
+    notifications: Res<Notifications>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some((doc_id, tab)) = active_tab(&mut app_state) else {
+            continue;
+        };
+        let tab_id = tab.id;
+        let Some(version) = tab
+            .versions
+            .iter()
+            .find(|version| version.id == button.version_id)
+            .cloned()
+        else {
+            continue;
+        };
+        if button.action != VersionAction::Preview
+            && preview.as_ref().map(|preview| preview.tab_id) != Some(tab_id)
+        {
+            continue;
+        }
+        match button.action {
+            VersionAction::Preview => {
+                for (entity, modal) in modals.iter() {
+                    if modal.action == ModalAction::Versions {
+                        commands.entity(entity).despawn_recursive();
+                        ui_state.modal_id = None;
+                    }
+                }
+                // keeps the canvas unless a version is already shown on it
+                commands.insert_resource(SaveTabRequest { doc_id, tab_id });
+                commands.insert_resource(LoadTabRequest {
+                    doc_id,
+                    tab_id,
+                    version: Some(version.id),
+                });
+            }
+            VersionAction::Restore => {
+                // the last checkpoint holds the canvas from before the preview
+                let current = tab.checkpoints.back().cloned().unwrap_or_default();
+                tab.versions.push(TabVersion {
+                    id: ReflectableUuid::generate(),
+                    name: format!("Before restoring \"{}\"", version.name),
+                    created: get_timestamp() as u64,
+                    snapshot: current,
+                });
+                if (tab.checkpoints.len() as i32) > MAX_CHECKPOINTS {
+                    tab.checkpoints.pop_front();
+                }
+                tab.checkpoints.push_back(version.snapshot);
+                // edits recorded for undo don't apply to the restored canvas
+                history.remove_tab(&tab_id);
+                app_state.unsaved_docs.insert(doc_id);
+                commands.insert_resource(LoadTabRequest {
+                    doc_id,
+                    tab_id,
+                    version: None,
+                });
+                notifications.info(format!("Restored \"{}\"", version.name));
+            }
+            VersionAction::Fork => {
+                tab.is_active = false;
+                let Some(doc) = app_state.docs.get_mut(&doc_id) else {
+                    continue;
+                };
+                let z_index = version
+                    .snapshot
+                    .nodes
+                    .iter()
+                    .map(|node| node.z)
+                    .fold(1., f32::max);
+                doc.tabs.push(Tab {
+                    id: ReflectableUuid::generate(),
+                    name: version.name.clone(),
+                    checkpoints: VecDeque::from([version.snapshot]),
+                    is_active: true,
+                    z_index,
+                    versions: vec![],
+                });
+                app_state.unsaved_docs.insert(doc_id);
+                commands.insert_resource(LoadDocRequest { doc_id });
+            }
+            VersionAction::ClosePreview => {
+                commands.insert_resource(LoadTabRequest {
+                    doc_id,
+                    tab_id,
+                    version: None,
+                });
+            }
+        }
+    }
+}
+
+/// Shows the preview bar while a version is on the canvas.
+pub fn update_version_banner(
+    mut commands: Commands,
+    preview: Option<Res<VersionPreview>>,
+    banners: Query<Entity, With<VersionPreviewBanner>>,
+    main_panel_query: Query<Entity, With<MainPanel>>,
+    theme: Res<Theme>,
+) {
+    let changed = match &preview {
+        Some(preview) => preview.is_changed(),
+        None => !banners.is_empty(),
+    };
+    if !changed {
+        return;
+    }
+    for entity in banners.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(preview) = preview {
+        let banner = spawn_version_banner(&mut commands, &theme, preview.version_id, &preview.name);
+        commands.entity(main_panel_query.single()).add_child(banner);
+    }
+}
+
+/// Outlines the nodes and arrows of a previewed version that differ from the tab.
+pub fn highlight_version_diff(
+    preview: Option<Res<VersionPreview>>,
+    theme: Res<Theme>,
+    mut shapes: Query<(&VeloShape, &mut Stroke)>,
+    mut arrows: Query<(&ArrowMeta, &mut Stroke), Without<VeloShape>>,
+) {
+    let Some(preview) = preview else {
+        return;
+    };
+    let color = |status: DiffStatus| match status {
+        DiffStatus::Added => theme.version_added,
+        DiffStatus::Changed => theme.version_changed,
+        DiffStatus::Removed => theme.version_removed,
+    };
+    let statuses = shapes.iter_mut().filter_map(|(shape, stroke)| {
+        Some((preview.diff.nodes.get(&shape.id.0).copied()?, stroke))
+    });
+    let arrow_statuses = arrows
+        .iter_mut()
+        .filter_map(|(arrow, stroke)| Some((preview.diff.arrow(arrow)?, stroke)));
+    for (status, mut stroke) in statuses.chain(arrow_statuses) {
+        // selecting a node resets the outlines
+        if stroke.color != color(status) || stroke.options.line_width != DIFF_LINE_WIDTH {
+            stroke.color = color(status);
+            stroke.options.line_width = DIFF_LINE_WIDTH;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::MainCamera;
+    use crate::themes::velo_light;
+    use crate::ui_plugin::{keyboard_input_system, AddRect, CanvasEdit, VeloNode};
+
+    #[test]
+    fn test_preview_is_read_only() {
+        let mut app = App::new();
+        app.add_systems(Update, keyboard_input_system.run_if(not_previewing));
+        app.add_event::<AddRect<(String, Color)>>();
+        app.add_event::<CanvasEdit>();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .add_asset::<Image>();
+        app.init_resource::<AppState>();
+        app.insert_resource(velo_light());
+        app.world.spawn((Window::default(), PrimaryWindow));
+        app.world.spawn((Transform::default(), MainCamera));
+        let id = ReflectableUuid::generate();
+        let node = app.world.spawn(VeloNode { id, tags: vec![] }).id();
+        app.insert_resource(UiState {
+            entity_to_edit: Some(id),
+            ..default()
+        });
+        let mut input = Input::<KeyCode>::default();
+        input.press(KeyCode::Delete);
+        app.insert_resource(input);
+        app.insert_resource(VersionPreview {
+            tab_id: ReflectableUuid::generate(),
+            version_id: ReflectableUuid::generate(),
+            name: "version".to_string(),
+            diff: default(),
+        });
+
+        app.update();
+        assert!(app.world.get_entity(node).is_some());
+        assert!(app.world.resource::<Events<CanvasEdit>>().is_empty());
+
+        app.world.remove_resource::<VersionPreview>();
+        app.update();
+        assert!(app.world.get_entity(node).is_none());
+    }
+}
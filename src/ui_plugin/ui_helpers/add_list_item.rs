@@ -16,7 +16,9 @@ use crate::{
     utils::{bevy_color_to_cosmic, ReflectableUuid},
 };
 
-use super::{DeleteDoc, DocListItemButton, DocListItemContainer, EditableText, GenericButton};
+use super::{
+    DeleteDoc, DocListItemButton, DocListItemContainer, EditableText, GenericButton, UnsavedMarker,
+};
 
 pub fn add_list_item(
     commands: &mut Commands,
@@ -75,6 +77,27 @@ pub fn add_list_item(
         .insert(GenericButton)
         .insert(DocListItemButton { id });
 
+    let unsaved_marker = commands
+        .spawn((
+            TextBundle {
+                visibility: Visibility::Hidden,
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "\u{e061}".to_string(),
+                        style: TextStyle {
+                            font_size: 10.,
+                            color: theme.font,
+                            font: icon_font.clone(),
+                        },
+                    }],
+                    ..default()
+                },
+                ..default()
+            },
+            Label,
+            UnsavedMarker { id },
+        ))
+        .id();
     let del_button = commands
         .spawn((
             ButtonBundle {
@@ -120,6 +143,7 @@ pub fn add_list_item(
         .id();
     commands.entity(del_button).add_child(del_label);
     commands.entity(root).add_child(cosmic_edit);
+    commands.entity(root).add_child(unsaved_marker);
     commands.entity(root).add_child(del_button);
     root
 }
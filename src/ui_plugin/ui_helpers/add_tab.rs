@@ -10,7 +10,7 @@ use crate::{
     utils::{bevy_color_to_cosmic, ReflectableUuid},
 };
 
-use super::{DeleteTab, EditableText, GenericButton, TabButton, TabContainer};
+use super::{DeleteTab, EditableText, GenericButton, TabButton, TabContainer, UnsavedMarker};
 
 pub fn add_tab(
     commands: &mut Commands,
@@ -71,6 +71,27 @@ pub fn add_tab(
         .insert(EditableText { id })
         .insert(GenericButton)
         .insert(TabButton { id });
+    let unsaved_marker = commands
+        .spawn((
+            TextBundle {
+                visibility: Visibility::Hidden,
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "\u{e061}".to_string(),
+                        style: TextStyle {
+                            font_size: 10.,
+                            color: theme.font,
+                            font: icon_font.clone(),
+                        },
+                    }],
+                    ..default()
+                },
+                ..default()
+            },
+            Label,
+            UnsavedMarker { id },
+        ))
+        .id();
     let del_button = commands
         .spawn((
             ButtonBundle {
@@ -121,6 +142,7 @@ pub fn add_tab(
         .id();
     commands.entity(del_button).add_child(del_label);
     commands.entity(root).add_child(cosmic_edit);
+    commands.entity(root).add_child(unsaved_marker);
     commands.entity(root).add_child(del_button);
     root
 }
@@ -1,3 +1,4 @@
+use crate::document::Side;
 use crate::{ui_plugin::NodeType, utils::ReflectableUuid};
 use bevy::prelude::*;
 use bevy_markdown::TextSpanMetadata;
@@ -58,6 +59,44 @@ pub struct ExportToFile;
 #[derive(Component, Clone)]
 pub struct SetWindowProperty;
 
+#[derive(Component, Clone)]
+pub struct EditTags;
+
+#[derive(Component, Clone)]
+pub struct FilterByTag;
+
+#[derive(Component, Clone)]
+pub struct SaveVersion;
+
+#[derive(Component, Clone)]
+pub struct ShowVersions;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionAction {
+    Preview,
+    Restore,
+    Fork,
+    ClosePreview,
+}
+
+#[derive(Component)]
+pub struct VersionButton {
+    pub version_id: ReflectableUuid,
+    pub action: VersionAction,
+}
+
+#[derive(Component)]
+pub struct VersionPreviewBanner;
+
+#[derive(Component)]
+pub struct MergeConflictButton {
+    pub index: usize,
+    pub side: Side,
+}
+
+#[derive(Component)]
+pub struct MergeConflictsPanel;
+
 #[derive(Component, Clone)]
 pub struct ImportFromFile;
 
@@ -79,6 +118,12 @@ pub struct DeleteDoc {
 pub struct TabButton {
     pub id: ReflectableUuid,
 }
+/// Dot shown next to a tab or document name while it has unsaved changes.
+#[derive(Component)]
+pub struct UnsavedMarker {
+    pub id: ReflectableUuid,
+}
+
 #[derive(Component)]
 pub struct TabContainer {
     pub id: ReflectableUuid,
@@ -144,6 +189,7 @@ pub struct VeloShape {
 #[derive(Component, Default, Debug)]
 pub struct VeloNode {
     pub id: ReflectableUuid,
+    pub tags: Vec<String>,
 }
 
 #[derive(PartialEq, Eq, Clone)]
@@ -204,6 +250,13 @@ pub enum ModalAction {
     LoadFromUrl,
     DeleteDocument,
     DeleteTab,
+    SaveAndExit,
+    RestoreJournal,
+    EditDocTags(ReflectableUuid),
+    EditNodeTags(ReflectableUuid),
+    FilterByTag,
+    SaveVersion,
+    Versions,
 }
 
 impl std::fmt::Display for ModalAction {
@@ -211,9 +264,26 @@ impl std::fmt::Display for ModalAction {
         match self {
             ModalAction::DeleteDocument => write!(f, "delete document"),
             ModalAction::DeleteTab => write!(f, "delete tab"),
-            ModalAction::LoadFromFile => write!(f, "Load from file:"),
+            ModalAction::SaveAndExit => write!(f, "save changes and exit"),
+            ModalAction::RestoreJournal => write!(f, "restore unsaved changes"),
+            ModalAction::EditDocTags(_) => write!(f, "Document tags:"),
+            ModalAction::EditNodeTags(_) => write!(f, "Node tags:"),
+            ModalAction::FilterByTag => write!(f, "Filter by tag:"),
+            ModalAction::SaveVersion => write!(f, "Version name:"),
+            ModalAction::Versions => write!(f, "Versions of this tab"),
+            ModalAction::LoadFromFile => {
+                write!(
+                    f,
+                    "Load from file (.json, .velo, .mmd, .dot, .canvas, .excalidraw or .opml):"
+                )
+            }
             ModalAction::LoadFromUrl => write!(f, "Load from URL:"),
-            ModalAction::SaveToFile => write!(f, "Save to file:"),
+            ModalAction::SaveToFile => {
+                write!(
+                    f,
+                    "Save to file (.json, .velo, .svg, .png, .md, .mmd, .dot, .canvas or .opml):"
+                )
+            }
         }
     }
 }
@@ -238,3 +308,11 @@ pub struct Drawing<T> {
     pub points: Vec<Vec2>,
     pub drawing_color: T,
 }
+
+#[derive(Component)]
+pub struct ToastContainer;
+
+#[derive(Component)]
+pub struct Toast {
+    pub timer: Timer,
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+
+use super::{
+    add_rectangle_txt, spawn_text_button, MergeConflictButton, MergeConflictsPanel, ScrollingList,
+};
+use crate::document::{Conflict, Side};
+use crate::themes::Theme;
+
+/// Panel listing the conflicts of a merge, each with a button per side.
+pub fn spawn_merge_conflicts(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    conflicts: &[Conflict],
+) -> Entity {
+    let panel = commands
+        .spawn((
+            NodeBundle {
+                z_index: ZIndex::Global(1),
+                border_color: theme.btn_border.into(),
+                background_color: theme.modal_bg.into(),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    width: Val::Px(520.),
+                    max_height: Val::Px(300.),
+                    flex_direction: FlexDirection::Column,
+                    border: UiRect::all(Val::Px(1.)),
+                    padding: UiRect::all(Val::Px(8.)),
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            },
+            MergeConflictsPanel,
+        ))
+        .id();
+    let title = commands
+        .spawn(add_rectangle_txt(
+            theme,
+            format!(
+                "{} merge conflicts, keeping mine until picked",
+                conflicts.len()
+            ),
+        ))
+        .id();
+    let container = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                width: Val::Percent(100.),
+                flex_grow: 1.,
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+    let list = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    width: Val::Percent(100.),
+                    row_gap: Val::Px(5.),
+                    ..default()
+                },
+                ..default()
+            },
+            ScrollingList::default(),
+        ))
+        .id();
+    for (index, conflict) in conflicts.iter().enumerate() {
+        let row = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    width: Val::Percent(100.),
+                    column_gap: Val::Px(5.),
+                    ..default()
+                },
+                ..default()
+            })
+            .id();
+        let label = commands
+            .spawn(add_rectangle_txt(theme, conflict.to_string()))
+            .id();
+        let mine = spawn_text_button(
+            commands,
+            theme,
+            "Keep mine",
+            MergeConflictButton {
+                index,
+                side: Side::Local,
+            },
+        );
+        let theirs = spawn_text_button(
+            commands,
+            theme,
+            "Take theirs",
+            MergeConflictButton {
+                index,
+                side: Side::Remote,
+            },
+        );
+        commands.entity(row).push_children(&[label, mine, theirs]);
+        commands.entity(list).add_child(row);
+    }
+    commands.entity(container).add_child(list);
+    commands.entity(panel).push_children(&[title, container]);
+    panel
+}
@@ -25,14 +25,37 @@ pub fn spawn_modal(
     id: ReflectableUuid,
     modal_action: ModalAction,
 ) -> Entity {
-    let width = 350.;
-    let height = 250.;
     let default_value = match modal_action {
         ModalAction::SaveToFile => "./velo.json".to_string(),
         ModalAction::LoadFromFile => "./velo.json".to_string(),
         ModalAction::LoadFromUrl => "https://gist..".to_string(),
         _ => "".to_string(),
     };
+    spawn_modal_with_value(
+        commands,
+        theme,
+        cosmic_fonts,
+        cosmic_font_handle,
+        window,
+        id,
+        modal_action,
+        default_value,
+    )
+}
+
+/// Same as `spawn_modal`, with `default_value` filled into the text input.
+pub fn spawn_modal_with_value(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    cosmic_fonts: &mut ResMut<Assets<CosmicFont>>,
+    cosmic_font_handle: Handle<CosmicFont>,
+    window: &Window,
+    id: ReflectableUuid,
+    modal_action: ModalAction,
+    default_value: String,
+) -> Entity {
+    let width = 350.;
+    let height = 250.;
     let top = commands
         .spawn((
             NodeBundle {
@@ -140,7 +163,13 @@ pub fn spawn_modal(
     commands.entity(modal_static).add_child(cancel_button);
 
     let modal_dynamic = match modal_action {
-        ModalAction::SaveToFile | ModalAction::LoadFromFile | ModalAction::LoadFromUrl => {
+        ModalAction::SaveToFile
+        | ModalAction::LoadFromFile
+        | ModalAction::LoadFromUrl
+        | ModalAction::EditDocTags(_)
+        | ModalAction::EditNodeTags(_)
+        | ModalAction::FilterByTag
+        | ModalAction::SaveVersion => {
             let top = commands
                 .spawn(NodeBundle {
                     style: Style {
@@ -214,7 +243,10 @@ pub fn spawn_modal(
             commands.entity(top).add_child(button);
             top
         }
-        ModalAction::DeleteDocument | ModalAction::DeleteTab => {
+        ModalAction::DeleteDocument
+        | ModalAction::DeleteTab
+        | ModalAction::SaveAndExit
+        | ModalAction::RestoreJournal => {
             let top = commands
                 .spawn(NodeBundle {
                     style: Style {
@@ -247,6 +279,7 @@ pub fn spawn_modal(
             commands.entity(top).add_child(node);
             top
         }
+        ModalAction::Versions => unreachable!("versions are listed by spawn_versions_modal"),
     };
     let modal = commands
         .spawn((NodeBundle {
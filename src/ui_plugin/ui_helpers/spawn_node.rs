@@ -2,8 +2,10 @@ use bevy_cosmic_edit::{
     spawn_cosmic_edit, ActiveEditor, CosmicEditMeta, CosmicEditSprite, CosmicFont, CosmicMetrics,
     CosmicNode, CosmicText,
 };
-use bevy_markdown::{generate_markdown_lines, BevyMarkdown, BevyMarkdownTheme};
-use bevy_prototype_lyon::prelude::{Fill, Path, Stroke};
+use bevy_markdown::{
+    generate_markdown_lines, BevyMarkdown, BevyMarkdownError, BevyMarkdownLines, BevyMarkdownTheme,
+};
+use bevy_prototype_lyon::prelude::{Fill, Path, PathBuilder, ShapeBundle, Stroke};
 
 use bevy::prelude::*;
 use cosmic_text::AttrsOwned;
@@ -11,10 +13,12 @@ use cosmic_text::AttrsOwned;
 use crate::canvas::shadows::systems::spawn_shadow;
 use crate::canvas::shadows::CustomShadowMaterial;
 use crate::themes::Theme;
-use crate::ui_plugin::NodeType;
+use crate::ui_plugin::{DrawingJsonNode, NodeType};
 use crate::TextPos;
 
-use super::{BevyMarkdownView, InteractiveNode, RawText, ResizeMarker, VeloNode, VeloShape};
+use super::{
+    BevyMarkdownView, Drawing, InteractiveNode, RawText, ResizeMarker, VeloNode, VeloShape,
+};
 use crate::canvas::arrow::components::{ArrowConnect, ArrowConnectPos};
 use crate::utils::{bevy_color_to_cosmic, ReflectableUuid};
 
@@ -30,6 +34,7 @@ pub struct NodeMeta {
     pub text_pos: TextPos,
     pub is_active: bool,
     pub visible: bool,
+    pub tags: Vec<String>,
 }
 
 pub fn spawn_sprite_node(
@@ -66,38 +71,14 @@ pub fn spawn_sprite_node(
                 visibility,
                 ..Default::default()
             },
-            VeloNode { id: item_meta.id },
+            VeloNode {
+                id: item_meta.id,
+                tags: item_meta.tags.clone(),
+            },
         ))
         .id();
 
-    let points = [
-        Vec2::new(-width / 2., -height / 2.),
-        Vec2::new(-width / 2., height / 2.),
-        Vec2::new(width / 2., height / 2.),
-        Vec2::new(width / 2., -height / 2.),
-    ];
-
-    let path: Path = match item_meta.node_type {
-        NodeType::Rect => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-            &bevy_prototype_lyon::shapes::RoundedPolygon {
-                points: points.into_iter().collect(),
-                closed: true,
-                radius: 10.,
-            },
-        ),
-        NodeType::Paper => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-            &bevy_prototype_lyon::shapes::Polygon {
-                points: points.into_iter().collect(),
-                closed: true,
-            },
-        ),
-        NodeType::Circle => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
-            &bevy_prototype_lyon::shapes::Circle {
-                radius: width / 2.,
-                center: Vec2::new(0., 0.),
-            },
-        ),
-    };
+    let path = node_path(&item_meta.node_type, width, height);
     let has_border = item_meta.node_type != NodeType::Paper;
     let is_transparent = item_meta.pair_bg_color.clone().1 == Color::NONE;
     let shape = commands
@@ -127,24 +108,12 @@ pub fn spawn_sprite_node(
         ))
         .id();
 
-    let mut attrs = cosmic_text::Attrs::new();
-    attrs = attrs.family(cosmic_text::Family::Name(theme.font_name.as_str()));
-    attrs = attrs.color(bevy_color_to_cosmic(theme.font));
+    let attrs = node_text_attrs(theme);
     let (text, span_metadata) = match item_meta.is_active {
         true => (CosmicText::OneStyle(item_meta.text.clone()), vec![]),
         false => {
-            let markdown_theme = BevyMarkdownTheme {
-                code_theme: theme.code_theme.clone(),
-                code_default_lang: theme.code_default_lang.clone(),
-                link: bevy_color_to_cosmic(theme.link),
-                inline_code: bevy_color_to_cosmic(theme.inline_code),
-            };
-            let markdown_lines = generate_markdown_lines(BevyMarkdown {
-                text: item_meta.text.clone(),
-                attrs: AttrsOwned::new(attrs),
-                markdown_theme,
-            })
-            .expect("should handle markdown convertion");
+            let markdown_lines =
+                markdown_lines(theme, &item_meta.text).expect("should handle markdown convertion");
             (
                 CosmicText::MultiStyle(markdown_lines.lines),
                 markdown_lines.span_metadata,
@@ -179,7 +148,7 @@ pub fn spawn_sprite_node(
         bg: Color::NONE,
         bg_image: item_meta.image,
         readonly: !item_meta.is_active,
-        attrs: AttrsOwned::new(attrs),
+        attrs,
     };
     let cosmic_edit = spawn_cosmic_edit(commands, cosmic_fonts, cosmic_edit_meta);
     commands
@@ -265,6 +234,91 @@ pub fn spawn_sprite_node(
     top
 }
 
+pub fn spawn_drawing(commands: &mut Commands, drawing: DrawingJsonNode<(String, Color)>) -> Entity {
+    let mut path_builder = PathBuilder::new();
+    let mut points_iter = drawing.points.iter();
+    if let Some(start) = points_iter.next() {
+        path_builder.move_to(*start);
+        path_builder.line_to(*start);
+    }
+    for point in points_iter {
+        path_builder.line_to(*point);
+    }
+    commands
+        .spawn((
+            ShapeBundle {
+                path: path_builder.build(),
+                transform: Transform::from_xyz(drawing.x, drawing.y, drawing.z),
+                ..Default::default()
+            },
+            Stroke::new(drawing.drawing_color.1, drawing.width),
+            Drawing {
+                id: drawing.id,
+                points: drawing.points,
+                drawing_color: drawing.drawing_color,
+            },
+            InteractiveNode,
+        ))
+        .id()
+}
+
+pub fn node_text_attrs(theme: &Theme) -> AttrsOwned {
+    let mut attrs = cosmic_text::Attrs::new();
+    attrs = attrs.family(cosmic_text::Family::Name(theme.font_name.as_str()));
+    attrs = attrs.color(bevy_color_to_cosmic(theme.font));
+    AttrsOwned::new(attrs)
+}
+
+/// Node text styled from its markdown, as nodes show it when they aren't edited.
+pub fn markdown_lines(
+    theme: &Theme,
+    text: &str,
+) -> Result<BevyMarkdownLines, Vec<BevyMarkdownError>> {
+    let markdown_theme = BevyMarkdownTheme {
+        code_theme: theme.code_theme.clone(),
+        code_default_lang: theme.code_default_lang.clone(),
+        link: bevy_color_to_cosmic(theme.link),
+        inline_code: bevy_color_to_cosmic(theme.inline_code),
+    };
+    generate_markdown_lines(BevyMarkdown {
+        text: text.to_string(),
+        attrs: node_text_attrs(theme),
+        markdown_theme,
+    })
+}
+
+/// Outline of a node shape centered on the node position.
+pub fn node_path(node_type: &NodeType, width: f32, height: f32) -> Path {
+    let points = [
+        Vec2::new(-width / 2., -height / 2.),
+        Vec2::new(-width / 2., height / 2.),
+        Vec2::new(width / 2., height / 2.),
+        Vec2::new(width / 2., -height / 2.),
+    ];
+
+    match node_type {
+        NodeType::Rect => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
+            &bevy_prototype_lyon::shapes::RoundedPolygon {
+                points: points.into_iter().collect(),
+                closed: true,
+                radius: 10.,
+            },
+        ),
+        NodeType::Paper => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
+            &bevy_prototype_lyon::shapes::Polygon {
+                points: points.into_iter().collect(),
+                closed: true,
+            },
+        ),
+        NodeType::Circle => bevy_prototype_lyon::prelude::GeometryBuilder::build_as(
+            &bevy_prototype_lyon::shapes::Circle {
+                radius: width / 2.,
+                center: Vec2::new(0., 0.),
+            },
+        ),
+    }
+}
+
 fn spawn_resize_marker(
     commands: &mut Commands,
     theme: &Res<Theme>,
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{Toast, ToastContainer};
+use crate::themes::Theme;
+use crate::ui_plugin::{Notification, NotificationKind};
+
+const INFO_TOAST_DURATION: Duration = Duration::from_secs(4);
+const ERROR_TOAST_DURATION: Duration = Duration::from_secs(8);
+
+/// Column in the bottom right corner that toasts are stacked in.
+pub fn spawn_toast_container(commands: &mut Commands) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                z_index: ZIndex::Global(2),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::FlexEnd,
+                    right: Val::Px(20.),
+                    bottom: Val::Px(60.),
+                    row_gap: Val::Px(8.),
+                    max_width: Val::Px(400.),
+                    ..default()
+                },
+                ..default()
+            },
+            ToastContainer,
+        ))
+        .id()
+}
+
+pub fn spawn_toast(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    notification: &Notification,
+) -> Entity {
+    let (border_color, duration) = match notification.kind {
+        NotificationKind::Info => (theme.btn_border, INFO_TOAST_DURATION),
+        NotificationKind::Error => (theme.del_button, ERROR_TOAST_DURATION),
+    };
+    let toast = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(10.)),
+                    border: UiRect::left(Val::Px(4.)),
+                    ..default()
+                },
+                background_color: theme.modal_bg.into(),
+                border_color: border_color.into(),
+                ..default()
+            },
+            Toast {
+                timer: Timer::new(duration, TimerMode::Once),
+            },
+            Interaction::default(),
+        ))
+        .id();
+    let text = commands
+        .spawn(TextBundle::from_section(
+            notification.message.clone(),
+            TextStyle {
+                font_size: theme.font_size,
+                color: theme.font,
+                ..default()
+            },
+        ))
+        .id();
+    commands.entity(toast).add_child(text);
+    toast
+}
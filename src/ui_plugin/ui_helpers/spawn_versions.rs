@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+
+use super::{
+    add_rectangle_txt, GenericButton, ModalAction, ModalCancel, ModalTop, ScrollingList,
+    VersionAction, VersionButton, VersionPreviewBanner,
+};
+use crate::document::{format_timestamp, TabVersion};
+use crate::{themes::Theme, utils::ReflectableUuid};
+
+/// Lists the versions of the active tab, newest first, each with a button to preview it.
+pub fn spawn_versions_modal(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    window: &Window,
+    id: ReflectableUuid,
+    versions: &[TabVersion],
+) -> Entity {
+    let top = commands
+        .spawn((
+            NodeBundle {
+                z_index: ZIndex::Global(1),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(window.width() / 2. - 250.),
+                    bottom: Val::Px(window.height() / 2. - 100.),
+                    width: Val::Px(450.),
+                    height: Val::Px(350.),
+                    ..default()
+                },
+                background_color: theme.shadow.into(),
+                ..default()
+            },
+            ModalTop {
+                id,
+                action: ModalAction::Versions,
+            },
+        ))
+        .id();
+    let modal = commands
+        .spawn(NodeBundle {
+            border_color: theme.btn_border.into(),
+            background_color: theme.modal_bg.into(),
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                border: UiRect::all(Val::Px(1.)),
+                padding: UiRect::all(Val::Px(10.)),
+                row_gap: Val::Px(10.),
+                position_type: PositionType::Absolute,
+                left: Val::Px(-3.),
+                top: Val::Px(-3.),
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+    let title = commands
+        .spawn(add_rectangle_txt(theme, ModalAction::Versions.to_string()))
+        .id();
+    let container = commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                width: Val::Percent(100.),
+                flex_grow: 1.,
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+    let list = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    width: Val::Percent(100.),
+                    row_gap: Val::Px(5.),
+                    ..default()
+                },
+                ..default()
+            },
+            ScrollingList::default(),
+        ))
+        .id();
+    if versions.is_empty() {
+        let empty = commands
+            .spawn(add_rectangle_txt(
+                theme,
+                "No versions yet, keep one with Save Version".to_string(),
+            ))
+            .id();
+        commands.entity(list).add_child(empty);
+    }
+    for version in versions.iter().rev() {
+        let row = commands
+            .spawn(NodeBundle {
+                style: Style {
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    width: Val::Percent(100.),
+                    ..default()
+                },
+                ..default()
+            })
+            .id();
+        let label = commands
+            .spawn(add_rectangle_txt(
+                theme,
+                format!(
+                    "{}  {} UTC",
+                    version.name,
+                    format_timestamp(version.created)
+                ),
+            ))
+            .id();
+        let preview = spawn_text_button(
+            commands,
+            theme,
+            "Preview",
+            VersionButton {
+                version_id: version.id,
+                action: VersionAction::Preview,
+            },
+        );
+        commands.entity(row).push_children(&[label, preview]);
+        commands.entity(list).add_child(row);
+    }
+    let close = spawn_text_button(commands, theme, "Close", ModalCancel { id });
+    commands.entity(container).add_child(list);
+    commands
+        .entity(modal)
+        .push_children(&[title, container, close]);
+    commands.entity(top).add_child(modal);
+    top
+}
+
+/// Bar shown above the canvas while a version is previewed, with a legend of the diff colors.
+pub fn spawn_version_banner(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    version_id: ReflectableUuid,
+    name: &str,
+) -> Entity {
+    let banner = commands
+        .spawn((
+            NodeBundle {
+                z_index: ZIndex::Global(1),
+                border_color: theme.btn_border.into(),
+                background_color: theme.modal_bg.into(),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.),
+                    left: Val::Percent(30.),
+                    align_items: AlignItems::Center,
+                    border: UiRect::all(Val::Px(1.)),
+                    padding: UiRect::all(Val::Px(8.)),
+                    column_gap: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            },
+            VersionPreviewBanner,
+        ))
+        .id();
+    let section = |text: String, color: Color| {
+        TextSection::new(
+            text,
+            TextStyle {
+                font_size: theme.font_size,
+                color,
+                ..default()
+            },
+        )
+    };
+    let legend = commands
+        .spawn(TextBundle::from_sections([
+            section(format!("Preview of \"{}\", read-only. ", name), theme.font),
+            section("Added".to_string(), theme.version_added),
+            section(", ".to_string(), theme.font),
+            section("changed".to_string(), theme.version_changed),
+            section(" and ".to_string(), theme.font),
+            section("removed".to_string(), theme.version_removed),
+            section(" since.".to_string(), theme.font),
+        ]))
+        .id();
+    commands.entity(banner).add_child(legend);
+    for (label, action) in [
+        ("Restore", VersionAction::Restore),
+        ("Fork", VersionAction::Fork),
+        ("Close", VersionAction::ClosePreview),
+    ] {
+        let button =
+            spawn_text_button(commands, theme, label, VersionButton { version_id, action });
+        commands.entity(banner).add_child(button);
+    }
+    banner
+}
+
+/// Button with a text label, carrying `component` to tell which button was pressed.
+pub fn spawn_text_button(
+    commands: &mut Commands,
+    theme: &Res<Theme>,
+    label: &str,
+    component: impl Component,
+) -> Entity {
+    commands
+        .spawn((
+            ButtonBundle {
+                border_color: theme.btn_border.into(),
+                background_color: theme.ok_cancel_bg.into(),
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    border: UiRect::all(Val::Px(1.)),
+                    padding: UiRect::all(Val::Px(5.)),
+                    ..default()
+                },
+                ..default()
+            },
+            GenericButton,
+            component,
+        ))
+        .with_children(|builder| {
+            builder.spawn(add_rectangle_txt(theme, label.to_string()));
+        })
+        .id()
+}
@@ -16,6 +16,15 @@ pub use add_tab::*;
 #[path = "add_list_item.rs"]
 mod add_list_item;
 pub use add_list_item::*;
+#[path = "spawn_toast.rs"]
+mod spawn_toast;
+pub use spawn_toast::*;
+#[path = "spawn_versions.rs"]
+mod spawn_versions;
+pub use spawn_versions::*;
+#[path = "spawn_merge_conflicts.rs"]
+mod spawn_merge_conflicts;
+pub use spawn_merge_conflicts::*;
 
 pub fn add_rectangle_txt(theme: &Res<Theme>, text: String) -> TextBundle {
     let text_style = TextStyle {
@@ -6,13 +6,34 @@ use serde::{Deserialize, Serialize};
 use crate::resources::AppState;
 use crate::ui_plugin::TextPos;
 
-use std::collections::HashMap;
 use std::{fs, path::PathBuf};
 use uuid::Uuid;
 
 use bevy_pkv::PkvStore;
 
-use crate::{components::Doc, ui_plugin::MAX_SAVED_DOCS_IN_MEMORY};
+use crate::document::{load_stored_doc, repair_doc};
+use crate::ui_plugin::{Notifications, MAX_SAVED_DOCS_IN_MEMORY};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{APP_NAME, ORG_NAME};
+
+/// Fonts bundled with velo, regular face first.
+pub const VELO_FONTS: [&[u8]; 6] = [
+    include_bytes!("../assets/fonts/VictorMono-Regular.ttf"),
+    include_bytes!("../assets/fonts/VictorMono-Italic.ttf"),
+    include_bytes!("../assets/fonts/VictorMono-Bold.ttf"),
+    include_bytes!("../assets/fonts/VictorMono-BoldItalic.ttf"),
+    include_bytes!("../assets/fonts/VictorMono-Medium.ttf"),
+    include_bytes!("../assets/fonts/VictorMono-SemiBold.ttf"),
+];
+
+/// Directory that holds the document store, the search index and the journal.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_dir() -> PathBuf {
+    match directories::ProjectDirs::from("", ORG_NAME, APP_NAME) {
+        Some(dirs) => dirs.data_dir().to_path_buf(),
+        None => PathBuf::from("."),
+    }
+}
 
 #[derive(Clone, Reflect, Default, Debug, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[reflect_value]
@@ -47,28 +68,51 @@ pub fn load_doc_to_memory(
     doc_id: ReflectableUuid,
     app_state: &mut ResMut<AppState>,
     pkv: &mut ResMut<PkvStore>,
+    notifications: &Notifications,
 ) {
     if app_state.docs.contains_key(&doc_id) {
         return;
     }
-    if let Ok(docs) = pkv.get::<HashMap<ReflectableUuid, Doc>>("docs") {
-        if docs.contains_key(&doc_id) {
-            let keys = app_state.docs.keys().cloned().collect::<Vec<_>>();
-            while (app_state.docs.len() as i32) >= MAX_SAVED_DOCS_IN_MEMORY {
-                app_state.docs.remove(&keys[0]);
+    if let Some(doc) = load_stored_doc(pkv, doc_id) {
+        match doc {
+            Ok(mut doc) => {
+                // unsaved changes only live in memory, so those docs are kept
+                let evictable = app_state
+                    .docs
+                    .keys()
+                    .filter(|id| {
+                        Some(**id) != app_state.current_document
+                            && !app_state.has_unsaved_changes(**id)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for id in evictable {
+                    if (app_state.docs.len() as i32) < MAX_SAVED_DOCS_IN_MEMORY {
+                        break;
+                    }
+                    app_state.docs.remove(&id);
+                }
+                let issues = repair_doc(&mut doc);
+                notifications.repaired(&doc.name, &issues);
+                if !issues.is_empty() {
+                    app_state.unsaved_docs.insert(doc_id);
+                }
+                app_state.docs.insert(doc_id, doc);
             }
-            app_state
-                .docs
-                .insert(doc_id, docs.get(&doc_id).unwrap().clone());
-        } else {
-            error!("Document not found in pkv");
+            Err(e) => notifications.error(format!("Failed to load document {:?}: {}", doc_id, e)),
         }
+    } else {
+        error!("Document not found in pkv");
     }
 }
 
 #[derive(Debug, Default)]
 pub struct Config {
     pub github_access_token: Option<String>,
+    /// Most canvas edits per tab that can be undone, unlimited when not set.
+    pub undo_limit: Option<usize>,
+    /// Folder of document files to show in the doc list and keep in sync.
+    pub sync_folder: Option<PathBuf>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -83,6 +127,18 @@ pub fn read_config_file() -> Option<Config> {
             config.github_access_token = Some(token_str.to_owned());
         }
     }
+    if let Some(limit) = config_value.get("undo_limit") {
+        config.undo_limit = limit.as_integer().and_then(|limit| limit.try_into().ok());
+    }
+    if let Some(folder) = config_value
+        .get("sync_folder")
+        .and_then(|folder| folder.as_str())
+    {
+        config.sync_folder = Some(match folder.strip_prefix("~/") {
+            Some(relative) => PathBuf::from(&home_dir).join(relative),
+            None => PathBuf::from(folder),
+        });
+    }
     Some(config)
 }
 
@@ -104,6 +160,26 @@ impl From<CosmicTextPos> for TextPos {
     }
 }
 
+/// Body of a successful HTTP response, or a message describing why the request failed.
+pub fn response_text(result: Result<ehttp::Response, ehttp::Error>) -> Result<String, String> {
+    let response = result?;
+    if !response.ok {
+        return Err(format!("{} {}", response.status, response.status_text));
+    }
+    response
+        .text()
+        .ok_or_else(|| "response is not text".to_string())
+}
+
+/// Body of a successful HTTP response as bytes, for downloads that may not be text.
+pub fn response_bytes(result: Result<ehttp::Response, ehttp::Error>) -> Result<Vec<u8>, String> {
+    let response = result?;
+    if !response.ok {
+        return Err(format!("{} {}", response.status, response.status_text));
+    }
+    Ok(response.bytes)
+}
+
 pub fn bevy_color_to_cosmic(color: bevy::prelude::Color) -> cosmic_text::Color {
     cosmic_text::Color::rgba(
         (color.r() * 255.) as u8,